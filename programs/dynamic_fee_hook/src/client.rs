@@ -0,0 +1,100 @@
+//! Host-side helpers for reading dynamic fee hook accounts
+//!
+//! Compiled only with the `client` feature so none of this ends up in the on-chain program.
+
+use anchor_lang::{prelude::*, Discriminator};
+
+use crate::{DynamicFeeError, DynamicFeeStats, VelocityWindows};
+
+/// Decoded fee stats account, including the windows stored after `DynamicFeeStats`
+#[derive(Clone)]
+pub struct FeeStatsSnapshot {
+    pub stats: DynamicFeeStats,
+    /// Minute windows tracked, with the 0 of older accounts read as the default
+    pub window_count: u8,
+    /// Transfer counts per minute window, a ring indexed like `stats.current_minute_slot`
+    pub recent_transfers: Vec<u64>,
+    /// Volumes per minute window, in the same order as `recent_transfers`
+    pub recent_volumes: Vec<u64>,
+}
+
+/// Decode the data of a fee stats account exactly as the hook lays it out
+///
+/// Takes the raw account bytes, discriminator included, at any alignment. Accounts created
+/// before the struct last grew decode with the newer fields zeroed, as the hook reads them
+/// until they are resized.
+pub fn decode_fee_stats(data: &[u8]) -> Result<FeeStatsSnapshot> {
+    require!(
+        data.len() >= 8 && data[..8] == *DynamicFeeStats::DISCRIMINATOR,
+        ErrorCode::AccountDiscriminatorMismatch
+    );
+
+    let mut padded = data.to_vec();
+    padded.resize(data.len().max(DynamicFeeStats::LEN), 0);
+    let (head, extra) = padded.split_at(DynamicFeeStats::LEN);
+
+    let stats: DynamicFeeStats = bytemuck::try_pod_read_unaligned(&head[8..])
+        .map_err(|_| DynamicFeeError::InvalidAccountSize)?;
+    let windows = VelocityWindows::load(&stats, extra)?;
+
+    Ok(FeeStatsSnapshot {
+        stats,
+        window_count: stats.window_count(),
+        recent_transfers: windows.transfers,
+        recent_volumes: windows.volumes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytemuck::Zeroable;
+
+    fn account_data(stats: &DynamicFeeStats, windows: &VelocityWindows) -> Vec<u8> {
+        let mut stats = *stats;
+        let mut data = vec![0u8; DynamicFeeStats::space(stats.window_count())];
+        data[..8].copy_from_slice(DynamicFeeStats::DISCRIMINATOR);
+        let mut extra = data[DynamicFeeStats::LEN..].to_vec();
+        windows.store(&mut stats, &mut extra);
+        data[8..DynamicFeeStats::LEN].copy_from_slice(bytemuck::bytes_of(&stats));
+        data[DynamicFeeStats::LEN..].copy_from_slice(&extra);
+        data
+    }
+
+    #[test]
+    fn test_decode_fee_stats() {
+        let mut stats = DynamicFeeStats::zeroed();
+        stats.current_fee_basis_points = 120;
+        stats.window_count = 10;
+        stats.current_minute_slot = 7;
+        stats.authority = Pubkey::new_unique();
+        let windows = VelocityWindows {
+            transfers: (1..=10).collect(),
+            volumes: (1..=10).map(|n| n * 1_000).collect(),
+        };
+        let data = account_data(&stats, &windows);
+
+        // Decoded from a misaligned copy, as RPC buffers often are
+        let mut shifted = vec![0u8];
+        shifted.extend_from_slice(&data);
+        let snapshot = decode_fee_stats(&shifted[1..]).unwrap();
+        assert_eq!(snapshot.stats.current_fee_basis_points, 120);
+        assert_eq!(snapshot.stats.authority, stats.authority);
+        assert_eq!(snapshot.window_count, 10);
+        assert_eq!(snapshot.recent_transfers, windows.transfers);
+        assert_eq!(snapshot.recent_volumes, windows.volumes);
+
+        // A legacy account without the last field reads it as zero, with the default windows
+        let mut legacy = DynamicFeeStats::zeroed();
+        legacy.wsol_lamports_per_token = 99;
+        let mut data = account_data(&legacy, &VelocityWindows::empty(6));
+        data.truncate(DynamicFeeStats::LEN - 8);
+        let snapshot = decode_fee_stats(&data).unwrap();
+        assert_eq!(snapshot.stats.wsol_lamports_per_token, 0);
+        assert_eq!(snapshot.window_count, 6);
+
+        // Other accounts are rejected
+        assert!(decode_fee_stats(&[0u8; 16]).is_err());
+        assert!(decode_fee_stats(&data[..4]).is_err());
+    }
+}
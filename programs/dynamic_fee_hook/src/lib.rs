@@ -1,15 +1,24 @@
-use std::{ cell::RefMut, str::FromStr };
-use anchor_lang::{ prelude::*, solana_program::{pubkey::Pubkey, program_error::ProgramError, clock::Clock, sysvar::Sysvar} };
+use std::cell::RefMut;
+use anchor_lang::{
+    prelude::*,
+    solana_program::{pubkey::Pubkey, program_error::ProgramError, clock::Clock, sysvar::Sysvar},
+    system_program::{transfer, Transfer},
+    Discriminator,
+};
+use bytemuck::Zeroable;
 use anchor_spl::{
-    associated_token::AssociatedToken,
-    token::Token,
+    associated_token::{get_associated_token_address, AssociatedToken},
+    token::{spl_token::native_mint, transfer_checked, Token, TransferChecked},
     token_2022::spl_token_2022::{
         extension::{
-            transfer_hook::TransferHookAccount,
+            transfer_hook::{TransferHook as MintTransferHook, TransferHookAccount},
+            BaseStateWithExtensions,
             BaseStateWithExtensionsMut,
             PodStateWithExtensionsMut,
+            StateWithExtensions,
         },
         pod::PodAccount,
+        state::{Account as TokenAccountState, Mint as MintState},
     },
     token_interface::{ Mint, TokenAccount },
 };
@@ -27,6 +36,25 @@ use spl_transfer_hook_interface::{
 // Fee scaling: 0.1% → 0.2% → 0.5% → 1.2% → 3.0% based on transaction velocity
 declare_id!("69VddXVhzGRGh3oU6eKoWEoNMJC8RJX6by1SgcuQfPR9");
 
+#[cfg(any(feature = "client", test))]
+pub mod client;
+
+/// Default weight kept per minute of window age, 0 turns decay off
+/// Every window then counts fully until it rolls over, as before decay existed
+pub const DEFAULT_DECAY_FACTOR_BPS: u16 = 0;
+
+/// Weighted transfers per minute above which each tier starts, when none were set
+pub const DEFAULT_TIER_THRESHOLDS: [u32; 4] = [10, 30, 60, 120];
+
+/// Minute windows tracked when `set_window_count` was never called
+pub const DEFAULT_WINDOW_COUNT: u8 = 6;
+
+/// Most minute windows a fee stats account can track
+pub const MAX_WINDOW_COUNT: u8 = 60;
+
+/// Windows stored inside `DynamicFeeStats`, the rest follow it in the account data
+const INLINE_WINDOWS: usize = 6;
+
 #[error_code]
 pub enum DynamicFeeError {
     #[msg("Math overflow in calculations")]
@@ -37,12 +65,75 @@ pub enum DynamicFeeError {
     FeeCalculationFailed,
     #[msg("Time window update failed")]
     TimeWindowUpdateFailed,
+    #[msg("Fee stats account size too small")]
+    InvalidAccountSize,
+    #[msg("Signer is not the fee stats authority")]
+    InvalidAuthority,
+    #[msg("Invalid fee parameters")]
+    InvalidFeeParameters,
+    #[msg("Sender WSOL balance is too low for the dynamic fee")]
+    InsufficientWsolBalance,
+    #[msg("Sender WSOL account has not approved the delegate for the dynamic fee")]
+    WsolFeeNotApproved,
+    #[msg("Mint still runs this hook")]
+    MintStillInUse,
+    #[msg("Fee stats account is not in the legacy Borsh layout")]
+    NotLegacyFeeStats,
+    #[msg("Fee stats authority is already set")]
+    AuthorityAlreadySet,
+    #[msg("WSOL account for the dynamic fee does not exist")]
+    WsolAccountMissing,
+}
+
+/// Emitted from the transfer hook whenever the dynamic fee changes value
+#[event]
+pub struct FeeTierChanged {
+    pub mint: Pubkey,
+    pub old_fee_bps: u16,
+    pub new_fee_bps: u16,
+    pub total_tpm: u64,
+    pub peak_tps: u16,
+}
+
+/// Emitted when a transfer goes through without its dynamic fee, because the owner's WSOL
+/// account can't pay it
+#[event]
+pub struct DynamicFeeSkipped {
+    pub mint: Pubkey,
+    pub owner: Pubkey,
+    pub fee_lamports: u64,
+}
+
+/// Emitted when the extra account metas of a wound-down mint are closed
+#[event]
+pub struct HookClosed {
+    pub mint: Pubkey,
+    pub destination: Pubkey,
+    pub lamports: u64,
 }
 
 #[program]
 pub mod dynamic_fee_hook {
     use super::*;
 
+    /// Create the fee stats account shared by every mint running this hook, with the signer as
+    /// its authority. The signer must be the hook program's upgrade authority, and it only runs
+    /// while no authority is set, so nobody can claim the account ahead of the deployer.
+    /// Accounts migrated from the Borsh layout have no authority yet and get theirs here as well
+    pub fn initialize_fee_stats(ctx: Context<InitializeFeeStats>) -> Result<()> {
+        let fee_stats_loader = &ctx.accounts.fee_stats;
+        let mut fee_stats = if fee_stats_loader.load().is_err() {
+            fee_stats_loader.load_init()?
+        } else {
+            fee_stats_loader.load_mut()?
+        };
+        require_keys_eq!(fee_stats.authority, Pubkey::default(), DynamicFeeError::AuthorityAlreadySet);
+        fee_stats.authority = ctx.accounts.authority.key();
+
+        msg!("Fee stats authority: {}", fee_stats.authority);
+        Ok(())
+    }
+
     #[interface(spl_transfer_hook_interface::initialize_extra_account_meta_list)]
     pub fn initialize_extra_account_meta_list(
         ctx: Context<InitializeExtraAccountMetaList>
@@ -64,22 +155,46 @@ pub mod dynamic_fee_hook {
         // Validate this is called within a transfer hook context
         check_transfer_state(&ctx)?;
 
-        let fee_stats = &mut ctx.accounts.fee_stats;
+        let fee_stats_info = ctx.accounts.fee_stats.to_account_info();
+        let mut fee_stats_data = fee_stats_info.try_borrow_mut_data()?;
+        let (fee_stats, extra_windows) = split_fee_stats(&mut fee_stats_data)?;
         
-        // Initialize fee stats on first use
-        if fee_stats.total_transfers == 0 {
+        // Initialize fee stats on first use, unless the authority already set parameters
+        if fee_stats.max_fee_basis_points == 0 {
             fee_stats.base_fee_basis_points = 10;  // 0.1%
             fee_stats.current_fee_basis_points = 10;
             fee_stats.max_fee_basis_points = 300;  // 3.0%
+            fee_stats.decay_factor_bps = DEFAULT_DECAY_FACTOR_BPS;
             fee_stats.last_update_timestamp = Clock::get()?.unix_timestamp;
             msg!("Fee stats initialized: base={}bp, max={}bp", 
                  fee_stats.base_fee_basis_points, fee_stats.max_fee_basis_points);
         }
 
         // Update velocity tracking and calculate dynamic fee
+        let old_fee = fee_stats.current_fee_basis_points;
         let current_timestamp = Clock::get()?.unix_timestamp;
-        let current_fee = update_velocity_and_calculate_fee(fee_stats, current_timestamp, amount)?;
+        let mut windows = VelocityWindows::load(fee_stats, extra_windows)?;
+        let (current_fee, total_tpm) =
+            update_velocity_and_calculate_fee(fee_stats, &mut windows, current_timestamp, amount)?;
+        windows.store(fee_stats, extra_windows);
+
+        if current_fee != old_fee {
+            emit!(FeeTierChanged {
+                mint: ctx.accounts.mint.key(),
+                old_fee_bps: old_fee,
+                new_fee_bps: current_fee,
+                total_tpm,
+                peak_tps: fee_stats.peak_tps,
+            });
+        }
         
+        let fee = wsol_fee(amount, current_fee, fee_stats.wsol_lamports_per_token, ctx.accounts.mint.decimals);
+        if fee > 0 && collect_wsol_fee(&ctx, fee)? {
+            fee_stats.total_fees_collected = fee_stats.total_fees_collected
+                .checked_add(fee)
+                .ok_or(DynamicFeeError::MathOverflow)?;
+        }
+
         // Update totals with proper error handling
         fee_stats.total_transfers = fee_stats.total_transfers
             .checked_add(1)
@@ -88,9 +203,189 @@ pub mod dynamic_fee_hook {
             .checked_add(amount)
             .ok_or(DynamicFeeError::MathOverflow)?;
 
-        msg!("Transfer #{}: amount={}, fee={}bp", 
-             fee_stats.total_transfers, amount, current_fee);
+        msg!("Transfer #{}: amount={}, fee={}bp ({} lamports WSOL)",
+             fee_stats.total_transfers, amount, current_fee, fee);
+
+        Ok(())
+    }
+
+    /// Update the fee parameters
+    /// Only callable by the fee stats authority
+    /// 
+    /// # Arguments
+    /// * `base_fee_basis_points` - Fee at low velocity, tiers scale from it
+    /// * `max_fee_basis_points` - Cap on the dynamic fee
+    /// * `decay_factor_bps` - Weight kept per minute of window age
+    /// * `fee_dwell_seconds` - Time after the last fee increase before the fee may decrease
+    /// * `tier_thresholds` - Weighted transfers per minute above which the 2x, 5x, 12x and max
+    ///   tiers start, increasing. All zero keeps `DEFAULT_TIER_THRESHOLDS`
+    pub fn set_fee_parameters(
+        ctx: Context<UpdateFeeStats>,
+        base_fee_basis_points: u16,
+        max_fee_basis_points: u16,
+        decay_factor_bps: u16,
+        fee_dwell_seconds: u32,
+        tier_thresholds: [u32; 4],
+    ) -> Result<()> {
+        require!(
+            base_fee_basis_points > 0
+                && base_fee_basis_points <= max_fee_basis_points
+                && max_fee_basis_points <= 10_000
+                && decay_factor_bps <= 10_000
+                && valid_tier_thresholds(&tier_thresholds),
+            DynamicFeeError::InvalidFeeParameters
+        );
+
+        let mut fee_stats = ctx.accounts.fee_stats.load_mut()?;
+        fee_stats.base_fee_basis_points = base_fee_basis_points;
+        fee_stats.max_fee_basis_points = max_fee_basis_points;
+        fee_stats.decay_factor_bps = decay_factor_bps;
+        fee_stats.fee_dwell_seconds = fee_dwell_seconds;
+        fee_stats.tier_thresholds = tier_thresholds;
+        fee_stats.current_fee_basis_points = fee_stats
+            .current_fee_basis_points
+            .clamp(base_fee_basis_points, max_fee_basis_points);
+
+        msg!("Fee parameters updated: base={}bp, max={}bp, decay={}bp, dwell={}s, tiers={:?}",
+             base_fee_basis_points, max_fee_basis_points, decay_factor_bps, fee_dwell_seconds,
+             fee_stats.tier_thresholds());
+        Ok(())
+    }
+
+    /// Set the price the dynamic fee is charged at, in lamports of WSOL per whole token
+    /// The fee is `fee_bps` of the tokens transferred, so a transfer of `amount` base units pays
+    /// `amount * fee_bps / 10_000 * wsol_lamports_per_token / 10^decimals` lamports. The rate is
+    /// shared by every mint running this hook. 0, the default, charges nothing
+    /// Only callable by the fee stats authority
+    pub fn set_fee_rate(ctx: Context<UpdateFeeStats>, wsol_lamports_per_token: u64) -> Result<()> {
+        ctx.accounts.fee_stats.load_mut()?.wsol_lamports_per_token = wsol_lamports_per_token;
+
+        msg!("Dynamic fee rate: {} lamports per token", wsol_lamports_per_token);
+        Ok(())
+    }
+
+    /// Hand the fee stats authority to a new key
+    /// Only callable by the current fee stats authority
+    pub fn transfer_hook_authority(ctx: Context<UpdateFeeStats>, new_authority: Pubkey) -> Result<()> {
+        ctx.accounts.fee_stats.load_mut()?.authority = new_authority;
 
+        msg!("Fee stats authority transferred to {}", new_authority);
+        Ok(())
+    }
+
+    /// Grow or shrink the fee stats account, never below what its velocity windows need
+    pub fn resize_fee_stats(ctx: Context<ResizeFeeStats>, _new_size: u32) -> Result<()> {
+        msg!(
+            "Fee stats resized to {} bytes",
+            ctx.accounts.fee_stats.to_account_info().data_len()
+        );
+        Ok(())
+    }
+
+    /// Rewrite a fee stats account created before the hook went zero-copy in the current layout
+    /// The Borsh layout packed its fields in another order, so `AccountLoader` can't read it.
+    /// Counters, fees and windows carry over; fields added since start at their defaults
+    pub fn migrate_fee_stats(ctx: Context<MigrateFeeStats>) -> Result<()> {
+        let fee_stats_info = ctx.accounts.fee_stats.to_account_info();
+        let legacy = {
+            let data = fee_stats_info.try_borrow_data()?;
+            require!(is_legacy_fee_stats(&data), DynamicFeeError::NotLegacyFeeStats);
+            LegacyDynamicFeeStats::deserialize(&mut &data[8..])?
+        };
+
+        let rent = Rent::get()?.minimum_balance(DynamicFeeStats::LEN);
+        let shortfall = rent.saturating_sub(fee_stats_info.lamports());
+        if shortfall > 0 {
+            transfer(
+                CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    Transfer {
+                        from: ctx.accounts.payer.to_account_info(),
+                        to: fee_stats_info.clone(),
+                    },
+                ),
+                shortfall,
+            )?;
+        }
+        fee_stats_info.realloc(DynamicFeeStats::LEN, true)?;
+
+        let mut fee_stats_data = fee_stats_info.try_borrow_mut_data()?;
+        let (fee_stats, _) = split_fee_stats(&mut fee_stats_data)?;
+        *fee_stats = legacy.migrate();
+
+        msg!("Fee stats migrated: {} transfers, fee={}bp", fee_stats.total_transfers, fee_stats.current_fee_basis_points);
+        Ok(())
+    }
+
+    /// Fee the next transfer starts from, with the velocity windows rolled to now
+    /// Read-only, returned so callers don't depend on the `DynamicFeeStats` layout
+    pub fn get_current_fee(ctx: Context<GetCurrentFee>) -> Result<u16> {
+        let fee_stats_info = ctx.accounts.fee_stats.to_account_info();
+        let fee_stats_data = fee_stats_info.try_borrow_data()?;
+        let (fee_stats, extra_windows) = read_fee_stats(&fee_stats_data)?;
+        let windows = VelocityWindows::load(fee_stats, extra_windows)?;
+
+        Ok(current_fee(fee_stats, &windows, Clock::get()?.unix_timestamp))
+    }
+
+    /// Track `window_count` minute windows instead of six, clearing the current ones
+    /// Windows past the sixth live after `DynamicFeeStats`, so grow the account with
+    /// `resize_fee_stats(DynamicFeeStats::space(window_count))` first
+    /// Only callable by the fee stats authority
+    pub fn set_window_count(ctx: Context<UpdateFeeStats>, window_count: u8) -> Result<()> {
+        require!(
+            (1..=MAX_WINDOW_COUNT).contains(&window_count),
+            DynamicFeeError::InvalidFeeParameters
+        );
+
+        let fee_stats_info = ctx.accounts.fee_stats.to_account_info();
+        require!(
+            fee_stats_info.data_len() >= DynamicFeeStats::space(window_count),
+            DynamicFeeError::InvalidAccountSize
+        );
+
+        let mut fee_stats_data = fee_stats_info.try_borrow_mut_data()?;
+        let (fee_stats, extra_windows) = split_fee_stats(&mut fee_stats_data)?;
+        fee_stats.window_count = window_count;
+        fee_stats.current_minute_slot = 0;
+        fee_stats.last_update_timestamp = Clock::get()?.unix_timestamp;
+        VelocityWindows::empty(window_count).store(fee_stats, extra_windows);
+
+        msg!("Velocity windows: {}", window_count);
+        Ok(())
+    }
+
+    /// Close the mint's extra account metas, returning their rent to `destination`
+    /// Only for mints that no longer run this hook. Even without supply a mint still pointing
+    /// here would need the metas for its next transfer. The fee stats are shared by every mint
+    /// running this hook, so they stay open along with their authority
+    /// Only callable by the fee stats authority
+    pub fn close_extra_account_meta_list(ctx: Context<CloseExtraAccountMetaList>) -> Result<()> {
+        let mint_info = ctx.accounts.mint.to_account_info();
+        let mint_data = mint_info.try_borrow_data()?;
+        let mint = StateWithExtensions::<MintState>::unpack(&mint_data)?;
+        let hook_program = mint
+            .get_extension::<MintTransferHook>()
+            .ok()
+            .and_then(|hook| Option::<Pubkey>::from(hook.program_id));
+        require!(!mint_in_use(hook_program), DynamicFeeError::MintStillInUse);
+
+        // The meta list isn't an Anchor account, so it is closed by hand
+        let destination = ctx.accounts.destination.to_account_info();
+        let extra_metas = ctx.accounts.extra_account_meta_list.to_account_info();
+        let lamports = extra_metas.lamports();
+        **destination.try_borrow_mut_lamports()? += lamports;
+        **extra_metas.try_borrow_mut_lamports()? = 0;
+        extra_metas.assign(&System::id());
+        extra_metas.realloc(0, false)?;
+
+        emit!(HookClosed {
+            mint: ctx.accounts.mint.key(),
+            destination: destination.key(),
+            lamports,
+        });
+
+        msg!("Extra account metas for {} closed, {} lamports to {}", ctx.accounts.mint.key(), lamports, destination.key());
         Ok(())
     }
 
@@ -112,6 +407,12 @@ pub mod dynamic_fee_hook {
     }
 }
 
+/// Whether a mint still needs this hook's accounts, from the program its TransferHook extension
+/// points at. Supply doesn't matter, tokens minted later would call into the hook too
+fn mint_in_use(hook_program: Option<Pubkey>) -> bool {
+    hook_program == Some(crate::ID)
+}
+
 /// Validates that this hook is called within a proper transfer context
 fn check_transfer_state(ctx: &Context<TransferHook>) -> Result<()> {
     let source_token_info = ctx.accounts.source_token.to_account_info();
@@ -126,36 +427,121 @@ fn check_transfer_state(ctx: &Context<TransferHook>) -> Result<()> {
     Ok(())
 }
 
+/// WSOL lamports charged for a transfer of `amount` base units at `fee_bps`
+/// The fee is `fee_bps` of the tokens, priced at `lamports_per_token` per whole token of
+/// `decimals`. Rounds down and saturates, a fee no sender can pay is skipped anyway
+fn wsol_fee(amount: u64, fee_bps: u16, lamports_per_token: u64, decimals: u8) -> u64 {
+    let Some(whole_token) = 10u128.checked_pow(decimals as u32) else {
+        return 0;
+    };
+    (amount as u128 * fee_bps as u128)
+        .checked_mul(lamports_per_token as u128)
+        .map_or(u64::MAX, |fee| u64::try_from(fee / 10_000 / whole_token).unwrap_or(u64::MAX))
+}
+
+/// State of the SPL Token account at `info`, None unless it exists and is initialized
+fn wsol_account(info: &AccountInfo) -> Option<TokenAccountState> {
+    if info.owner != &Token::id() {
+        return None;
+    }
+    let data = info.try_borrow_data().ok()?;
+    StateWithExtensions::<TokenAccountState>::unpack(&data).ok().map(|account| account.base)
+}
+
+/// Fail unless `sender_wsol` approved `delegate` for at least `fee` and holds it
+/// Pool vault payouts are sent by the pool's config, whose WSOL account LokoSwap's
+/// `fund_hook_fee` funds and approves
+fn check_wsol_fee_source(sender_wsol: &TokenAccountState, delegate: &Pubkey, fee: u64) -> Result<()> {
+    require!(
+        sender_wsol.delegate == Some(*delegate).into() && sender_wsol.delegated_amount >= fee,
+        DynamicFeeError::WsolFeeNotApproved
+    );
+    require!(sender_wsol.amount >= fee, DynamicFeeError::InsufficientWsolBalance);
+    Ok(())
+}
+
+/// Move `fee` from the sender's WSOL account to the delegate's, under the delegate PDA's approval
+/// A sender whose WSOL account is missing, unapproved or short still transfers: the fee is
+/// skipped with `DynamicFeeSkipped`, so the hook never blocks the tokens it runs on.
+/// Returns whether the fee was collected
+fn collect_wsol_fee(ctx: &Context<TransferHook>, fee: u64) -> Result<bool> {
+    let sender_wsol = ctx.accounts.sender_wsol_token_account.to_account_info();
+    let delegate_wsol = ctx.accounts.delegate_wsol_token_account.to_account_info();
+    let ready = match (wsol_account(&sender_wsol), wsol_account(&delegate_wsol)) {
+        (Some(sender), Some(_)) => check_wsol_fee_source(&sender, &ctx.accounts.delegate.key(), fee),
+        _ => err!(DynamicFeeError::WsolAccountMissing),
+    };
+    if let Err(error) = ready {
+        msg!("Dynamic fee of {} lamports skipped: {}", fee, error);
+        emit!(DynamicFeeSkipped {
+            mint: ctx.accounts.mint.key(),
+            owner: ctx.accounts.owner.key(),
+            fee_lamports: fee,
+        });
+        return Ok(false);
+    }
+
+    let signer_seeds: &[&[&[u8]]] = &[&[b"delegate", &[ctx.bumps.delegate]]];
+    transfer_checked(
+        CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: sender_wsol,
+                mint: ctx.accounts.wsol_mint.to_account_info(),
+                to: delegate_wsol,
+                authority: ctx.accounts.delegate.to_account_info(),
+            },
+            signer_seeds,
+        ),
+        fee,
+        ctx.accounts.wsol_mint.decimals,
+    )?;
+    Ok(true)
+}
+
+/// Transfers across the minute windows, each weighted by `decay^age`
+/// Fixed-point in basis points; a decay of 10000 weighs every window fully, and so does 0,
+/// which is decay turned off as on accounts from before it existed
+fn weighted_transfer_count(recent_transfers: &[u64], current_slot: u8, decay_factor_bps: u16) -> u64 {
+    let decay_factor_bps = if decay_factor_bps == 0 { 10_000 } else { decay_factor_bps };
+    let window_count = recent_transfers.len();
+    let mut weight: u128 = 10_000;
+    let mut weighted_sum: u128 = 0;
+
+    for age in 0..window_count {
+        let slot = (current_slot as usize + window_count - age) % window_count;
+        weighted_sum += recent_transfers[slot] as u128 * weight;
+        weight = weight * decay_factor_bps as u128 / 10_000;
+    }
+
+    (weighted_sum / 10_000) as u64
+}
+
 /// Dynamic fee scaling based on transaction velocity
-/// TPM thresholds: 10->20bp, 30->50bp, 60->120bp, 120->300bp
+/// Default TPM thresholds: 10->20bp, 30->50bp, 60->120bp, 120->300bp
+/// With decay, older windows count for less, so the fee eases down instead of dropping when a
+/// window rolls over
+/// Returns the new fee and the weighted transfers per minute it was derived from
 fn update_velocity_and_calculate_fee(
     fee_stats: &mut DynamicFeeStats,
+    windows: &mut VelocityWindows,
     current_timestamp: i64,
     amount: u64,
-) -> Result<u16> {
-    let time_diff = current_timestamp - fee_stats.last_update_timestamp;
-    
-    if time_diff >= 60 {
-        let windows_to_advance = std::cmp::min(6, (time_diff / 60) as usize);
-        
-        for _ in 0..windows_to_advance {
-            fee_stats.current_minute_slot = (fee_stats.current_minute_slot + 1) % 6;
-            let slot = fee_stats.current_minute_slot as usize;
-            fee_stats.recent_transfers[slot] = 0;
-            fee_stats.recent_volumes[slot] = 0;
-        }
-        
-        fee_stats.last_update_timestamp = current_timestamp;
-    }
+) -> Result<(u16, u64)> {
+    roll_windows(fee_stats, windows, current_timestamp);
     let current_slot = fee_stats.current_minute_slot as usize;
-    fee_stats.recent_transfers[current_slot] = fee_stats.recent_transfers[current_slot]
+    windows.transfers[current_slot] = windows.transfers[current_slot]
         .checked_add(1)
         .ok_or(DynamicFeeError::MathOverflow)?;
-    fee_stats.recent_volumes[current_slot] = fee_stats.recent_volumes[current_slot]
+    windows.volumes[current_slot] = windows.volumes[current_slot]
         .checked_add(amount)
         .ok_or(DynamicFeeError::MathOverflow)?;
     
-    let total_tpm = fee_stats.recent_transfers.iter().sum::<u64>();
+    let total_tpm = weighted_transfer_count(
+        &windows.transfers,
+        fee_stats.current_minute_slot,
+        fee_stats.decay_factor_bps,
+    );
     if fee_stats.total_transfers > 0 {
         fee_stats.avg_transfer_size = (fee_stats.avg_transfer_size
             .checked_mul(fee_stats.total_transfers)
@@ -166,39 +552,152 @@ fn update_velocity_and_calculate_fee(
         fee_stats.avg_transfer_size = amount;
     }
     
-    let base_fee = if total_tpm <= 10 {
-        fee_stats.base_fee_basis_points
-    } else if total_tpm <= 30 {
-        fee_stats.base_fee_basis_points * 2
-    } else if total_tpm <= 60 {
-        fee_stats.base_fee_basis_points * 5
-    } else if total_tpm <= 120 {
-        fee_stats.base_fee_basis_points * 12
-    } else {
-        fee_stats.max_fee_basis_points
-    };
+    let old_fee = fee_stats.current_fee_basis_points;
+    let smoothed_fee = held_fee(
+        fee_stats,
+        smoothed_fee(fee_stats, tier_fee(fee_stats, total_tpm)),
+        current_timestamp,
+    );
     
-    let fee_change_limit = fee_stats.base_fee_basis_points;
-    let smoothed_fee = if base_fee > fee_stats.current_fee_basis_points {
-        std::cmp::min(base_fee, fee_stats.current_fee_basis_points + fee_change_limit)
-    } else {
-        std::cmp::max(base_fee, fee_stats.current_fee_basis_points.saturating_sub(fee_change_limit))
-    };
-    
-    let current_tps = (total_tpm as f64 / 60.0) as u16;
+    let current_tps = tps_from_tpm(total_tpm);
     if current_tps > fee_stats.peak_tps {
         fee_stats.peak_tps = current_tps;
     }
     
     fee_stats.current_fee_basis_points = std::cmp::min(smoothed_fee, fee_stats.max_fee_basis_points);
     if fee_stats.avg_transfer_size > 0 && amount > fee_stats.avg_transfer_size * 10 {
-        fee_stats.current_fee_basis_points = std::cmp::min(
-            (fee_stats.current_fee_basis_points as f64 * 1.5) as u16,
-            fee_stats.max_fee_basis_points
-        );
+        fee_stats.current_fee_basis_points =
+            large_transfer_fee(fee_stats.current_fee_basis_points, fee_stats.max_fee_basis_points);
+    }
+    if fee_stats.current_fee_basis_points > old_fee {
+        fee_stats.last_fee_increase_timestamp = current_timestamp;
     }
     
-    Ok(fee_stats.current_fee_basis_points)
+    Ok((fee_stats.current_fee_basis_points, total_tpm))
+}
+
+/// Transfers per second for a transfers-per-minute count, capped at `u16::MAX`
+fn tps_from_tpm(total_tpm: u64) -> u16 {
+    (total_tpm / 60).min(u16::MAX as u64) as u16
+}
+
+/// Fee for a transfer over 10x the average size, 1.5x the current fee up to `max_fee`
+fn large_transfer_fee(current_fee: u16, max_fee: u16) -> u16 {
+    (current_fee as u32 * 3 / 2).min(max_fee as u32) as u16
+}
+
+/// Advance the minute windows to `current_timestamp`, clearing the ones that expired
+/// Windows are wall-clock minutes, so a transfer lands in the minute it happened in however long
+/// ago the last one was. As many elapsed minutes as there are windows expire every window
+fn roll_windows(fee_stats: &mut DynamicFeeStats, windows: &mut VelocityWindows, current_timestamp: i64) {
+    let window_count = windows.transfers.len();
+    let elapsed_minutes =
+        current_timestamp.div_euclid(60) - fee_stats.last_update_timestamp.div_euclid(60);
+    
+    if elapsed_minutes >= window_count as i64 {
+        windows.transfers.fill(0);
+        windows.volumes.fill(0);
+        fee_stats.current_minute_slot = 0;
+    } else if elapsed_minutes > 0 {
+        for _ in 0..elapsed_minutes {
+            let slot = (fee_stats.current_minute_slot as usize + 1) % window_count;
+            fee_stats.current_minute_slot = slot as u8;
+            windows.transfers[slot] = 0;
+            windows.volumes[slot] = 0;
+        }
+    }
+
+    // Never move back, a validator clock behind the last transfer keeps the current minute
+    fee_stats.last_update_timestamp = fee_stats.last_update_timestamp.max(current_timestamp);
+}
+
+/// Fee tier for a weighted transfers-per-minute count
+fn tier_fee(fee_stats: &DynamicFeeStats, total_tpm: u64) -> u16 {
+    let [first, second, third, fourth] = fee_stats.tier_thresholds().map(u64::from);
+    if total_tpm <= first {
+        fee_stats.base_fee_basis_points
+    } else if total_tpm <= second {
+        fee_stats.base_fee_basis_points.saturating_mul(2)
+    } else if total_tpm <= third {
+        fee_stats.base_fee_basis_points.saturating_mul(5)
+    } else if total_tpm <= fourth {
+        fee_stats.base_fee_basis_points.saturating_mul(12)
+    } else {
+        fee_stats.max_fee_basis_points
+    }
+}
+
+/// Tier thresholds must increase strictly, or all be zero for the defaults
+fn valid_tier_thresholds(tier_thresholds: &[u32; 4]) -> bool {
+    *tier_thresholds == [0; 4] || tier_thresholds.windows(2).all(|pair| pair[0] < pair[1])
+}
+
+/// Move the current fee toward `target` by at most one base fee
+fn smoothed_fee(fee_stats: &DynamicFeeStats, target: u16) -> u16 {
+    let fee_change_limit = fee_stats.base_fee_basis_points;
+    if target > fee_stats.current_fee_basis_points {
+        std::cmp::min(target, fee_stats.current_fee_basis_points.saturating_add(fee_change_limit))
+    } else {
+        std::cmp::max(target, fee_stats.current_fee_basis_points.saturating_sub(fee_change_limit))
+    }
+}
+
+/// Keep the current fee instead of a lower `fee` until `fee_dwell_seconds` passed since it last rose
+/// Gives hysteresis, so the fee steps down only once congestion has stayed cleared
+fn held_fee(fee_stats: &DynamicFeeStats, fee: u16, current_timestamp: i64) -> u16 {
+    let since_increase = current_timestamp.saturating_sub(fee_stats.last_fee_increase_timestamp);
+    if fee < fee_stats.current_fee_basis_points && since_increase < fee_stats.fee_dwell_seconds as i64 {
+        fee_stats.current_fee_basis_points
+    } else {
+        fee
+    }
+}
+
+/// Fee as of `current_timestamp` without recording a transfer
+/// Expired windows roll off and the fee eases toward the tier of what's left, like on a transfer
+fn current_fee(fee_stats: &DynamicFeeStats, windows: &VelocityWindows, current_timestamp: i64) -> u16 {
+    // Not initialized by a first transfer yet
+    if fee_stats.max_fee_basis_points == 0 {
+        return fee_stats.current_fee_basis_points;
+    }
+
+    let mut stats = *fee_stats;
+    let mut windows = windows.clone();
+    roll_windows(&mut stats, &mut windows, current_timestamp);
+    let total_tpm = weighted_transfer_count(
+        &windows.transfers,
+        stats.current_minute_slot,
+        stats.decay_factor_bps,
+    );
+
+    let fee = held_fee(&stats, smoothed_fee(&stats, tier_fee(&stats, total_tpm)), current_timestamp);
+    std::cmp::min(fee, stats.max_fee_basis_points)
+}
+
+#[derive(Accounts)]
+pub struct InitializeFeeStats<'info> {
+    /// Must be the hook program's upgrade authority
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        init_if_needed,
+        seeds = [b"fee_stats"],
+        bump,
+        payer = authority,
+        space = DynamicFeeStats::LEN
+    )]
+    pub fee_stats: AccountLoader<'info, DynamicFeeStats>,
+    #[account(
+        constraint = program.programdata_address()? == Some(program_data.key())
+            @ DynamicFeeError::InvalidAuthority
+    )]
+    pub program: Program<'info, crate::program::DynamicFeeHook>,
+    #[account(
+        constraint = program_data.upgrade_authority_address == Some(authority.key())
+            @ DynamicFeeError::InvalidAuthority
+    )]
+    pub program_data: Account<'info, ProgramData>,
+    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
@@ -218,18 +717,20 @@ pub struct InitializeExtraAccountMetaList<'info> {
     )]
     pub extra_account_meta_list: AccountInfo<'info>,
     pub mint: InterfaceAccount<'info, Mint>,
-    #[account(init_if_needed, seeds = [b"fee_stats"], bump, payer = payer, space = 8 + 200)]
-    pub fee_stats: Account<'info, DynamicFeeStats>,
+    /// Created by `initialize_fee_stats`, every transfer of the mint writes to it
+    #[account(seeds = [b"fee_stats"], bump)]
+    pub fee_stats: AccountLoader<'info, DynamicFeeStats>,
     pub system_program: Program<'info, System>,
 }
 
 impl<'info> InitializeExtraAccountMetaList<'info> {
+    /// Accounts Token-2022 appends to `Execute`, after source (0), mint (1), destination (2),
+    /// owner (3) and this list (4):
+    /// 5 WSOL mint, 6 token program, 7 associated token program, 8 delegate PDA,
+    /// 9 delegate's WSOL ATA, 10 owner's WSOL ATA, 11 fee stats PDA
     pub fn extra_account_metas() -> Result<Vec<ExtraAccountMeta>> {
         Ok(vec![
-            ExtraAccountMeta::new_with_pubkey(
-                &Pubkey::from_str("So11111111111111111111111111111111111111112").unwrap(),
-                false, false
-            )?,
+            ExtraAccountMeta::new_with_pubkey(&native_mint::ID, false, false)?,
             ExtraAccountMeta::new_with_pubkey(&Token::id(), false, false)?,
             ExtraAccountMeta::new_with_pubkey(&AssociatedToken::id(), false, false)?,
             ExtraAccountMeta::new_with_seeds(
@@ -272,21 +773,93 @@ pub struct TransferHook<'info> {
     /// CHECK: ExtraAccountMetaList Account
     #[account(seeds = [b"extra-account-metas", mint.key().as_ref()], bump)]
     pub extra_account_meta_list: UncheckedAccount<'info>,
+    #[account(address = native_mint::ID)]
     pub wsol_mint: InterfaceAccount<'info, Mint>,
     pub token_program: Program<'info, Token>,
     pub associated_token_program: Program<'info, AssociatedToken>,
+    /// Approved on senders' WSOL accounts to collect the dynamic fee
     #[account(mut, seeds = [b"delegate"], bump)]
     pub delegate: SystemAccount<'info>,
-    #[account(mut, token::mint = wsol_mint, token::authority = delegate)]
-    pub delegate_wsol_token_account: InterfaceAccount<'info, TokenAccount>,
-    /// CHECK: WSOL token account
-    #[account(mut, token::mint = wsol_mint)]
-    pub sender_wsol_token_account: InterfaceAccount<'info, TokenAccount>,
+    /// CHECK: the delegate's WSOL ATA, receives the dynamic fee. Fees are skipped while it doesn't exist
+    #[account(mut, address = get_associated_token_address(&delegate.key(), &native_mint::ID))]
+    pub delegate_wsol_token_account: UncheckedAccount<'info>,
+    /// CHECK: the owner's WSOL ATA, pays the dynamic fee when it exists, is funded and approved
+    #[account(mut, address = get_associated_token_address(&owner.key(), &native_mint::ID))]
+    pub sender_wsol_token_account: UncheckedAccount<'info>,
     #[account(mut, seeds = [b"fee_stats"], bump)]
-    pub fee_stats: Account<'info, DynamicFeeStats>,
+    pub fee_stats: AccountLoader<'info, DynamicFeeStats>,
 }
 
-#[account]
+#[derive(Accounts)]
+pub struct UpdateFeeStats<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"fee_stats"],
+        bump,
+        has_one = authority @ DynamicFeeError::InvalidAuthority
+    )]
+    pub fee_stats: AccountLoader<'info, DynamicFeeStats>,
+}
+
+#[derive(Accounts)]
+pub struct CloseExtraAccountMetaList<'info> {
+    pub authority: Signer<'info>,
+    /// Shared by every mint running this hook, only read for its authority
+    #[account(
+        seeds = [b"fee_stats"],
+        bump,
+        has_one = authority @ DynamicFeeError::InvalidAuthority
+    )]
+    pub fee_stats: AccountLoader<'info, DynamicFeeStats>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    /// CHECK: ExtraAccountMetaList Account, closed by the instruction
+    #[account(mut, seeds = [b"extra-account-metas", mint.key().as_ref()], bump)]
+    pub extra_account_meta_list: UncheckedAccount<'info>,
+    /// CHECK: Receives the meta list's rent
+    #[account(mut)]
+    pub destination: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct GetCurrentFee<'info> {
+    #[account(seeds = [b"fee_stats"], bump)]
+    pub fee_stats: AccountLoader<'info, DynamicFeeStats>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateFeeStats<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    /// CHECK: still in the legacy Borsh layout `AccountLoader` can't read, checked in the instruction
+    #[account(mut, seeds = [b"fee_stats"], bump, owner = crate::ID)]
+    pub fee_stats: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(new_size: u32)]
+pub struct ResizeFeeStats<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"fee_stats"],
+        bump,
+        constraint = new_size as usize >= DynamicFeeStats::required_space(&fee_stats.to_account_info())?
+            @ DynamicFeeError::InvalidAccountSize,
+        realloc = new_size as usize,
+        realloc::payer = payer,
+        realloc::zero = false
+    )]
+    pub fee_stats: AccountLoader<'info, DynamicFeeStats>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Zero-copy so the account can grow and be updated in place
+/// The AMM reads the fee through `get_current_fee`, not this layout;
+/// off-chain tooling decodes it with `client::decode_fee_stats`
+#[account(zero_copy)]
 pub struct DynamicFeeStats {
     pub total_fees_collected: u64,
     pub total_transfers: u64,
@@ -294,10 +867,530 @@ pub struct DynamicFeeStats {
     pub current_fee_basis_points: u16,
     pub base_fee_basis_points: u16,
     pub max_fee_basis_points: u16,
+    pub peak_tps: u16,
     pub recent_transfers: [u64; 6],
     pub recent_volumes: [u64; 6],
-    pub current_minute_slot: u8,
-    pub last_update_timestamp: i64,
-    pub peak_tps: u16,
     pub avg_transfer_size: u64,
-}
\ No newline at end of file
+    pub last_update_timestamp: i64,
+    pub current_minute_slot: u8,
+    pub window_count: u8,                      // Minute windows tracked, 0 on older accounts means 6
+    pub decay_factor_bps: u16,                 // Weight kept per minute of window age, 0 = no decay
+    pub fee_dwell_seconds: u32,                // Hold after an increase before the fee may decrease
+    pub authority: Pubkey,                     // Signs parameter changes
+    pub last_fee_increase_timestamp: i64,
+    pub tier_thresholds: [u32; 4],             // Weighted TPM where each tier starts, 0s = defaults
+    pub wsol_lamports_per_token: u64,          // Price of the fee in WSOL per whole token, 0 = free
+}
+
+impl DynamicFeeStats {
+    /// Minimum account size including the discriminator
+    pub const LEN: usize = 8 + std::mem::size_of::<DynamicFeeStats>();
+
+    /// Account size needed to track `window_count` windows
+    pub fn space(window_count: u8) -> usize {
+        Self::LEN + (window_count as usize).saturating_sub(INLINE_WINDOWS) * 2 * 8
+    }
+
+    /// Size the account must keep, `LEN` for accounts created before the struct last grew
+    fn required_space(info: &AccountInfo) -> Result<usize> {
+        let data = info.try_borrow_data()?;
+        if data.len() < Self::LEN {
+            return Ok(Self::LEN);
+        }
+        let fee_stats: &Self = bytemuck::try_from_bytes(&data[8..Self::LEN])
+            .map_err(|_| DynamicFeeError::InvalidAccountSize)?;
+        Ok(Self::space(fee_stats.window_count()))
+    }
+
+    fn tier_thresholds(&self) -> [u32; 4] {
+        if self.tier_thresholds == [0; 4] {
+            DEFAULT_TIER_THRESHOLDS
+        } else {
+            self.tier_thresholds
+        }
+    }
+
+    fn window_count(&self) -> u8 {
+        if self.window_count == 0 {
+            DEFAULT_WINDOW_COUNT
+        } else {
+            self.window_count
+        }
+    }
+}
+
+/// Size of a fee stats account created with the Borsh layout, discriminator included
+const LEGACY_FEE_STATS_LEN: usize = 8 + 200;
+
+/// `DynamicFeeStats` as the hook stored it with Borsh, before it went zero-copy
+/// Borsh packs the fields in declaration order without padding
+#[derive(AnchorSerialize, AnchorDeserialize, Debug, Default, PartialEq)]
+struct LegacyDynamicFeeStats {
+    total_fees_collected: u64,
+    total_transfers: u64,
+    total_volume: u64,
+    current_fee_basis_points: u16,
+    base_fee_basis_points: u16,
+    max_fee_basis_points: u16,
+    recent_transfers: [u64; 6],
+    recent_volumes: [u64; 6],
+    current_minute_slot: u8,
+    last_update_timestamp: i64,
+    peak_tps: u16,
+    avg_transfer_size: u64,
+}
+
+impl LegacyDynamicFeeStats {
+    /// The same stats in the zero-copy layout, six windows and no authority yet
+    fn migrate(&self) -> DynamicFeeStats {
+        let mut fee_stats = DynamicFeeStats::zeroed();
+        fee_stats.total_fees_collected = self.total_fees_collected;
+        fee_stats.total_transfers = self.total_transfers;
+        fee_stats.total_volume = self.total_volume;
+        fee_stats.current_fee_basis_points = self.current_fee_basis_points;
+        fee_stats.base_fee_basis_points = self.base_fee_basis_points;
+        fee_stats.max_fee_basis_points = self.max_fee_basis_points;
+        fee_stats.peak_tps = self.peak_tps;
+        fee_stats.recent_transfers = self.recent_transfers;
+        fee_stats.recent_volumes = self.recent_volumes;
+        fee_stats.avg_transfer_size = self.avg_transfer_size;
+        fee_stats.last_update_timestamp = self.last_update_timestamp;
+        fee_stats.current_minute_slot = self.current_minute_slot;
+        fee_stats
+    }
+}
+
+/// Whether fee stats account data still holds the Borsh layout
+/// Those accounts were always created at `LEGACY_FEE_STATS_LEN`, and zero-copy accounts of any
+/// size have their authority set, which sits past the end of the Borsh fields
+fn is_legacy_fee_stats(data: &[u8]) -> bool {
+    let authority = std::mem::offset_of!(DynamicFeeStats, authority) + 8;
+    data.len() == LEGACY_FEE_STATS_LEN
+        && data[..8] == *DynamicFeeStats::DISCRIMINATOR
+        && data[authority..authority + 32].iter().all(|byte| *byte == 0)
+}
+
+/// Fee stats and the extra window region after them, from the data of a fee stats account
+/// The account's discriminator and owner are checked when its `AccountLoader` is built
+fn split_fee_stats(data: &mut [u8]) -> Result<(&mut DynamicFeeStats, &mut [u8])> {
+    require!(data.len() >= DynamicFeeStats::LEN, DynamicFeeError::InvalidAccountSize);
+    let (head, extra) = data.split_at_mut(DynamicFeeStats::LEN);
+    let fee_stats = bytemuck::try_from_bytes_mut(&mut head[8..])
+        .map_err(|_| DynamicFeeError::InvalidAccountSize)?;
+    Ok((fee_stats, extra))
+}
+
+/// Read-only `split_fee_stats`, for instructions that don't write the account
+fn read_fee_stats(data: &[u8]) -> Result<(&DynamicFeeStats, &[u8])> {
+    require!(data.len() >= DynamicFeeStats::LEN, DynamicFeeError::InvalidAccountSize);
+    let (head, extra) = data.split_at(DynamicFeeStats::LEN);
+    let fee_stats = bytemuck::try_from_bytes(&head[8..])
+        .map_err(|_| DynamicFeeError::InvalidAccountSize)?;
+    Ok((fee_stats, extra))
+}
+
+/// Per-minute transfer counts and volumes, oldest to newest around `current_minute_slot`
+///
+/// The first six windows live inline in `DynamicFeeStats`. Further windows follow it in the
+/// account data, all transfer counts first and then all volumes, as little-endian `u64`s.
+#[derive(Clone, Debug, PartialEq)]
+struct VelocityWindows {
+    transfers: Vec<u64>,
+    volumes: Vec<u64>,
+}
+
+impl VelocityWindows {
+    fn empty(window_count: u8) -> Self {
+        Self {
+            transfers: vec![0; window_count as usize],
+            volumes: vec![0; window_count as usize],
+        }
+    }
+
+    fn load(fee_stats: &DynamicFeeStats, extra: &[u8]) -> Result<Self> {
+        let window_count = fee_stats.window_count() as usize;
+        let extra_count = window_count.saturating_sub(INLINE_WINDOWS);
+        require!(extra.len() >= extra_count * 2 * 8, DynamicFeeError::InvalidAccountSize);
+
+        let read = |index: usize| u64::from_le_bytes(extra[index * 8..index * 8 + 8].try_into().unwrap());
+        let inline = window_count.min(INLINE_WINDOWS);
+
+        let mut windows = Self {
+            transfers: fee_stats.recent_transfers[..inline].to_vec(),
+            volumes: fee_stats.recent_volumes[..inline].to_vec(),
+        };
+        for index in 0..extra_count {
+            windows.transfers.push(read(index));
+            windows.volumes.push(read(extra_count + index));
+        }
+
+        Ok(windows)
+    }
+
+    /// Write back into the places `load` read from, `extra` must be as large as it was then
+    fn store(&self, fee_stats: &mut DynamicFeeStats, extra: &mut [u8]) {
+        let inline = self.transfers.len().min(INLINE_WINDOWS);
+        let extra_count = self.transfers.len() - inline;
+
+        fee_stats.recent_transfers[..inline].copy_from_slice(&self.transfers[..inline]);
+        fee_stats.recent_volumes[..inline].copy_from_slice(&self.volumes[..inline]);
+
+        let mut write = |index: usize, value: u64| {
+            extra[index * 8..index * 8 + 8].copy_from_slice(&value.to_le_bytes());
+        };
+        for index in 0..extra_count {
+            write(index, self.transfers[inline + index]);
+            write(extra_count + index, self.volumes[inline + index]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn busy_stats() -> (DynamicFeeStats, VelocityWindows) {
+        let mut stats = DynamicFeeStats::zeroed();
+        stats.base_fee_basis_points = 10;
+        stats.current_fee_basis_points = 50;
+        stats.max_fee_basis_points = 300;
+        stats.decay_factor_bps = DEFAULT_DECAY_FACTOR_BPS;
+        stats.current_minute_slot = 3;
+        stats.last_update_timestamp = 1_000;
+        let windows = VelocityWindows {
+            transfers: vec![5, 6, 7, 8, 9, 10],
+            volumes: vec![500, 600, 700, 800, 900, 1_000],
+        };
+        (stats, windows)
+    }
+
+    #[test]
+    fn test_roll_windows_full_reset() {
+        // A 10 minute gap expires every window, wherever the ring was
+        for slot in 0..6 {
+            let (mut stats, mut windows) = busy_stats();
+            stats.current_minute_slot = slot;
+            roll_windows(&mut stats, &mut windows, 1_600);
+
+            assert_eq!(windows.transfers, [0; 6]);
+            assert_eq!(windows.volumes, [0; 6]);
+            assert_eq!(stats.current_minute_slot, 0);
+            assert_eq!(stats.last_update_timestamp, 1_600);
+        }
+
+        // Exactly six minutes is a full reset too
+        let (mut stats, mut windows) = busy_stats();
+        roll_windows(&mut stats, &mut windows, 1_360);
+        assert_eq!(windows.transfers, [0; 6]);
+
+        // Two minutes only clear the two windows rolled into
+        let (mut stats, mut windows) = busy_stats();
+        roll_windows(&mut stats, &mut windows, 1_120);
+        assert_eq!(stats.current_minute_slot, 5);
+        assert_eq!(windows.transfers, [5, 6, 7, 8, 0, 0]);
+    }
+
+    #[test]
+    fn test_transfers_bucketed_by_minute() {
+        let mut stats = DynamicFeeStats::zeroed();
+        stats.base_fee_basis_points = 10;
+        stats.current_fee_basis_points = 10;
+        stats.max_fee_basis_points = 300;
+        let mut windows = VelocityWindows::empty(DEFAULT_WINDOW_COUNT);
+
+        // 0 and 59 share the first minute, 61 and 119 the second
+        for now in [0, 59, 61, 119] {
+            update_velocity_and_calculate_fee(&mut stats, &mut windows, now, 10).unwrap();
+        }
+        assert_eq!(windows.transfers, [2, 2, 0, 0, 0, 0]);
+        assert_eq!(stats.current_minute_slot, 1);
+        assert_eq!(stats.last_update_timestamp, 119);
+
+        // 121 is two seconds after 119 but already the third minute
+        update_velocity_and_calculate_fee(&mut stats, &mut windows, 121, 10).unwrap();
+        assert_eq!(windows.transfers, [2, 2, 1, 0, 0, 0]);
+        assert_eq!(windows.volumes, [20, 20, 10, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_transfer_after_long_gap_counts_alone() {
+        let (mut stats, mut windows) = busy_stats();
+        update_velocity_and_calculate_fee(&mut stats, &mut windows, 1_600, 42).unwrap();
+
+        assert_eq!(windows.transfers, [1, 0, 0, 0, 0, 0]);
+        assert_eq!(windows.volumes, [42, 0, 0, 0, 0, 0]);
+        // Idle velocity eases the fee down one step toward the base tier
+        assert_eq!(stats.current_fee_basis_points, 40);
+    }
+
+    #[test]
+    fn test_fee_dwell_after_burst() {
+        let mut stats = DynamicFeeStats::zeroed();
+        stats.base_fee_basis_points = 10;
+        stats.current_fee_basis_points = 10;
+        stats.max_fee_basis_points = 300;
+        stats.decay_factor_bps = 10_000;
+        stats.fee_dwell_seconds = 600;
+        stats.last_update_timestamp = 1_000;
+        let mut windows = VelocityWindows::empty(DEFAULT_WINDOW_COUNT);
+
+        // A burst climbs a tier per transfer past 10 and 30 per minute
+        for _ in 0..40 {
+            update_velocity_and_calculate_fee(&mut stats, &mut windows, 1_000, 100).unwrap();
+        }
+        assert_eq!(stats.current_fee_basis_points, 50);
+        assert_eq!(stats.last_fee_increase_timestamp, 1_000);
+
+        // Quiet traffic would step down right away without the dwell
+        let mut undwelled = stats;
+        undwelled.fee_dwell_seconds = 0;
+        update_velocity_and_calculate_fee(&mut undwelled, &mut windows.clone(), 1_360, 100).unwrap();
+        assert_eq!(undwelled.current_fee_basis_points, 40);
+
+        // With it the fee holds until 600s after the last increase, then ratchets down
+        update_velocity_and_calculate_fee(&mut stats, &mut windows, 1_360, 100).unwrap();
+        assert_eq!(stats.current_fee_basis_points, 50);
+        assert_eq!(current_fee(&stats, &windows, 1_599), 50);
+        assert_eq!(current_fee(&stats, &windows, 1_600), 40);
+        update_velocity_and_calculate_fee(&mut stats, &mut windows, 1_600, 100).unwrap();
+        assert_eq!(stats.current_fee_basis_points, 40);
+        update_velocity_and_calculate_fee(&mut stats, &mut windows, 1_601, 100).unwrap();
+        assert_eq!(stats.current_fee_basis_points, 30);
+        assert_eq!(stats.last_fee_increase_timestamp, 1_000);
+    }
+
+    #[test]
+    fn test_integer_tps_and_large_transfer_fee() {
+        // Same as the former `as f64` computations for representative inputs
+        for tpm in [0u64, 59, 60, 61, 119, 600, 3_599, 3_600, 1_000_000] {
+            assert_eq!(tps_from_tpm(tpm), (tpm as f64 / 60.0) as u16);
+        }
+        for fee in [0u16, 1, 3, 10, 33, 50, 199, 200, 6_666] {
+            assert_eq!(large_transfer_fee(fee, 10_000), (fee as f64 * 1.5) as u16);
+        }
+
+        // Clamped at the boundaries instead of overflowing
+        assert_eq!(tps_from_tpm(u64::MAX), u16::MAX);
+        assert_eq!(tps_from_tpm(u16::MAX as u64 * 60 + 59), u16::MAX);
+        assert_eq!(large_transfer_fee(u16::MAX, u16::MAX), u16::MAX);
+        assert_eq!(large_transfer_fee(u16::MAX, 300), 300);
+        assert_eq!(large_transfer_fee(250, 300), 300);
+
+        let (mut stats, _) = busy_stats();
+        stats.base_fee_basis_points = 10_000;
+        stats.max_fee_basis_points = 10_000;
+        assert_eq!(tier_fee(&stats, 100), u16::MAX);
+        stats.current_fee_basis_points = u16::MAX;
+        assert_eq!(smoothed_fee(&stats, u16::MAX), u16::MAX);
+    }
+
+    #[test]
+    fn test_mint_in_use() {
+        let other_hook = Pubkey::new_unique();
+
+        // Burning the supply down isn't enough while the mint still points here
+        assert!(mint_in_use(Some(crate::ID)));
+        // Moved to another hook, or the hook cleared
+        assert!(!mint_in_use(Some(other_hook)));
+        assert!(!mint_in_use(None));
+    }
+
+    #[test]
+    fn test_wsol_fee() {
+        // 0.1% of 5 tokens of 6 decimals, at 0.02 SOL per token: 0.005 tokens, 100_000 lamports
+        assert_eq!(wsol_fee(5_000_000, 10, 20_000_000, 6), 100_000);
+        // The same value moved as a 9 decimal token pays the same
+        assert_eq!(wsol_fee(5_000_000_000, 10, 20_000_000, 9), 100_000);
+        assert_eq!(wsol_fee(5_000_000, 300, 20_000_000, 6), 3_000_000);
+
+        // Free until the authority sets a rate, and dust rounds down to nothing
+        assert_eq!(wsol_fee(5_000_000, 10, 0, 6), 0);
+        assert_eq!(wsol_fee(49, 10, 20_000_000, 6), 0);
+        assert_eq!(wsol_fee(50, 10, 20_000_000, 6), 1);
+
+        // Saturates instead of failing the transfer
+        assert_eq!(wsol_fee(u64::MAX, 10_000, u64::MAX, 0), u64::MAX);
+        assert_eq!(wsol_fee(u64::MAX, 10_000, 1, 0), u64::MAX);
+        assert_eq!(wsol_fee(u64::MAX, 10_000, u64::MAX, u8::MAX), 0);
+    }
+
+    #[test]
+    fn test_check_wsol_fee_source() {
+        use anchor_spl::token_2022::spl_token_2022::state::AccountState;
+
+        let delegate = Pubkey::new_unique();
+        let funded = TokenAccountState {
+            mint: native_mint::ID,
+            owner: Pubkey::new_unique(),
+            amount: 5_000,
+            delegate: Some(delegate).into(),
+            delegated_amount: 5_000,
+            state: AccountState::Initialized,
+            ..Default::default()
+        };
+        assert!(check_wsol_fee_source(&funded, &delegate, 5_000).is_ok());
+
+        // No approval, another delegate, or an approval spent below the fee
+        for sender in [
+            TokenAccountState { delegate: None.into(), delegated_amount: 0, ..funded },
+            TokenAccountState { delegate: Some(Pubkey::new_unique()).into(), ..funded },
+            TokenAccountState { delegated_amount: 4_999, ..funded },
+        ] {
+            assert_eq!(
+                check_wsol_fee_source(&sender, &delegate, 5_000).unwrap_err(),
+                error!(DynamicFeeError::WsolFeeNotApproved)
+            );
+        }
+
+        // Approved for more than the account still holds
+        let drained = TokenAccountState { amount: 4_999, ..funded };
+        assert_eq!(
+            check_wsol_fee_source(&drained, &delegate, 5_000).unwrap_err(),
+            error!(DynamicFeeError::InsufficientWsolBalance)
+        );
+        assert!(check_wsol_fee_source(&drained, &delegate, 4_999).is_ok());
+    }
+
+    #[test]
+    fn test_ten_velocity_windows() {
+        let (mut stats, _) = busy_stats();
+        stats.window_count = 10;
+        stats.current_minute_slot = 8;
+        let mut windows = VelocityWindows {
+            transfers: (1..=10).collect(),
+            volumes: (1..=10).map(|n| n * 100).collect(),
+        };
+
+        // Windows past the sixth round-trip through the region after the struct
+        let mut extra = vec![0u8; DynamicFeeStats::space(10) - DynamicFeeStats::LEN];
+        assert_eq!(extra.len(), 64);
+        windows.store(&mut stats, &mut extra);
+        assert_eq!(stats.recent_transfers, [1, 2, 3, 4, 5, 6]);
+        assert_eq!(VelocityWindows::load(&stats, &extra).unwrap(), windows);
+        assert!(VelocityWindows::load(&stats, &extra[..63]).is_err());
+
+        // Every window counts fully without decay, and rotation wraps at ten
+        assert_eq!(weighted_transfer_count(&windows.transfers, 8, 10_000), 55);
+        roll_windows(&mut stats, &mut windows, 1_180);
+        assert_eq!(stats.current_minute_slot, 1);
+        assert_eq!(windows.transfers, [0, 0, 3, 4, 5, 6, 7, 8, 9, 0]);
+
+        // Nine idle minutes still leave the newest window, ten clear them all
+        windows.transfers[1] = 2;
+        let mut rolled = windows.clone();
+        roll_windows(&mut stats.clone(), &mut rolled, 1_180 + 9 * 60);
+        assert_eq!(rolled.transfers, [0, 2, 0, 0, 0, 0, 0, 0, 0, 0]);
+        roll_windows(&mut stats, &mut windows, 1_180 + 10 * 60);
+        assert_eq!(windows.transfers, [0; 10]);
+        assert_eq!(stats.current_minute_slot, 0);
+
+        // Accounts written before the count existed keep six inline windows
+        let (stats, _) = busy_stats();
+        assert_eq!(VelocityWindows::load(&stats, &[]).unwrap().transfers.len(), 6);
+        assert_eq!(DynamicFeeStats::space(6), DynamicFeeStats::LEN);
+    }
+
+    #[test]
+    fn test_migrate_legacy_fee_stats() {
+        let legacy = LegacyDynamicFeeStats {
+            total_fees_collected: 12_345,
+            total_transfers: 678,
+            total_volume: 9_000_000,
+            current_fee_basis_points: 50,
+            base_fee_basis_points: 10,
+            max_fee_basis_points: 300,
+            recent_transfers: [1, 2, 3, 4, 5, 6],
+            recent_volumes: [10, 20, 30, 40, 50, 60],
+            current_minute_slot: 4,
+            last_update_timestamp: 1_700_000_000,
+            peak_tps: 7,
+            avg_transfer_size: 13_274,
+        };
+
+        // An account as the Borsh version of the hook wrote it
+        let mut data = vec![0u8; LEGACY_FEE_STATS_LEN];
+        data[..8].copy_from_slice(DynamicFeeStats::DISCRIMINATOR);
+        let mut fields = &mut data[8..];
+        legacy.serialize(&mut fields).unwrap();
+        assert!(is_legacy_fee_stats(&data));
+
+        // Read in place, the reordered fields come out wrong
+        let mut padded = data.clone();
+        padded.resize(padded.len().max(DynamicFeeStats::LEN), 0);
+        let misread: DynamicFeeStats =
+            bytemuck::pod_read_unaligned(&padded[8..DynamicFeeStats::LEN]);
+        assert_ne!(misread.peak_tps, legacy.peak_tps);
+        assert_ne!(misread.last_update_timestamp, legacy.last_update_timestamp);
+
+        let decoded = LegacyDynamicFeeStats::deserialize(&mut &data[8..]).unwrap();
+        assert_eq!(decoded, legacy);
+        let fee_stats = decoded.migrate();
+        assert_eq!(fee_stats.total_fees_collected, 12_345);
+        assert_eq!(fee_stats.total_transfers, 678);
+        assert_eq!(fee_stats.total_volume, 9_000_000);
+        assert_eq!(fee_stats.current_fee_basis_points, 50);
+        assert_eq!(fee_stats.base_fee_basis_points, 10);
+        assert_eq!(fee_stats.max_fee_basis_points, 300);
+        assert_eq!(fee_stats.recent_transfers, [1, 2, 3, 4, 5, 6]);
+        assert_eq!(fee_stats.recent_volumes, [10, 20, 30, 40, 50, 60]);
+        assert_eq!(fee_stats.current_minute_slot, 4);
+        assert_eq!(fee_stats.last_update_timestamp, 1_700_000_000);
+        assert_eq!(fee_stats.peak_tps, 7);
+        assert_eq!(fee_stats.avg_transfer_size, 13_274);
+        assert_eq!(fee_stats.window_count(), DEFAULT_WINDOW_COUNT);
+        assert_eq!(fee_stats.authority, Pubkey::default());
+
+        // Migrated and newer accounts are left alone
+        let mut migrated = vec![0u8; DynamicFeeStats::LEN];
+        migrated[..8].copy_from_slice(DynamicFeeStats::DISCRIMINATOR);
+        migrated[8..].copy_from_slice(bytemuck::bytes_of(&fee_stats));
+        assert!(!is_legacy_fee_stats(&migrated));
+        let mut claimed = data.clone();
+        let authority = std::mem::offset_of!(DynamicFeeStats, authority) + 8;
+        claimed[authority] = 1;
+        assert!(!is_legacy_fee_stats(&claimed));
+        data[0] ^= 1;
+        assert!(!is_legacy_fee_stats(&data));
+    }
+
+    #[test]
+    fn test_decay_defaults_off() {
+        let windows = [5, 6, 7, 8, 9, 10];
+
+        // Accounts that never set a decay sum every window, like the hook did before decay
+        assert_eq!(DEFAULT_DECAY_FACTOR_BPS, 0);
+        assert_eq!(weighted_transfer_count(&windows, 3, 0), 45);
+        assert_eq!(weighted_transfer_count(&windows, 3, 10_000), 45);
+        // Halving per minute from the newest window, 8 at slot 3
+        assert_eq!(weighted_transfer_count(&windows, 3, 5_000), 14);
+
+        // Without decay a window still drops out whole when it rolls over
+        let (mut stats, mut windows) = busy_stats();
+        stats.decay_factor_bps = 0;
+        roll_windows(&mut stats, &mut windows, 1_060);
+        assert_eq!(weighted_transfer_count(&windows.transfers, stats.current_minute_slot, 0), 36);
+    }
+
+    #[test]
+    fn test_tier_thresholds() {
+        let (mut stats, _) = busy_stats();
+        assert_eq!(stats.tier_thresholds(), DEFAULT_TIER_THRESHOLDS);
+        assert_eq!(tier_fee(&stats, 10), 10);
+        assert_eq!(tier_fee(&stats, 11), 20);
+        assert_eq!(tier_fee(&stats, 121), 300);
+
+        stats.tier_thresholds = [100, 200, 400, 800];
+        assert_eq!(tier_fee(&stats, 100), 10);
+        assert_eq!(tier_fee(&stats, 101), 20);
+        assert_eq!(tier_fee(&stats, 201), 50);
+        assert_eq!(tier_fee(&stats, 401), 120);
+        assert_eq!(tier_fee(&stats, 800), 120);
+        assert_eq!(tier_fee(&stats, 801), 300);
+
+        assert!(valid_tier_thresholds(&[0; 4]));
+        assert!(valid_tier_thresholds(&[1, 2, 3, 4]));
+        assert!(!valid_tier_thresholds(&[10, 10, 60, 120]));
+        assert!(!valid_tier_thresholds(&[10, 30, 30, 120]));
+        assert!(!valid_tier_thresholds(&[120, 60, 30, 10]));
+    }
+}
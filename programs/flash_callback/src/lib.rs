@@ -0,0 +1,63 @@
+use anchor_lang::{
+    prelude::*,
+    solana_program::{
+        instruction::{AccountMeta, Instruction},
+        program::invoke,
+    },
+};
+
+declare_id!("8A9U9A4bsJFg5zZjipkB7AfyQAqExA7BP5vo8iGdKRPm");
+
+/// Flash loan callback used by the tests to call back into a pool while its loan is out
+#[program]
+pub mod flash_callback {
+    use super::*;
+
+    /// Invoke the first remaining account with `data` and the other remaining accounts
+    /// Never repays the loan, the nested call is expected to fail before that matters
+    pub fn reenter<'info>(
+        ctx: Context<'_, '_, 'info, 'info, Reenter<'info>>,
+        data: Vec<u8>,
+    ) -> Result<()> {
+        let (program, accounts) = ctx
+            .remaining_accounts
+            .split_first()
+            .ok_or(ErrorCode::AccountNotEnoughKeys)?;
+
+        let metas = accounts
+            .iter()
+            .map(|account| {
+                if account.is_writable {
+                    AccountMeta::new(account.key(), account.is_signer)
+                } else {
+                    AccountMeta::new_readonly(account.key(), account.is_signer)
+                }
+            })
+            .collect();
+
+        invoke(
+            &Instruction {
+                program_id: program.key(),
+                accounts: metas,
+                data,
+            },
+            ctx.remaining_accounts,
+        )?;
+
+        Ok(())
+    }
+}
+
+/// Accounts `flash_loan` passes every callback ahead of its remaining accounts
+#[derive(Accounts)]
+pub struct Reenter<'info> {
+    pub borrower: Signer<'info>,
+    /// CHECK: passed through by the flash loan, unused
+    pub borrower_token_account: UncheckedAccount<'info>,
+    /// CHECK: passed through by the flash loan, unused
+    pub vault: UncheckedAccount<'info>,
+    /// CHECK: passed through by the flash loan, unused
+    pub mint: UncheckedAccount<'info>,
+    /// CHECK: passed through by the flash loan, unused
+    pub token_program: UncheckedAccount<'info>,
+}
@@ -0,0 +1,151 @@
+//! Host-side helpers for building LokoSwap transactions
+//!
+//! Compiled only with the `client` feature so none of this ends up in the on-chain program.
+
+use anchor_lang::{prelude::*, solana_program::instruction::AccountMeta};
+use anchor_spl::token_interface::spl_token_2022::{
+    extension::{transfer_hook::TransferHook, BaseStateWithExtensions, StateWithExtensions},
+    state::Mint,
+};
+use spl_transfer_hook_interface::{
+    get_extra_account_metas_address, instruction::TransferHookInstruction,
+};
+
+use crate::{
+    error::AmmError,
+    utils::account_resolver::{parse_extra_account_metas, resolve_extra_account_metas},
+};
+
+/// Accounts a client must append to `remaining_accounts` for a transfer of `mint`
+///
+/// Resolves the hook's extra accounts exactly like the program does, then appends the hook
+/// program and its extra-account-metas account. Mints without an active transfer hook need none.
+/// `account_data` returns the current data of an account, used for the extra-account-metas
+/// account and for seeds derived from account data.
+pub fn transfer_hook_remaining_accounts(
+    mint: &Pubkey,
+    mint_data: &[u8],
+    source: &Pubkey,
+    destination: &Pubkey,
+    authority: &Pubkey,
+    amount: u64,
+    account_data: impl Fn(&Pubkey) -> Option<Vec<u8>>,
+) -> Result<Vec<AccountMeta>> {
+    let hook_program_id = match StateWithExtensions::<Mint>::unpack(mint_data) {
+        Ok(state) => match state.get_extension::<TransferHook>() {
+            Ok(hook) => Option::<Pubkey>::from(hook.program_id),
+            Err(_) => None,
+        },
+        // Legacy SPL Token mints have no extensions
+        Err(_) => None,
+    };
+    let Some(hook_program_id) = hook_program_id else {
+        return Ok(vec![]);
+    };
+
+    let extra_metas_address = get_extra_account_metas_address(mint, &hook_program_id);
+    let extra_metas_data = account_data(&extra_metas_address).ok_or(AmmError::HookAccountsMissing)?;
+    let extra_metas = parse_extra_account_metas(&extra_metas_data)?;
+
+    let base_accounts = [
+        AccountMeta::new(*source, false),
+        AccountMeta::new_readonly(*mint, false),
+        AccountMeta::new(*destination, false),
+        AccountMeta::new_readonly(*authority, true),
+        AccountMeta::new_readonly(extra_metas_address, false),
+    ];
+    let instruction_data = TransferHookInstruction::Execute { amount }.pack();
+
+    let mut accounts = resolve_extra_account_metas(
+        &extra_metas,
+        &hook_program_id,
+        &instruction_data,
+        &base_accounts,
+        account_data,
+    )?;
+    accounts.push(AccountMeta::new_readonly(hook_program_id, false));
+    accounts.push(AccountMeta::new_readonly(extra_metas_address, false));
+
+    Ok(accounts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_spl::token_interface::spl_token_2022::extension::{
+        BaseStateWithExtensionsMut, ExtensionType, StateWithExtensionsMut,
+    };
+    use spl_tlv_account_resolution::{
+        account::ExtraAccountMeta, seeds::Seed, state::ExtraAccountMetaList,
+    };
+    use spl_transfer_hook_interface::instruction::ExecuteInstruction;
+
+    fn hook_mint_data(program_id: Option<Pubkey>) -> Vec<u8> {
+        let len = ExtensionType::try_calculate_account_len::<Mint>(&[ExtensionType::TransferHook]).unwrap();
+        let mut data = vec![0u8; len];
+        let mut state = StateWithExtensionsMut::<Mint>::unpack_uninitialized(&mut data).unwrap();
+        state.init_extension::<TransferHook>(true).unwrap().program_id = program_id.try_into().unwrap();
+        state.base = Mint {
+            decimals: 6,
+            is_initialized: true,
+            ..Default::default()
+        };
+        state.pack_base();
+        state.init_account_type().unwrap();
+        data
+    }
+
+    #[test]
+    fn test_transfer_hook_remaining_accounts() {
+        let mint = Pubkey::new_unique();
+        let (source, destination, authority) =
+            (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let hook_program_id = Pubkey::new_unique();
+        let extra_metas_address = get_extra_account_metas_address(&mint, &hook_program_id);
+
+        let extra_metas = [ExtraAccountMeta::new_with_seeds(
+            &[Seed::Literal { bytes: b"fee_stats".to_vec() }],
+            false,
+            true,
+        )
+        .unwrap()];
+        let mut metas_data = vec![0u8; ExtraAccountMetaList::size_of(extra_metas.len()).unwrap()];
+        ExtraAccountMetaList::init::<ExecuteInstruction>(&mut metas_data, &extra_metas).unwrap();
+
+        let fetch = |key: &Pubkey| (*key == extra_metas_address).then(|| metas_data.clone());
+
+        let accounts = transfer_hook_remaining_accounts(
+            &mint,
+            &hook_mint_data(Some(hook_program_id)),
+            &source,
+            &destination,
+            &authority,
+            1_000,
+            fetch,
+        )
+        .unwrap();
+
+        let (fee_stats, _) = Pubkey::find_program_address(&[b"fee_stats"], &hook_program_id);
+        assert_eq!(
+            accounts,
+            vec![
+                AccountMeta::new(fee_stats, false),
+                AccountMeta::new_readonly(hook_program_id, false),
+                AccountMeta::new_readonly(extra_metas_address, false),
+            ]
+        );
+
+        // A cleared hook program needs no extra accounts
+        let none = transfer_hook_remaining_accounts(
+            &mint,
+            &hook_mint_data(None),
+            &source,
+            &destination,
+            &authority,
+            1_000,
+            fetch,
+        )
+        .unwrap();
+        assert!(none.is_empty());
+    }
+}
@@ -2,3 +2,59 @@ use anchor_lang::prelude::*;
 
 #[constant]
 pub const SEED: &str = "anchor";
+
+/// Layout version of `Config`, bumped whenever fields are added
+/// Pools created before versioning are version 0
+#[constant]
+pub const CONFIG_VERSION: u8 = 12;
+
+/// Highest trading fee a pool charges, in basis points
+/// Also bounds the dynamic fee a transfer hook reports
+#[constant]
+pub const MAX_FEE_BPS: u16 = 1_000;
+
+/// Largest share of the pool's sqrt(k) growth `mint_protocol_lp` can take, in basis points
+#[constant]
+pub const MAX_PROTOCOL_LP_SHARE_BPS: u16 = 5_000;
+
+/// LP supply a partial withdrawal must leave behind, in LP base units
+/// Withdrawing the whole supply is always allowed
+#[constant]
+pub const MIN_LP_SUPPLY: u64 = 1_000;
+
+/// Flash loan fee charged on new pools, in basis points
+#[constant]
+pub const DEFAULT_FLASH_FEE_BPS: u16 = 9;
+
+/// Upper bound on the share of the trading fee a swap can route to a referrer, in basis points
+#[constant]
+pub const MAX_REFERRAL_FEE_BPS: u16 = 50;
+
+/// LP mint decimals when `initialize` isn't given any
+#[constant]
+pub const DEFAULT_LP_DECIMALS: u8 = 6;
+
+/// Symbol written to the LP mint metadata
+#[constant]
+pub const LP_SYMBOL: &str = "LOKO-LP";
+
+/// Most fills `swap_batched` splits an order into, bounded by compute
+#[constant]
+pub const MAX_SWAP_STEPS: u8 = 8;
+
+/// Most source accounts `collect_fees` withdraws from in one instruction, bounded by compute
+#[constant]
+pub const MAX_FEE_SOURCES: u8 = 20;
+
+/// Largest hook extra-account-metas account the resolver parses, bounded by compute
+#[constant]
+pub const MAX_EXTRA_METAS_ACCOUNT_LEN: u32 = 10_240;
+
+/// Most `remaining_accounts` the hook legs of one instruction may need or be passed
+/// Keeps swaps of two hooked mints within what a transaction can carry
+#[constant]
+pub const MAX_HOOK_ACCOUNTS: u8 = 32;
+
+/// Most extra accounts a hook may ask the resolver for
+#[constant]
+pub const MAX_EXTRA_ACCOUNT_METAS: u8 = 32;
@@ -52,6 +52,64 @@ pub enum AmmError {
     UnsupportedHookProgram,
     #[msg("Invalid account data")]
     InvalidAccountData,
-    
+    #[msg("Protocol fee account required")]
+    MissingProtocolFeeAccount,
+    #[msg("Pool is in the middle of a flash loan")]
+    FlashLoanActive,
+    #[msg("Flash loan not repaid with fee")]
+    FlashLoanNotRepaid,
+    #[msg("Reentrant call into the pool")]
+    ReentrancyDetected,
+    #[msg("Dynamic fee stats account not provided")]
+    FeeStatsNotFound,
+    #[msg("Transfer hook accounts missing from remaining accounts")]
+    HookAccountsMissing,
+    #[msg("LP decimals must be at most 9")]
+    InvalidLpDecimals,
+    #[msg("mint_x must sort before mint_y")]
+    MintOrdering,
+    #[msg("Timelock delay has not elapsed")]
+    TimelockNotElapsed,
+    #[msg("No pending config change")]
+    NoPendingChange,
+    #[msg("Timelock delay must not be negative")]
+    InvalidTimelockDelay,
+    #[msg("Config uses an older layout, run migrate_config")]
+    ConfigOutdated,
+    #[msg("Config is already at the current version")]
+    ConfigAlreadyMigrated,
+    #[msg("Mint is not on the pool's allowlist")]
+    MintNotAllowed,
+    #[msg("Permissioned pools need a mint allowlist")]
+    AllowlistMissing,
+    #[msg("Mint allowlist is full")]
+    AllowlistFull,
+    #[msg("Fee vault not provided")]
+    MissingFeeVault,
+    #[msg("Fee source account is not a token account of the collected mint")]
+    InvalidFeeSource,
+    #[msg("Fee source account passed more than once")]
+    DuplicateFeeSource,
+    #[msg("Too many fee source accounts")]
+    TooManyFeeSources,
+    #[msg("A pool vault is frozen by its mint's freeze authority")]
+    VaultFrozen,
+    #[msg("Transfer hook program is not the pool's approved hook")]
+    UnauthorizedHookProgram,
+    #[msg("Tracked reserves exceed the vault balance")]
+    ReservesExceedVault,
+    #[msg("Transfer hooks need more accounts than fit in a transaction")]
+    TooManyHookAccounts,
+    #[msg("Pool is in emergency mode, only emergency withdrawals are allowed")]
+    EmergencyMode,
+    #[msg("Pool is not in emergency mode")]
+    NotInEmergency,
+    #[msg("Source token account has CPI Guard enabled, disable it or approve a delegate")]
+    CpiGuardEnabled,
+    #[msg("Protocol LP minting is off for this pool")]
+    ProtocolLpDisabled,
+    #[msg("Withdrawal would leave less than the minimum LP supply, withdraw less or everything")]
+    LpSupplyTooLow,
+    #[msg("Transfer hook failed, see the program log for the underlying error")]
+    HookExecutionFailed,
 }
-
@@ -0,0 +1,212 @@
+use anchor_lang::prelude::*;
+
+use crate::state::ConfigChange;
+
+/// Emitted when a pool is created, so indexers can discover pools without scraping logs
+#[event]
+pub struct PoolInitialized {
+    pub config: Pubkey,
+    pub mint_x: Pubkey,
+    pub mint_y: Pubkey,
+    pub mint_lp: Pubkey,
+    pub fee: u16,
+    pub authority: Option<Pubkey>,
+    pub supports_transfer_fees: bool,
+    pub supports_transfer_hooks: bool,
+}
+
+/// Emitted after every successful swap
+#[event]
+pub struct SwapEvent {
+    pub config: Pubkey,
+    pub user: Pubkey,
+    pub is_x: bool,
+    pub amount_in: u64,
+    pub amount_out: u64,
+    pub fee_basis_points: u16,
+    pub protocol_fee: u64,
+    pub referral_fee: u64,
+}
+
+/// Snapshot of a pool's state, emitted and returned by `pool_info`
+#[event]
+#[derive(Clone)]
+pub struct PoolInfo {
+    pub config: Pubkey,
+    pub reserve_x: u64,
+    pub reserve_y: u64,
+    pub lp_supply: u64,
+    pub fee: u16,
+    pub protocol_fee_bps: u16,
+    pub fee_enabled: bool,
+    pub locked: bool,
+    pub supports_transfer_fees: bool,
+    pub supports_transfer_hooks: bool,
+    pub supports_metadata: bool,
+    pub supports_interest_bearing: bool,
+    pub cumulative_volume_x: u128,
+    pub cumulative_volume_y: u128,
+    pub swap_count: u64,
+}
+
+/// Tracked reserves against the live vault balances, emitted and returned by `check_invariants`
+#[event]
+#[derive(Clone)]
+pub struct HealthReport {
+    pub config: Pubkey,
+    pub reserve_x: u64,
+    pub reserve_y: u64,
+    pub vault_x: u64,
+    pub vault_y: u64,
+    /// Vault balance minus tracked reserve, positive for tokens `sync` or `skim` would pick up
+    pub drift_x: i128,
+    pub drift_y: i128,
+    /// reserve_x · reserve_y the curve prices against
+    pub k: u128,
+    /// False when a vault holds less than its tracked reserve
+    pub healthy: bool,
+}
+
+/// Decimals-normalized price of the pool, emitted and returned by `spot_price`
+#[event]
+#[derive(Clone)]
+pub struct SpotPrice {
+    pub config: Pubkey,
+    /// Price of one whole X token in whole Y tokens, Q64.64 fixed point
+    pub price_x_in_y_q64: u128,
+    pub decimals_x: u8,
+    pub decimals_y: u8,
+}
+
+/// Reserves an LP amount is currently worth, emitted and returned by `lp_to_tokens`
+#[event]
+#[derive(Clone)]
+pub struct LpValue {
+    pub config: Pubkey,
+    pub lp_amount: u64,
+    /// Pro-rata reserves, before the mints' transfer fees
+    pub amount_x: u64,
+    pub amount_y: u64,
+}
+
+/// Expected result of a swap, emitted and returned by `quote_swap`
+#[event]
+#[derive(Clone)]
+pub struct SwapQuote {
+    pub config: Pubkey,
+    pub is_x: bool,
+    pub amount_in: u64,
+    /// Output the trader would receive, after the output mint's transfer fee
+    pub amount_out: u64,
+    /// Fee rate the swap would charge, including a transfer hook's dynamic fee
+    pub fee_basis_points: u16,
+    /// Swap fee in input tokens, protocol share included
+    pub fee: u64,
+    /// Input mint's transfer fee on `amount_in`
+    pub transfer_fee_in: u64,
+}
+
+/// Emitted when an authority change is queued behind the pool's timelock
+#[event]
+pub struct ConfigChangeQueued {
+    pub config: Pubkey,
+    pub change: ConfigChange,
+    pub effective_at: i64,
+}
+
+/// Emitted when an authority change takes effect
+#[event]
+pub struct ConfigChangeApplied {
+    pub config: Pubkey,
+    pub change: ConfigChange,
+}
+
+/// Emitted when a new trading fee takes effect
+#[event]
+pub struct TradingFeeUpdated {
+    pub config: Pubkey,
+    pub old_fee: u16,
+    pub new_fee: u16,
+}
+
+/// Emitted by `collect_fees` with the batch it processed, and by `collect_fees_from_mint` with no accounts
+/// `amount` is the balance increase of the collection target
+/// A non-zero `accounts_remaining` means the caller should collect from the last that many
+/// accounts it passed in another call. `sources` lists the accounts collected from,
+/// empty accounts skipped on the way are only counted.
+#[event]
+pub struct FeesCollected {
+    pub config: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub accounts_processed: u32,
+    pub accounts_skipped: u32,
+    pub accounts_remaining: u32,
+    pub sources: Vec<Pubkey>,
+}
+
+/// Emitted when collected transfer fees are deposited back into the pool as protocol-owned liquidity
+#[event]
+pub struct FeesReinvested {
+    pub config: Pubkey,
+    pub amount_x: u64,
+    pub amount_y: u64,
+    pub lp_minted: u64,
+}
+
+/// Emitted when the protocol is minted its share of the pool's sqrt(k) growth
+#[event]
+pub struct ProtocolLpMinted {
+    pub config: Pubkey,
+    pub destination: Pubkey,
+    pub k_sqrt: u128,
+    pub lp_minted: u64,
+}
+
+/// Emitted when collected transfer fees leave the fee vault
+#[event]
+pub struct FeesDistributed {
+    pub config: Pubkey,
+    pub mint: Pubkey,
+    pub destination: Pubkey,
+    pub amount: u64,
+}
+
+/// Emitted when reserves are added without minting LP, amounts are what reached the vaults
+#[event]
+pub struct LiquidityDonated {
+    pub config: Pubkey,
+    pub donor: Pubkey,
+    pub amount_x: u64,
+    pub amount_y: u64,
+}
+
+/// Emitted when the config's WSOL account is funded and approved for the hook's dynamic fee
+#[event]
+pub struct HookFeeFunded {
+    pub config: Pubkey,
+    pub delegate: Pubkey,
+    pub amount: u64,
+    pub balance: u64,
+}
+
+/// One extra account a hook's Execute instruction needs, in the order the hook expects
+/// `provided` tells whether it was among the remaining accounts of the simulation
+#[cfg(feature = "devnet-tools")]
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct ResolvedHookAccount {
+    pub pubkey: Pubkey,
+    pub is_signer: bool,
+    pub is_writable: bool,
+    pub provided: bool,
+}
+
+/// Emitted by `simulate_hook_resolution` with the extra accounts a transfer of `mint` needs
+/// The hook program and its extra-account-metas account follow them in `remaining_accounts`
+#[cfg(feature = "devnet-tools")]
+#[event]
+pub struct HookAccountsResolved {
+    pub mint: Pubkey,
+    pub hook_program: Pubkey,
+    pub accounts: Vec<ResolvedHookAccount>,
+}
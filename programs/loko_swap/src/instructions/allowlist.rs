@@ -0,0 +1,64 @@
+use anchor_lang::prelude::*;
+
+use crate::state::MintAllowlist;
+
+/// Account structure for creating a mint allowlist owned by `authority`
+#[derive(Accounts)]
+pub struct CreateAllowlist<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"allowlist", authority.key().as_ref()],
+        bump,
+        space = 8 + MintAllowlist::INIT_SPACE
+    )]
+    pub mint_allowlist: Account<'info, MintAllowlist>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> CreateAllowlist<'info> {
+    pub fn create_allowlist(&mut self, bumps: &CreateAllowlistBumps) -> Result<()> {
+        self.mint_allowlist.set_inner(MintAllowlist {
+            authority: self.authority.key(),
+            bump: bumps.mint_allowlist,
+            mints: vec![],
+        });
+
+        msg!("Mint allowlist created: {}", self.mint_allowlist.key());
+
+        Ok(())
+    }
+}
+
+/// Account structure for adding or removing allowlisted mints
+/// Removing a mint doesn't affect pools that already pair it
+#[derive(Accounts)]
+pub struct UpdateAllowlist<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"allowlist", authority.key().as_ref()],
+        bump = mint_allowlist.bump,
+        has_one = authority
+    )]
+    pub mint_allowlist: Account<'info, MintAllowlist>,
+}
+
+impl<'info> UpdateAllowlist<'info> {
+    pub fn add_allowed_mint(&mut self, mint: Pubkey) -> Result<()> {
+        self.mint_allowlist.add(mint)?;
+        msg!("Mint {} allowed", mint);
+        Ok(())
+    }
+
+    pub fn remove_allowed_mint(&mut self, mint: Pubkey) -> Result<()> {
+        self.mint_allowlist.remove(&mint)?;
+        msg!("Mint {} removed from allowlist", mint);
+        Ok(())
+    }
+}
@@ -2,14 +2,20 @@ use anchor_lang::prelude::*;
 use anchor_spl::{
     associated_token::AssociatedToken,
     token_interface::{
-        mint_to, transfer_checked, Mint, MintTo, TokenAccount, TokenInterface, TransferChecked,
-        transfer_checked_with_fee, TransferCheckedWithFee,
+        mint_to, Mint, MintTo, TokenAccount, TokenInterface,
     },
 };
 use crate::{
     error::AmmError, 
+    instructions::swap::{pool_swap_fee, price_swap, SwapPricing},
     state::Config,
-    utils::token_utils::{TokenExtensions, invoke_transfer_checked_with_hooks},
+    utils::{
+        math::{deposit_amount_for_lp, lp_for_exact_amounts, mul_div_floor, optimal_swap_amount},
+        native_sol::{unwrap_sol, wrap_sol},
+        token_utils::{
+            check_hook_account_budget, check_pool_vaults, check_vaults_not_frozen, TokenExtensions, transfer_with_extensions,
+        },
+    },
 };
 use constant_product_curve::ConstantProduct;
 
@@ -22,7 +28,8 @@ pub struct Deposit<'info> {
     pub mint_y: InterfaceAccount<'info, Mint>,
 
     #[account(
-        mut,
+        init_if_needed,
+        payer = user,
         associated_token::mint = mint_x,
         associated_token::authority = user,
         associated_token::token_program = token_program
@@ -30,7 +37,8 @@ pub struct Deposit<'info> {
     pub user_x: Box<InterfaceAccount<'info, TokenAccount>>,
 
     #[account(
-        mut,
+        init_if_needed,
+        payer = user,
         associated_token::mint = mint_y,
         associated_token::authority = user,
         associated_token::token_program = token_program
@@ -54,6 +62,7 @@ pub struct Deposit<'info> {
     pub vault_y: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
+        mut,
         seeds = [b"config", config.seed.to_be_bytes().as_ref()],
         bump = config.config_bump
     )]
@@ -74,6 +83,11 @@ pub struct Deposit<'info> {
     )]
     pub user_lp: InterfaceAccount<'info, TokenAccount>,
 
+    /// Receives the protocol share of a single-sided deposit's swap fee, as in `swap`
+    /// Must hold the input mint and be owned by `config.protocol_fee_destination`
+    #[account(mut)]
+    pub protocol_fee_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
     pub associated_token_program: Program<'info, AssociatedToken>,
     pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
@@ -85,14 +99,44 @@ impl<'info> Deposit<'info> {
         amount: u64,
         max_x: u64,
         max_y: u64,
+        min_lp_out: u64,
+        deadline: i64,
+        native_sol: bool,
         _remaining_accounts: &[AccountInfo<'info>],
     ) -> Result<()> {
+        compute_trace!("deposit: start");
+        self.config.check_version()?;
         require!(self.config.locked == false, AmmError::PoolLocked);
+        require!(!self.config.borrowing, AmmError::FlashLoanActive);
+        require!(!self.config.emergency, AmmError::EmergencyMode);
+
+        let now = Clock::get()?.unix_timestamp;
+        check_deadline(deadline, now)?;
+
+        // Persist the guard so a nested call into this pool fails on entry
+        self.config.enter()?;
+        self.config.exit(&crate::ID)?;
+
+        // Oracle sees the reserves as they were before this instruction
+        self.config.update_oracle(now);
+
         require!(amount > 0, AmmError::InvalidAmount);
         
         // Manual validation replacing has_one constraints
         require!(self.config.mint_x == self.mint_x.key(), AmmError::InvalidToken);
         require!(self.config.mint_y == self.mint_y.key(), AmmError::InvalidToken);
+        check_pool_vaults(
+            &self.config.key(),
+            &self.config.mint_x,
+            &self.config.mint_y,
+            &self.vault_x,
+            &self.vault_y,
+        )?;
+        check_vaults_not_frozen(&self.vault_x, &self.vault_y)?;
+        check_hook_account_budget(
+            &[self.mint_x.to_account_info(), self.mint_y.to_account_info()],
+            _remaining_accounts,
+        )?;
 
         // Calculate transfer fees (scoped to minimize stack lifetime)
         let (x_transfer_fee, y_transfer_fee) = {
@@ -107,32 +151,40 @@ impl<'info> Deposit<'info> {
 
         require!(net_max_x > 0 && net_max_y > 0, AmmError::InvalidAmount);
 
+        // Price against tracked reserves so tokens donated to the vaults can't skew the ratio
         let (x, y) = if self.mint_lp.supply == 0 
-            && self.vault_x.amount == 0 
-            && self.vault_y.amount == 0 
+            && self.config.reserve_x == 0 
+            && self.config.reserve_y == 0 
         {
-            // Initial deposit - use net amounts
+            // Initial deposit - use net amounts, minting sqrt(x * y) of what reaches the vaults
             (net_max_x, net_max_y)
         } else {
             // Calculate required amounts based on current pool ratio
             let amounts = ConstantProduct::xy_deposit_amounts_from_l(
-                self.vault_x.amount,
-                self.vault_y.amount,
+                self.config.reserve_x,
+                self.config.reserve_y,
                 self.mint_lp.supply,
                 amount,
-                6,
+                self.mint_lp.decimals as u32,
             )
             .map_err(|_| AmmError::MathOverflow)?;
 
+            // Never take less than the exact share, whichever way the curve rounded
+            let x = amounts.x.max(deposit_amount_for_lp(self.config.reserve_x, self.mint_lp.supply, amount)?);
+            let y = amounts.y.max(deposit_amount_for_lp(self.config.reserve_y, self.mint_lp.supply, amount)?);
+
             // Ensure we don't exceed the net amounts user is willing to deposit
             require!(
-                amounts.x <= net_max_x && amounts.y <= net_max_y,
+                x <= net_max_x && y <= net_max_y,
                 AmmError::SlippageExceeded
             );
 
-            (amounts.x, amounts.y)
+            (x, y)
         };
 
+        // Dust deposits can round to nothing while still minting `amount` LP
+        require!(x > 0 && y > 0, AmmError::InvalidAmount);
+
         // Calculate the gross amounts needed (including fees) to get the net amounts
         let (gross_x, gross_y) = {
             let x_ext = TokenExtensions::new(&self.mint_x.to_account_info())?;
@@ -143,19 +195,316 @@ impl<'info> Deposit<'info> {
         require!(gross_x <= max_x && gross_y <= max_y, AmmError::SlippageExceeded);
 
         // Perform transfers (these will deduct fees automatically)
+        let (before_x, before_y) = (self.vault_x.amount, self.vault_y.amount);
+        if native_sol {
+            self.wrap_sol_input(true, gross_x)?;
+            self.wrap_sol_input(false, gross_y)?;
+        }
         self.deposit_tokens(true, gross_x, _remaining_accounts)?;
         self.deposit_tokens(false, gross_y, _remaining_accounts)?;
 
+        // Track what actually reached the vaults
+        self.vault_x.reload()?;
+        self.vault_y.reload()?;
+        let lp_amount = self.settled_lp(amount, before_x, before_y)?;
+        require!(lp_amount >= min_lp_out, AmmError::SlippageExceeded);
+        self.config.apply_vault_deltas(before_x, self.vault_x.amount, before_y, self.vault_y.amount)?;
+        self.config.check_reserves_backed(self.vault_x.amount, self.vault_y.amount)?;
+
         // Mint LP tokens based on the net amounts that reached the vault
-        self.mint_lp_tokens(amount)
+        self.mint_lp_tokens(lp_amount)?;
+
+        if native_sol {
+            self.unwrap_sol_accounts()?;
+        }
+
+        self.config.leave();
+
+        compute_trace!("deposit: end");
+
+        Ok(())
     }
 
+    /// Deposit up to `desired_x` and `desired_y`, minting LP for whichever side binds at the pool ratio
+    /// Only the matching amount of the other side is transferred
+    pub fn deposit_exact_tokens(
+        &mut self,
+        desired_x: u64,
+        desired_y: u64,
+        min_lp_out: u64,
+        deadline: i64,
+        native_sol: bool,
+        _remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        self.config.check_version()?;
+        require!(self.config.locked == false, AmmError::PoolLocked);
+        require!(!self.config.borrowing, AmmError::FlashLoanActive);
+        require!(!self.config.emergency, AmmError::EmergencyMode);
 
-    pub fn deposit_tokens(
+        let now = Clock::get()?.unix_timestamp;
+        check_deadline(deadline, now)?;
+
+        // Persist the guard so a nested call into this pool fails on entry
+        self.config.enter()?;
+        self.config.exit(&crate::ID)?;
+
+        // Oracle sees the reserves as they were before this instruction
+        self.config.update_oracle(now);
+
+        require!(desired_x > 0 && desired_y > 0, AmmError::InvalidAmount);
+
+        require!(self.config.mint_x == self.mint_x.key(), AmmError::InvalidToken);
+        require!(self.config.mint_y == self.mint_y.key(), AmmError::InvalidToken);
+        check_pool_vaults(
+            &self.config.key(),
+            &self.config.mint_x,
+            &self.config.mint_y,
+            &self.vault_x,
+            &self.vault_y,
+        )?;
+        check_vaults_not_frozen(&self.vault_x, &self.vault_y)?;
+        check_hook_account_budget(
+            &[self.mint_x.to_account_info(), self.mint_y.to_account_info()],
+            _remaining_accounts,
+        )?;
+
+        let x_ext = TokenExtensions::new(&self.mint_x.to_account_info())?;
+        let y_ext = TokenExtensions::new(&self.mint_y.to_account_info())?;
+
+        // Net amounts that would reach the vault after transfer fees
+        let net_x = desired_x.saturating_sub(x_ext.calculate_fee(desired_x));
+        let net_y = desired_y.saturating_sub(y_ext.calculate_fee(desired_y));
+
+        let (lp_amount, x, y) = lp_for_exact_amounts(
+            net_x,
+            net_y,
+            self.config.reserve_x,
+            self.config.reserve_y,
+            self.mint_lp.supply,
+        )?;
+
+        require!(lp_amount > 0, AmmError::InvalidAmount);
+        require!(lp_amount >= min_lp_out, AmmError::SlippageExceeded);
+
+        // Gross up for the transfer fees, never beyond what the user offered
+        let gross_x = x_ext.calculate_gross_for_net(x).min(desired_x);
+        let gross_y = y_ext.calculate_gross_for_net(y).min(desired_y);
+
+        let (before_x, before_y) = (self.vault_x.amount, self.vault_y.amount);
+        if native_sol {
+            self.wrap_sol_input(true, gross_x)?;
+            self.wrap_sol_input(false, gross_y)?;
+        }
+        self.deposit_tokens(true, gross_x, _remaining_accounts)?;
+        self.deposit_tokens(false, gross_y, _remaining_accounts)?;
+
+        // Track what actually reached the vaults
+        self.vault_x.reload()?;
+        self.vault_y.reload()?;
+        let lp_amount = self.settled_lp(lp_amount, before_x, before_y)?;
+        require!(lp_amount >= min_lp_out, AmmError::SlippageExceeded);
+        self.config.apply_vault_deltas(before_x, self.vault_x.amount, before_y, self.vault_y.amount)?;
+        self.config.check_reserves_backed(self.vault_x.amount, self.vault_y.amount)?;
+
+        self.mint_lp_tokens(lp_amount)?;
+
+        if native_sol {
+            self.unwrap_sol_accounts()?;
+        }
+
+        self.config.leave();
+
+        Ok(())
+    }
+
+    /// Deposit a single token, swapping the optimal share of it against the pool
+    /// The swap is settled inside the vaults, so only the input leg pays a transfer fee
+    pub fn deposit_single_sided(
         &mut self,
         is_x: bool,
         amount: u64,
+        min_lp_out: u64,
+        deadline: i64,
+        native_sol: bool,
         _remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        self.config.check_version()?;
+        require!(self.config.locked == false, AmmError::PoolLocked);
+        require!(!self.config.borrowing, AmmError::FlashLoanActive);
+        require!(!self.config.emergency, AmmError::EmergencyMode);
+
+        let now = Clock::get()?.unix_timestamp;
+        check_deadline(deadline, now)?;
+
+        // Persist the guard so a nested call into this pool fails on entry
+        self.config.enter()?;
+        self.config.exit(&crate::ID)?;
+
+        // Oracle sees the reserves as they were before this instruction
+        self.config.update_oracle(now);
+
+        require!(amount > 0, AmmError::InvalidAmount);
+
+        require!(self.config.mint_x == self.mint_x.key(), AmmError::InvalidToken);
+        require!(self.config.mint_y == self.mint_y.key(), AmmError::InvalidToken);
+        check_pool_vaults(
+            &self.config.key(),
+            &self.config.mint_x,
+            &self.config.mint_y,
+            &self.vault_x,
+            &self.vault_y,
+        )?;
+        check_vaults_not_frozen(&self.vault_x, &self.vault_y)?;
+        let input_mint = if is_x { &self.mint_x } else { &self.mint_y };
+        check_hook_account_budget(&[input_mint.to_account_info()], _remaining_accounts)?;
+
+        let supply = self.mint_lp.supply;
+        let x_ext = TokenExtensions::new(&self.mint_x.to_account_info())?;
+        let y_ext = TokenExtensions::new(&self.mint_y.to_account_info())?;
+        let input_ext = if is_x { &x_ext } else { &y_ext };
+
+        // Net amount that will reach the vault after the input transfer fee
+        let net_amount_in = amount.saturating_sub(input_ext.calculate_fee(amount));
+
+        // The implicit swap pays the fee a swap would, read before the input transfer updates the hook's stats
+        let fee_bps = pool_swap_fee(&self.config, &x_ext, &y_ext, _remaining_accounts)?;
+        let plan = plan_single_sided(&self.config, is_x, net_amount_in, supply, fee_bps)?;
+
+        require!(plan.lp_amount > 0, AmmError::InvalidAmount);
+        require!(plan.lp_amount >= min_lp_out, AmmError::SlippageExceeded);
+
+        let (before_x, before_y) = (self.vault_x.amount, self.vault_y.amount);
+        if native_sol {
+            self.wrap_sol_input(is_x, amount)?;
+        }
+        self.deposit_tokens(is_x, amount, _remaining_accounts)?;
+
+        // Track what actually reached the vaults
+        self.vault_x.reload()?;
+        self.vault_y.reload()?;
+
+        // A vault credited less than estimated only shrinks the deposited part
+        let received_in = if is_x {
+            self.vault_x.amount.checked_sub(before_x)
+        } else {
+            self.vault_y.amount.checked_sub(before_y)
+        }
+        .ok_or(AmmError::MathOverflow)?;
+        let settled_in = received_in.checked_sub(plan.swap_amount).ok_or(AmmError::InsufficientFunds)?;
+        let lp_amount = plan.lp_amount.min(mul_div_floor(settled_in, supply, plan.post_reserve_in)?);
+        require!(lp_amount > 0, AmmError::InvalidAmount);
+        require!(lp_amount >= min_lp_out, AmmError::SlippageExceeded);
+
+        // Protocol share of the swap fee leaves the input vault, as it does for a swap
+        if plan.protocol_fee > 0 {
+            self.transfer_protocol_fee(is_x, plan.protocol_fee, input_ext, _remaining_accounts)?;
+            self.vault_x.reload()?;
+            self.vault_y.reload()?;
+        }
+
+        self.config.apply_vault_deltas(before_x, self.vault_x.amount, before_y, self.vault_y.amount)?;
+        self.config.check_reserves_backed(self.vault_x.amount, self.vault_y.amount)?;
+
+        self.mint_lp_tokens(lp_amount)?;
+
+        if native_sol {
+            self.unwrap_sol_accounts()?;
+        }
+
+        self.config.leave();
+
+        Ok(())
+    }
+
+
+    /// Fund the user's WSOL account of one side from their lamports
+    /// A failed deposit reverts the wrap with everything else
+    fn wrap_sol_input(&self, is_x: bool, amount: u64) -> Result<()> {
+        let user_account = if is_x { &self.user_x } else { &self.user_y };
+        wrap_sol(
+            self.system_program.to_account_info(),
+            self.token_program.to_account_info(),
+            self.user.to_account_info(),
+            user_account,
+            amount,
+        )
+    }
+
+    /// LP to mint for what actually reached the vaults, never more than `planned` once the pool has supply
+    /// Transfer fees or hooks taking more than estimated would otherwise mint LP the pool wasn't paid for.
+    /// Call after reloading the vaults and before the reserves are updated
+    fn settled_lp(&self, planned: u64, before_x: u64, before_y: u64) -> Result<u64> {
+        let received_x = self.vault_x.amount.checked_sub(before_x).ok_or(AmmError::MathOverflow)?;
+        let received_y = self.vault_y.amount.checked_sub(before_y).ok_or(AmmError::MathOverflow)?;
+        settled_lp_amount(
+            planned,
+            received_x,
+            received_y,
+            self.config.reserve_x,
+            self.config.reserve_y,
+            self.mint_lp.supply,
+        )
+    }
+
+    /// Send the protocol share of a single-sided deposit's swap fee from the input vault
+    fn transfer_protocol_fee(
+        &self,
+        is_x: bool,
+        amount: u64,
+        extensions: &TokenExtensions,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        let protocol_fee_account = self
+            .protocol_fee_account
+            .as_ref()
+            .ok_or(AmmError::MissingProtocolFeeAccount)?;
+
+        let (vault, mint) = if is_x {
+            (&self.vault_x, &self.mint_x)
+        } else {
+            (&self.vault_y, &self.mint_y)
+        };
+
+        require!(protocol_fee_account.mint == mint.key(), AmmError::InvalidTokenAccount);
+        require!(
+            protocol_fee_account.owner == self.config.protocol_fee_destination,
+            AmmError::InvalidTokenAccount
+        );
+
+        let seeds = &[
+            b"config",
+            &self.config.seed.to_be_bytes()[..],
+            &[self.config.config_bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        transfer_with_extensions(
+            self.token_program.to_account_info(),
+            vault.to_account_info(),
+            mint.to_account_info(),
+            protocol_fee_account.to_account_info(),
+            self.config.to_account_info(),
+            extensions,
+            remaining_accounts,
+            amount,
+            mint.decimals,
+            signer_seeds,
+        )
+    }
+
+    /// Close the user's WSOL accounts so any wrapped SOL left over comes back as lamports
+    fn unwrap_sol_accounts(&self) -> Result<()> {
+        for account in [&self.user_x, &self.user_y] {
+            unwrap_sol(self.token_program.to_account_info(), self.user.to_account_info(), account)?;
+        }
+        Ok(())
+    }
+
+    pub fn deposit_tokens(
+        &mut self,
+        is_x: bool,
+        amount: u64,
+        remaining_accounts: &[AccountInfo<'info>],
     ) -> Result<()> {
         let (from, to, mint) = if is_x {
             (
@@ -171,74 +520,27 @@ impl<'info> Deposit<'info> {
             )
         };
 
-        let decimals = mint.decimals;
-        let cpi_program = self.token_program.to_account_info();
-
-        // Get extension information using centralized utilities (boxed for stack efficiency)
         let extensions = TokenExtensions::new(&mint.to_account_info())?;
 
-        match (extensions.has_transfer_fee, extensions.has_transfer_hook) {
-            // Token with transfer fee only
-            (true, false) => {
-                let cpi_accounts = TransferCheckedWithFee {
-                    source: from.to_account_info(),
-                    destination: to.to_account_info(),
-                    authority: self.user.to_account_info(),
-                    mint: mint.to_account_info(),
-                    token_program_id: cpi_program.clone(),
-                };
-                let ctx = CpiContext::new(cpi_program, cpi_accounts);
-                let expected_fee = extensions.calculate_fee(amount);
-                transfer_checked_with_fee(ctx, amount, decimals, expected_fee)?;
-            }
-            
-            // Token with BOTH transfer fee AND transfer hook - use direct Token-2022 call
-            (true, true) => {
-                invoke_transfer_checked_with_hooks(
-                    &cpi_program.key(),
-                    from.to_account_info(),
-                    mint.to_account_info(),
-                    to.to_account_info(),
-                    self.user.to_account_info(),
-                    _remaining_accounts,
-                    amount,
-                    decimals,
-                    &[], // No signer seeds needed for user authority
-                )?;
-            }
-            
-            // Token with transfer hook only - use direct Token-2022 call
-            (false, true) => {
-                invoke_transfer_checked_with_hooks(
-                    &cpi_program.key(),
-                    from.to_account_info(),
-                    mint.to_account_info(),
-                    to.to_account_info(),
-                    self.user.to_account_info(),
-                    _remaining_accounts,
-                    amount,
-                    decimals,
-                    &[], // No signer seeds needed for user authority
-                )?;
-            }
-            
-            // Standard token (no extensions)
-            (false, false) => {
-                let cpi_accounts = TransferChecked {
-                    from: from.to_account_info(),
-                    to: to.to_account_info(),
-                    authority: self.user.to_account_info(),
-                    mint: mint.to_account_info(),
-                };
-                let ctx = CpiContext::new(cpi_program, cpi_accounts);
-                transfer_checked(ctx, amount, decimals)?;
-            }
-        }
-
-        Ok(())
+        // No signer seeds needed for user authority
+        transfer_with_extensions(
+            self.token_program.to_account_info(),
+            from.to_account_info(),
+            mint.to_account_info(),
+            to.to_account_info(),
+            self.user.to_account_info(),
+            &extensions,
+            remaining_accounts,
+            amount,
+            mint.decimals,
+            &[],
+        )
     }
 
     pub fn mint_lp_tokens(&mut self, amount: u64) -> Result<()> {
+        // Fail with a pool error before the token program would with its own
+        self.mint_lp.supply.checked_add(amount).ok_or(AmmError::MathOverflow)?;
+
         let cpi_accounts = MintTo {
             mint: self.mint_lp.to_account_info(),
             to: self.user_lp.to_account_info(),
@@ -262,4 +564,199 @@ impl<'info> Deposit<'info> {
         mint_to(ctx, amount)?;
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+/// Implicit swap and LP of a single-sided deposit of `net_amount_in`
+struct SingleSidedPlan {
+    swap_amount: u64,
+    protocol_fee: u64,
+    /// Input reserve after the swap, once the protocol share has left the vault
+    post_reserve_in: u64,
+    lp_amount: u64,
+}
+
+/// Swap the optimal share of `net_amount_in` at `fee_bps` and price the LP for the rest
+/// The swap goes through `price_swap`, so it pays the fee and protocol share a `swap` would
+fn plan_single_sided(
+    config: &Config,
+    is_x: bool,
+    net_amount_in: u64,
+    supply: u64,
+    fee_bps: u16,
+) -> Result<SingleSidedPlan> {
+    let (reserve_in, reserve_out) = if is_x {
+        (config.reserve_x, config.reserve_y)
+    } else {
+        (config.reserve_y, config.reserve_x)
+    };
+    require!(
+        supply > 0 && reserve_in > 0 && reserve_out > 0,
+        AmmError::NoLiquidityInPool
+    );
+
+    // Swap part of the input through the curve, the output never leaves the vault
+    let swap_amount = optimal_swap_amount(reserve_in, net_amount_in, fee_bps)?;
+    require!(
+        swap_amount > 0 && swap_amount < net_amount_in,
+        AmmError::InvalidAmount
+    );
+    let SwapPricing { protocol_fee, amount_out, .. } = price_swap(config, is_x, swap_amount, supply, fee_bps, 0)?;
+
+    // Deposit the rest of the input and the swap output against the post-swap reserves
+    let deposit_in = net_amount_in
+        .checked_sub(swap_amount)
+        .ok_or(AmmError::Underflow)?;
+    let post_reserve_in = reserve_in
+        .checked_add(swap_amount)
+        .ok_or(AmmError::Overflow)?
+        .checked_sub(protocol_fee)
+        .ok_or(AmmError::Underflow)?;
+    let post_reserve_out = reserve_out
+        .checked_sub(amount_out)
+        .ok_or(AmmError::Underflow)?;
+
+    let lp_from_in = mul_div_floor(deposit_in, supply, post_reserve_in)?;
+    let lp_from_out = mul_div_floor(amount_out, supply, post_reserve_out)?;
+
+    Ok(SingleSidedPlan {
+        swap_amount,
+        protocol_fee,
+        post_reserve_in,
+        lp_amount: std::cmp::min(lp_from_in, lp_from_out),
+    })
+}
+
+/// Fail once `now` is past `deadline`, a deadline of 0 never expires
+fn check_deadline(deadline: i64, now: i64) -> Result<()> {
+    require!(deadline == 0 || now <= deadline, AmmError::OfferExpired);
+    Ok(())
+}
+
+/// LP justified by `received_x`/`received_y` actually reaching the vaults, capped at `planned`
+/// A fee change or hook can credit a vault less than predicted, and the LP shrinks to match.
+/// The pool's first deposit mints sqrt(x * y) of what arrived instead, with `planned` as its minimum.
+fn settled_lp_amount(
+    planned: u64,
+    received_x: u64,
+    received_y: u64,
+    reserve_x: u64,
+    reserve_y: u64,
+    supply: u64,
+) -> Result<u64> {
+    let (justified, _, _) = lp_for_exact_amounts(received_x, received_y, reserve_x, reserve_y, supply)?;
+    if supply == 0 {
+        require!(justified >= planned, AmmError::SlippageExceeded);
+        return Ok(justified);
+    }
+    Ok(planned.min(justified))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_spl::token_interface::spl_token_2022::extension::transfer_fee::TransferFee;
+
+    #[test]
+    fn test_check_deadline() {
+        assert!(check_deadline(0, 1_700_000_000).is_ok());
+        assert!(check_deadline(1_700_000_000, 1_700_000_000).is_ok());
+        assert_eq!(
+            check_deadline(1_700_000_000, 1_700_000_001).unwrap_err(),
+            error!(AmmError::OfferExpired)
+        );
+    }
+
+    #[test]
+    fn test_settled_lp_with_fee_token() {
+        let (reserve_x, reserve_y, supply) = (1_000_000, 1_000_000, 1_000_000);
+        let planned = 10_104;
+        let needed_y = deposit_amount_for_lp(reserve_y, supply, planned).unwrap();
+
+        // A 1% fee token whose fee on the gross amount has a fractional part
+        let y_ext = TokenExtensions {
+            has_transfer_fee: true,
+            has_transfer_hook: false,
+            transfer_hook_program_id: None,
+            transfer_fee_basis_points: 100,
+            transfer_fee_maximum: u64::MAX,
+        };
+        let gross_y = y_ext.calculate_gross_for_net(needed_y);
+        let token_fee = TransferFee {
+            epoch: 0.into(),
+            maximum_fee: u64::MAX.into(),
+            transfer_fee_basis_points: 100.into(),
+        };
+        assert_ne!(gross_y % 100, 0);
+
+        // The expected fee matches the one Token-2022 checks in transfer_checked_with_fee
+        assert_eq!(y_ext.calculate_fee(gross_y), token_fee.calculate_fee(gross_y).unwrap());
+
+        // The gross-up rounds like Token-2022, so the vault receives exactly what was priced
+        let received_y = gross_y - y_ext.calculate_fee(gross_y);
+        assert_eq!(received_y, needed_y);
+        assert_eq!(settled_lp_amount(planned, needed_y, received_y, reserve_x, reserve_y, supply).unwrap(), planned);
+
+        // A vault credited a unit short, e.g. after a fee change, isn't paid the full planned LP
+        let lp = settled_lp_amount(planned, needed_y, needed_y - 1, reserve_x, reserve_y, supply).unwrap();
+        assert_eq!(lp, planned - 1);
+
+        // More than predicted never mints extra
+        assert_eq!(settled_lp_amount(planned, needed_y * 2, needed_y * 2, reserve_x, reserve_y, supply).unwrap(), planned);
+    }
+
+    #[test]
+    fn test_settled_lp_near_u64_max() {
+        // The first deposit mints sqrt(x * y) of what arrived, which the u64 product would overflow
+        assert_eq!(settled_lp_amount(1, u64::MAX, u64::MAX, 0, 0, 0).unwrap(), u64::MAX);
+        assert_eq!(settled_lp_amount(1, u64::MAX, 4, 0, 0, 0).unwrap(), (1u64 << 33) - 1);
+
+        // The caller's `amount` is the floor of the first deposit, not what it mints
+        assert_eq!(settled_lp_amount(1_000, 4_000_000, 1_000_000, 0, 0, 0).unwrap(), 2_000_000);
+        assert_eq!(
+            settled_lp_amount(2_000_001, 4_000_000, 1_000_000, 0, 0, 0).unwrap_err(),
+            error!(AmmError::SlippageExceeded)
+        );
+
+        // Later deposits against near-full reserves and supply still settle without overflowing
+        let half = u64::MAX / 2;
+        let (reserve, supply) = (u64::MAX - 1, u64::MAX - 1);
+        assert_eq!(settled_lp_amount(half, half, half, reserve, reserve, supply).unwrap(), half);
+        assert_eq!(settled_lp_amount(half, half, half - 1, reserve, reserve, supply).unwrap(), half - 1);
+    }
+
+    #[test]
+    fn test_single_sided_swap_priced_like_swap() {
+        let config = Config {
+            reserve_x: 1_000_000_000,
+            reserve_y: 1_000_000_000,
+            fee: 30,
+            protocol_fee_bps: 5,
+            fee_enabled: true,
+            ..Config::default()
+        };
+        let supply = 1_000_000_000;
+
+        // The implicit swap carves the same protocol share a swap of that size pays
+        let plan = plan_single_sided(&config, true, 1_000_000, supply, 30).unwrap();
+        let swap = price_swap(&config, true, plan.swap_amount, supply, 30, 0).unwrap();
+        assert!(plan.protocol_fee > 0);
+        assert_eq!(plan.protocol_fee, swap.protocol_fee);
+        assert_eq!(plan.post_reserve_in, 1_000_000_000 + plan.swap_amount - plan.protocol_fee);
+
+        // A higher dynamic fee costs the zap LP, as it costs a swap output
+        let dynamic = plan_single_sided(&config, true, 1_000_000, supply, 100).unwrap();
+        assert!(dynamic.lp_amount < plan.lp_amount);
+        assert!(dynamic.protocol_fee >= plan.protocol_fee);
+
+        // With the fee switch off the whole fee stays with the LPs
+        let off = Config { fee_enabled: false, ..config };
+        let plan = plan_single_sided(&off, false, 1_000_000, supply, 30).unwrap();
+        assert_eq!(plan.protocol_fee, 0);
+        assert_eq!(plan.post_reserve_in, 1_000_000_000 + plan.swap_amount);
+
+        assert_eq!(
+            plan_single_sided(&Config::default(), true, 1_000_000, supply, 30).map(|_| ()).unwrap_err(),
+            error!(AmmError::NoLiquidityInPool)
+        );
+    }
+}
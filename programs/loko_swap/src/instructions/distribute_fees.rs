@@ -0,0 +1,88 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+use crate::{
+    error::AmmError,
+    events::FeesDistributed,
+    state::Config,
+    utils::token_utils::{transfer_with_extensions, TokenExtensions},
+};
+
+/// Account structure for paying collected transfer fees out of the fee vault
+/// Permissionless, the destination is fixed by `config.fee_destination`
+#[derive(Accounts)]
+pub struct DistributeFees<'info> {
+    pub payer: Signer<'info>,
+
+    #[account(
+        seeds = [b"config", config.seed.to_be_bytes().as_ref()],
+        bump = config.config_bump
+    )]
+    pub config: Account<'info, Config>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"fee_vault", config.key().as_ref(), mint.key().as_ref()],
+        bump,
+        token::mint = mint,
+        token::authority = config,
+        token::token_program = token_program
+    )]
+    pub fee_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// Token account of `config.fee_destination` for `mint`
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = config.fee_destination,
+        token::token_program = token_program
+    )]
+    pub fee_destination: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    // remaining_accounts: transfer hook accounts for `mint`, if any
+}
+
+impl<'info> DistributeFees<'info> {
+    /// Move the whole fee vault balance to the fee destination
+    pub fn distribute_fees(&mut self, remaining_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        self.config.check_version()?;
+
+        let amount = self.fee_vault.amount;
+        require!(amount > 0, AmmError::InvalidAmount);
+
+        let seeds = &[
+            b"config",
+            &self.config.seed.to_be_bytes()[..],
+            &[self.config.config_bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let extensions = TokenExtensions::new(&self.mint.to_account_info())?;
+        transfer_with_extensions(
+            self.token_program.to_account_info(),
+            self.fee_vault.to_account_info(),
+            self.mint.to_account_info(),
+            self.fee_destination.to_account_info(),
+            self.config.to_account_info(),
+            &extensions,
+            remaining_accounts,
+            amount,
+            self.mint.decimals,
+            signer_seeds,
+        )?;
+
+        emit!(FeesDistributed {
+            config: self.config.key(),
+            mint: self.mint.key(),
+            destination: self.fee_destination.key(),
+            amount,
+        });
+
+        msg!("Distributed {} collected fees to {}", amount, self.fee_destination.key());
+
+        Ok(())
+    }
+}
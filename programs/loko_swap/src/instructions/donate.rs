@@ -0,0 +1,165 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+use crate::{
+    error::AmmError,
+    events::LiquidityDonated,
+    state::Config,
+    utils::token_utils::{
+        check_hook_account_budget, check_vaults_not_frozen, transfer_with_extensions, TokenExtensions,
+    },
+};
+
+/// Account structure for adding reserves without minting LP
+/// The donation is shared by the existing LPs, so the pool must already have some
+#[derive(Accounts)]
+pub struct DonateLiquidity<'info> {
+    pub donor: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config", config.seed.to_be_bytes().as_ref()],
+        bump = config.config_bump
+    )]
+    pub config: Box<Account<'info, Config>>,
+
+    #[account(address = config.mint_x @ AmmError::InvalidToken)]
+    pub mint_x: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(address = config.mint_y @ AmmError::InvalidToken)]
+    pub mint_y: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        token::mint = mint_x,
+        token::authority = donor,
+        token::token_program = token_program
+    )]
+    pub donor_x: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        token::mint = mint_y,
+        token::authority = donor,
+        token::token_program = token_program
+    )]
+    pub donor_y: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint_x,
+        associated_token::authority = config,
+        associated_token::token_program = token_program
+    )]
+    pub vault_x: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint_y,
+        associated_token::authority = config,
+        associated_token::token_program = token_program
+    )]
+    pub vault_y: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        seeds = [b"lp", config.key().as_ref()],
+        bump = config.lp_bump
+    )]
+    pub mint_lp: Box<InterfaceAccount<'info, Mint>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    // remaining_accounts: transfer hook accounts for the pool mints, if any
+}
+
+impl<'info> DonateLiquidity<'info> {
+    pub fn donate_liquidity(
+        &mut self,
+        amount_x: u64,
+        amount_y: u64,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        self.config.check_version()?;
+        require!(!self.config.locked, AmmError::PoolLocked);
+        require!(!self.config.borrowing, AmmError::FlashLoanActive);
+        require!(!self.config.emergency, AmmError::EmergencyMode);
+
+        // Persist the guard so a nested call into this pool fails on entry
+        self.config.enter()?;
+        self.config.exit(&crate::ID)?;
+
+        // Oracle sees the reserves as they were before this instruction
+        self.config.update_oracle(Clock::get()?.unix_timestamp);
+
+        require!(amount_x > 0 || amount_y > 0, AmmError::InvalidAmount);
+
+        // Before the first deposit a donation would set the ratio the first LP prices against
+        require!(
+            self.mint_lp.supply > 0 && self.config.reserve_x > 0 && self.config.reserve_y > 0,
+            AmmError::NoLiquidityInPool
+        );
+
+        check_vaults_not_frozen(&self.vault_x, &self.vault_y)?;
+        check_hook_account_budget(
+            &[self.mint_x.to_account_info(), self.mint_y.to_account_info()],
+            remaining_accounts,
+        )?;
+
+        let (before_x, before_y) = (self.vault_x.amount, self.vault_y.amount);
+        if amount_x > 0 {
+            self.donate_tokens(true, amount_x, remaining_accounts)?;
+        }
+        if amount_y > 0 {
+            self.donate_tokens(false, amount_y, remaining_accounts)?;
+        }
+
+        // Only what reached the vaults after transfer fees counts as donated
+        self.vault_x.reload()?;
+        self.vault_y.reload()?;
+        self.config.apply_vault_deltas(before_x, self.vault_x.amount, before_y, self.vault_y.amount)?;
+        self.config.check_reserves_backed(self.vault_x.amount, self.vault_y.amount)?;
+
+        self.config.leave();
+
+        let received_x = self.vault_x.amount - before_x;
+        let received_y = self.vault_y.amount - before_y;
+
+        emit!(LiquidityDonated {
+            config: self.config.key(),
+            donor: self.donor.key(),
+            amount_x: received_x,
+            amount_y: received_y,
+        });
+
+        msg!("Donated liquidity: x={}, y={}", received_x, received_y);
+
+        Ok(())
+    }
+
+    fn donate_tokens(
+        &self,
+        is_x: bool,
+        amount: u64,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        let (from, to, mint) = if is_x {
+            (&self.donor_x, &self.vault_x, &self.mint_x)
+        } else {
+            (&self.donor_y, &self.vault_y, &self.mint_y)
+        };
+
+        let extensions = TokenExtensions::new(&mint.to_account_info())?;
+
+        transfer_with_extensions(
+            self.token_program.to_account_info(),
+            from.to_account_info(),
+            mint.to_account_info(),
+            to.to_account_info(),
+            self.donor.to_account_info(),
+            &extensions,
+            remaining_accounts,
+            amount,
+            mint.decimals,
+            &[],
+        )
+    }
+}
@@ -0,0 +1,168 @@
+use anchor_lang::{
+    prelude::*,
+    solana_program::{
+        instruction::{AccountMeta, Instruction},
+        program::invoke,
+    },
+};
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+use crate::{
+    error::AmmError,
+    state::Config,
+    utils::{transfer_with_extensions, TokenExtensions},
+};
+
+/// Account structure for borrowing from a vault within a single instruction
+#[derive(Accounts)]
+pub struct FlashLoan<'info> {
+    pub borrower: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config", config.seed.to_be_bytes().as_ref()],
+        bump = config.config_bump
+    )]
+    pub config: Account<'info, Config>,
+
+    /// Mint X or mint Y of the pool
+    #[account(
+        constraint = mint.key() == config.mint_x || mint.key() == config.mint_y @ AmmError::InvalidToken
+    )]
+    pub mint: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = config,
+        associated_token::token_program = token_program
+    )]
+    pub vault: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    /// Receives the borrowed tokens
+    #[account(mut, token::mint = mint)]
+    pub borrower_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    /// CHECK: Arbitrary program invoked with the borrowed funds, it must repay the vault
+    #[account(
+        executable,
+        constraint = callback_program.key() != crate::ID @ AmmError::InvalidAccountData
+    )]
+    pub callback_program: UncheckedAccount<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+impl<'info> FlashLoan<'info> {
+    pub fn flash_loan(
+        &mut self,
+        amount: u64,
+        data: Vec<u8>,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        self.config.check_version()?;
+        require!(self.config.locked == false, AmmError::PoolLocked);
+        require!(!self.config.borrowing, AmmError::FlashLoanActive);
+        require!(!self.config.emergency, AmmError::EmergencyMode);
+        require!(amount > 0, AmmError::InvalidAmount);
+        require!(amount <= self.vault.amount, AmmError::InsufficientFunds);
+
+        // Premium rounded up so small loans are never free
+        let fee = (amount as u128 * self.config.flash_fee_bps as u128).div_ceil(10_000) as u64;
+        let balance_before = self.vault.amount;
+        let required_balance = balance_before
+            .checked_add(fee)
+            .ok_or(AmmError::Overflow)?;
+
+        // Persist both flags so nested calls into this pool fail during the callback
+        self.config.enter()?;
+        self.config.borrowing = true;
+        self.config.exit(&crate::ID)?;
+
+        self.lend(amount, remaining_accounts)?;
+        self.invoke_callback(data, remaining_accounts)?;
+
+        self.vault.reload()?;
+        require!(
+            self.vault.amount >= required_balance,
+            AmmError::FlashLoanNotRepaid
+        );
+
+        // The premium accrues to LPs through the tracked reserves
+        self.config.update_oracle(Clock::get()?.unix_timestamp);
+        if self.mint.key() == self.config.mint_x {
+            self.config.apply_vault_deltas(balance_before, self.vault.amount, 0, 0)?;
+        } else {
+            self.config.apply_vault_deltas(0, 0, balance_before, self.vault.amount)?;
+        }
+        self.config.borrowing = false;
+        self.config.leave();
+
+        msg!("Flash loan repaid: amount={}, fee={}", amount, fee);
+
+        Ok(())
+    }
+
+    fn lend(&self, amount: u64, remaining_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        let seeds = &[
+            b"config",
+            &self.config.seed.to_be_bytes()[..],
+            &[self.config.config_bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let extensions = TokenExtensions::new(&self.mint.to_account_info())?;
+
+        transfer_with_extensions(
+            self.token_program.to_account_info(),
+            self.vault.to_account_info(),
+            self.mint.to_account_info(),
+            self.borrower_token_account.to_account_info(),
+            self.config.to_account_info(),
+            &extensions,
+            remaining_accounts,
+            amount,
+            self.mint.decimals,
+            signer_seeds,
+        )
+    }
+
+    /// Invoke the callback with the loan accounts followed by all remaining accounts
+    /// The config never signs this CPI
+    fn invoke_callback(&self, data: Vec<u8>, remaining_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        let mut accounts = vec![
+            AccountMeta::new_readonly(self.borrower.key(), true),
+            AccountMeta::new(self.borrower_token_account.key(), false),
+            AccountMeta::new(self.vault.key(), false),
+            AccountMeta::new_readonly(self.mint.key(), false),
+            AccountMeta::new_readonly(self.token_program.key(), false),
+        ];
+        let mut account_infos = vec![
+            self.borrower.to_account_info(),
+            self.borrower_token_account.to_account_info(),
+            self.vault.to_account_info(),
+            self.mint.to_account_info(),
+            self.token_program.to_account_info(),
+        ];
+
+        for account in remaining_accounts {
+            accounts.push(if account.is_writable {
+                AccountMeta::new(account.key(), account.is_signer)
+            } else {
+                AccountMeta::new_readonly(account.key(), account.is_signer)
+            });
+            account_infos.push(account.clone());
+        }
+        account_infos.push(self.callback_program.to_account_info());
+
+        let instruction = Instruction {
+            program_id: self.callback_program.key(),
+            accounts,
+            data,
+        };
+
+        invoke(&instruction, &account_infos)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,103 @@
+use anchor_lang::{prelude::*, solana_program::instruction::AccountMeta};
+use anchor_spl::token_interface::Mint;
+use spl_transfer_hook_interface::instruction::TransferHookInstruction;
+
+use crate::{
+    error::AmmError,
+    events::{HookAccountsResolved, ResolvedHookAccount},
+    utils::{
+        account_resolver::{
+            parse_extra_account_metas, resolve_extra_account_metas, validate_extra_account_metas_address,
+        },
+        token_utils::TokenExtensions,
+    },
+};
+
+/// Accounts of a transfer of `mint` whose hook accounts should be resolved
+/// Nothing is written or moved, the accounts only feed the resolution
+#[derive(Accounts)]
+pub struct SimulateHookResolution<'info> {
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// CHECK: Source token account of the simulated transfer, only read for seeds
+    pub source: UncheckedAccount<'info>,
+
+    /// CHECK: Destination token account of the simulated transfer, only read for seeds
+    pub destination: UncheckedAccount<'info>,
+
+    /// CHECK: Authority of the simulated transfer, only read for seeds
+    pub authority: UncheckedAccount<'info>,
+
+    /// CHECK: Checked against the hook's extra-account-metas PDA for `mint` before parsing
+    pub extra_account_metas: UncheckedAccount<'info>,
+    // remaining_accounts: accounts whose data derives seeds, and the candidates to check for
+}
+
+impl<'info> SimulateHookResolution<'info> {
+    pub fn simulate_hook_resolution(
+        &self,
+        amount: u64,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        let mint = self.mint.to_account_info();
+        let hook_program_id = TokenExtensions::new(&mint)?
+            .transfer_hook_program_id
+            .ok_or(AmmError::TransferHookNotFound)?;
+
+        let extra_account_metas = self.extra_account_metas.to_account_info();
+        validate_extra_account_metas_address(&hook_program_id, mint.key, extra_account_metas.key)?;
+        let extra_metas = parse_extra_account_metas(&extra_account_metas.try_borrow_data()?)?;
+
+        let base_infos = [
+            self.source.to_account_info(),
+            mint,
+            self.destination.to_account_info(),
+            self.authority.to_account_info(),
+            extra_account_metas,
+        ];
+        let base_accounts: Vec<AccountMeta> = base_infos
+            .iter()
+            .map(|info| AccountMeta::new_readonly(info.key(), false))
+            .collect();
+
+        let find_info = |key: &Pubkey| {
+            base_infos
+                .iter()
+                .chain(remaining_accounts.iter())
+                .find(|info| info.key == key)
+        };
+
+        let instruction_data = TransferHookInstruction::Execute { amount }.pack();
+        let resolved = resolve_extra_account_metas(
+            &extra_metas,
+            &hook_program_id,
+            &instruction_data,
+            &base_accounts,
+            |key| find_info(key).and_then(|info| info.try_borrow_data().ok().map(|d| d.to_vec())),
+        )?;
+
+        let accounts: Vec<ResolvedHookAccount> = resolved
+            .iter()
+            .map(|meta| ResolvedHookAccount {
+                pubkey: meta.pubkey,
+                is_signer: meta.is_signer,
+                is_writable: meta.is_writable,
+                provided: remaining_accounts.iter().any(|info| info.key == &meta.pubkey),
+            })
+            .collect();
+
+        msg!(
+            "Resolved {} extra accounts for hook {}",
+            accounts.len(),
+            hook_program_id
+        );
+
+        emit!(HookAccountsResolved {
+            mint: self.mint.key(),
+            hook_program: hook_program_id,
+            accounts,
+        });
+
+        Ok(())
+    }
+}
@@ -0,0 +1,136 @@
+use anchor_lang::{
+    prelude::*,
+    system_program::{transfer, Transfer},
+};
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token::{approve, spl_token::native_mint, sync_native, Approve, SyncNative, Token},
+    token_interface::{Mint, TokenAccount},
+};
+
+use crate::{error::AmmError, events::HookFeeFunded, state::Config};
+
+/// Account structure for funding the config's WSOL account that pays the hook's dynamic fee
+/// Transfers out of the vaults are sent by the config PDA, so the hook charges its WSOL account
+#[derive(Accounts)]
+pub struct FundHookFee<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [b"config", config.seed.to_be_bytes().as_ref()],
+        bump = config.config_bump,
+        constraint = config.authority.is_some() @ AmmError::NoAuthoritySet,
+        constraint = config.authority == Some(authority.key()) @ AmmError::InvalidAuthority
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(address = native_mint::ID @ AmmError::InvalidToken)]
+    pub wsol_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    /// The owner's WSOL ATA the hook resolves for transfers the config sends
+    #[account(
+        init_if_needed,
+        payer = authority,
+        associated_token::mint = wsol_mint,
+        associated_token::authority = config,
+        associated_token::token_program = token_program
+    )]
+    pub config_wsol: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    /// CHECK: the fee delegate PDA of the pool's hook program, checked in the instruction
+    pub hook_delegate: UncheckedAccount<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> FundHookFee<'info> {
+    pub fn fund_hook_fee(&mut self, amount: u64) -> Result<()> {
+        self.config.check_version()?;
+
+        let hook_program = self
+            .config
+            .default_hook_program
+            .ok_or(AmmError::TransferHookNotFound)?;
+        require_keys_eq!(
+            self.hook_delegate.key(),
+            hook_fee_delegate(&hook_program),
+            AmmError::UnauthorizedHookProgram
+        );
+
+        if amount > 0 {
+            transfer(
+                CpiContext::new(
+                    self.system_program.to_account_info(),
+                    Transfer {
+                        from: self.authority.to_account_info(),
+                        to: self.config_wsol.to_account_info(),
+                    },
+                ),
+                amount,
+            )?;
+
+            // The token balance only picks up the lamports once synced
+            sync_native(CpiContext::new(
+                self.token_program.to_account_info(),
+                SyncNative {
+                    account: self.config_wsol.to_account_info(),
+                },
+            ))?;
+            self.config_wsol.reload()?;
+        }
+
+        // The hook only collects what the delegate was approved for, so approve the whole balance
+        let balance = self.config_wsol.amount;
+        let seeds = &[
+            b"config",
+            &self.config.seed.to_be_bytes()[..],
+            &[self.config.config_bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+        approve(
+            CpiContext::new_with_signer(
+                self.token_program.to_account_info(),
+                Approve {
+                    to: self.config_wsol.to_account_info(),
+                    delegate: self.hook_delegate.to_account_info(),
+                    authority: self.config.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            balance,
+        )?;
+
+        emit!(HookFeeFunded {
+            config: self.config.key(),
+            delegate: self.hook_delegate.key(),
+            amount,
+            balance,
+        });
+
+        msg!("Hook fee account funded with {}, {} approved for {}", amount, balance, self.hook_delegate.key());
+
+        Ok(())
+    }
+}
+
+/// PDA a dynamic fee hook collects its WSOL fee with, `["delegate"]` under the hook program
+fn hook_fee_delegate(hook_program: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"delegate"], hook_program).0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hook_fee_delegate() {
+        let hook_program = Pubkey::new_unique();
+        let (expected, _) = Pubkey::find_program_address(&[b"delegate"], &hook_program);
+
+        assert_eq!(hook_fee_delegate(&hook_program), expected);
+        assert_ne!(hook_fee_delegate(&Pubkey::new_unique()), expected);
+    }
+}
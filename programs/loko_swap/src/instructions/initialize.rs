@@ -3,31 +3,54 @@ use anchor_spl::{
     associated_token::AssociatedToken,
     token_interface::{Mint, TokenAccount, TokenInterface},
 };
+use anchor_spl::token_interface::spl_token_2022::extension::ExtensionType;
 use std::str::FromStr;
 
-use crate::{error::AmmError, state::Config};
+use crate::{
+    constants::{CONFIG_VERSION, DEFAULT_FLASH_FEE_BPS, DEFAULT_LP_DECIMALS, MAX_FEE_BPS},
+    error::AmmError,
+    events::PoolInitialized,
+    state::{Config, MintAllowlist},
+    utils::{has_interest_bearing_extension, validate_transfer_fee_config},
+};
 
 #[derive(Accounts)]
-#[instruction(seed: u64)]
+#[instruction(
+    seed: u64,
+    fee: u16,
+    authority: Option<Pubkey>,
+    transfer_fee_basis_points: u16,
+    max_transfer_fee: u64,
+    hook_program_id: Option<Pubkey>,
+    protocol_fee_bps: u16,
+    protocol_fee_destination: Pubkey,
+    lp_decimals: Option<u8>,
+    timelock_delay: i64,
+    permissioned: bool
+)]
 pub struct Initialize<'info> {
     #[account(mut)]
     pub admin: Signer<'info>,
 
     /// The first token mint - Token 2022 or Standard Token
+    /// Must sort before mint_y by key bytes so each pair and seed has a single pool
     pub mint_x: InterfaceAccount<'info, Mint>,
 
     /// The second token mint - Token 2022 or Standard Token
     pub mint_y: InterfaceAccount<'info, Mint>,
 
-    /// LP token mint - created as Token 2022 to support future extensions
+    /// LP token mint - created as Token 2022, limited to the metadata pointer and token metadata extensions
+    /// The metadata pointer targets the mint itself so `set_lp_metadata` can add a name later
     #[account(
         init,
         payer = admin,
         seeds = [b"lp", config.key().as_ref()],
         bump,
-        mint::decimals = 6,
+        mint::decimals = lp_decimals.unwrap_or(DEFAULT_LP_DECIMALS),
         mint::authority = config,
-        mint::token_program = token_program
+        mint::token_program = token_program,
+        extensions::metadata_pointer::authority = config,
+        extensions::metadata_pointer::metadata_address = mint_lp
     )]
     pub mint_lp: InterfaceAccount<'info, Mint>,
 
@@ -61,6 +84,9 @@ pub struct Initialize<'info> {
     )]
     pub config: Account<'info, Config>,
 
+    /// Allowlist both mints must be on, required when `permissioned` is set
+    pub mint_allowlist: Option<Account<'info, MintAllowlist>>,
+
     /// Token program for LP Standard token or Token 2022 tokens
     pub token_program: Interface<'info, TokenInterface>,
     
@@ -85,12 +111,24 @@ impl<'info> Initialize<'info> {
         transfer_fee_basis_points: u16,
         max_transfer_fee: u64,
         hook_program_id: Option<Pubkey>,
+        protocol_fee_bps: u16,
+        protocol_fee_destination: Pubkey,
+        lp_decimals: Option<u8>,
+        timelock_delay: i64,
+        permissioned: bool,
+        pass_output_fee: bool,
         bumps: &InitializeBumps,
         _remaining_accounts: &[AccountInfo<'info>],
     ) -> Result<()> {
         // Validate fee is reasonable (max 10% = 1000 basis points)
-        require!(fee <= 1000, AmmError::InvalidFee);
-        require!(transfer_fee_basis_points <= 10000, AmmError::InvalidFee);
+        require!(fee <= MAX_FEE_BPS, AmmError::InvalidFee);
+        validate_transfer_fee_config(transfer_fee_basis_points, max_transfer_fee)?;
+        require!(protocol_fee_bps <= fee, AmmError::InvalidFee);
+        require!(
+            lp_decimals.unwrap_or(DEFAULT_LP_DECIMALS) <= 9,
+            AmmError::InvalidLpDecimals
+        );
+        require!(timelock_delay >= 0, AmmError::InvalidTimelockDelay);
         
         // Validate hook program if provided
         if let Some(hook_program) = hook_program_id {
@@ -100,20 +138,29 @@ impl<'info> Initialize<'info> {
         // Validate token programs match the mints
         self.validate_token_programs()?;
         
-        // Ensure mints are different
-        require!(
-            self.mint_x.key() != self.mint_y.key(),
-            AmmError::IdenticalMints
-        );
+        // Ensure mints are different and in canonical order
+        check_mint_order(&self.mint_x.key(), &self.mint_y.key())?;
+
+        // Permissioned pools only pair allowlisted mints
+        let mint_allowlist = if permissioned {
+            let allowlist = self.mint_allowlist.as_ref().ok_or(AmmError::AllowlistMissing)?;
+            allowlist.check_pair(&self.mint_x.key(), &self.mint_y.key())?;
+            allowlist.key()
+        } else {
+            Pubkey::default()
+        };
 
         // Check for supported Token 2022 extensions
         self.validate_token_extensions()?;
+        check_lp_mint_extensions(&self.mint_lp.to_account_info().try_borrow_data()?)?;
 
         // Detect extension support
         let x_has_transfer_fee = self.has_transfer_fee(&self.mint_x)?;
         let y_has_transfer_fee = self.has_transfer_fee(&self.mint_y)?;
         let x_has_transfer_hook = self.has_transfer_hook(&self.mint_x)?.is_some();
         let y_has_transfer_hook = self.has_transfer_hook(&self.mint_y)?.is_some();
+        let has_interest_bearing = has_interest_bearing_extension(&self.mint_x.to_account_info())?
+            || has_interest_bearing_extension(&self.mint_y.to_account_info())?;
 
         // Initialize approved hook programs list
         let approved_hook_programs = vec![
@@ -147,7 +194,42 @@ impl<'info> Initialize<'info> {
             supports_transfer_fees: x_has_transfer_fee || y_has_transfer_fee,
             supports_transfer_hooks: x_has_transfer_hook || y_has_transfer_hook,
             supports_metadata: false, // Could be extended to check for metadata
-            supports_interest_bearing: false, // Could be extended to check for interest bearing
+            supports_interest_bearing: has_interest_bearing, // Priced in raw base units, not UI amounts
+
+            // Protocol fee split
+            protocol_fee_bps,
+            protocol_fee_destination,
+            reserve_x: 0,
+            reserve_y: 0,
+            borrowing: false,
+            flash_fee_bps: DEFAULT_FLASH_FEE_BPS,
+            locked_for_reentrancy: false,
+            price_x_cumulative: 0,
+            price_y_cumulative: 0,
+            last_oracle_timestamp: Clock::get()?.unix_timestamp,
+            strict_hooks: false,
+            timelock_delay,
+            pending_change: None,
+            version: CONFIG_VERSION,
+            permissioned,
+            mint_allowlist,
+            min_reserve: 0,
+            direct_fee_collection: false,
+            collected_fees_x: 0,
+            collected_fees_y: 0,
+            fee_enabled: true,
+            cumulative_volume_x: 0,
+            cumulative_volume_y: 0,
+            swap_count: 0,
+            emergency: false,
+            min_dynamic_fee_bps: fee,
+            max_dynamic_fee_bps: MAX_FEE_BPS,
+            protocol_lp_destination: Pubkey::default(),
+            protocol_lp_share_bps: 0,
+            last_k_sqrt: 0,
+            last_k_lp_supply: 0,
+            pass_output_fee,
+            hook_unsafe: false,
         });
 
         msg!("AMM initialized with:");
@@ -155,6 +237,10 @@ impl<'info> Initialize<'info> {
         msg!("  Mint Y: {}", self.mint_y.key());
         msg!("  LP Mint: {}", self.mint_lp.key());
         msg!("  Fee: {} basis points", fee);
+        msg!("  Protocol Fee: {} basis points", protocol_fee_bps);
+        msg!("  Timelock Delay: {} seconds", timelock_delay);
+        msg!("  Permissioned: {}", permissioned);
+        msg!("  Pass Output Fee: {}", pass_output_fee);
         msg!("  Default Transfer Fee: {} basis points", transfer_fee_basis_points);
         msg!("  Max Transfer Fee: {}", max_transfer_fee);
         msg!("  X has transfer fee: {}", x_has_transfer_fee);
@@ -162,6 +248,17 @@ impl<'info> Initialize<'info> {
         msg!("  X has transfer hook: {}", x_has_transfer_hook);
         msg!("  Y has transfer hook: {}", y_has_transfer_hook);
 
+        emit!(PoolInitialized {
+            config: self.config.key(),
+            mint_x: self.mint_x.key(),
+            mint_y: self.mint_y.key(),
+            mint_lp: self.mint_lp.key(),
+            fee,
+            authority,
+            supports_transfer_fees: self.config.supports_transfer_fees,
+            supports_transfer_hooks: self.config.supports_transfer_hooks,
+        });
+
         Ok(())
     }
 
@@ -216,53 +313,7 @@ impl<'info> Initialize<'info> {
         }
 
         let mint_data = mint_info.try_borrow_data()?;
-        
-        use anchor_spl::token_interface::spl_token_2022::extension::{StateWithExtensions, ExtensionType, BaseStateWithExtensions, default_account_state::DefaultAccountState};
-        use anchor_spl::token_interface::spl_token_2022::state::AccountState;
-        
-        if let Ok(mint_with_extension) = StateWithExtensions::<anchor_spl::token_interface::spl_token_2022::state::Mint>::unpack(&mint_data) {
-            let extension_types = mint_with_extension.get_extension_types()?;
-            
-            for extension_type in extension_types {
-                match extension_type {
-                    // Supported extensions
-                    ExtensionType::TransferFeeConfig => {
-                        msg!("{} has transfer fee extension - supported", mint_name);
-                    }
-                    ExtensionType::TransferHook => {
-                        msg!("{} has transfer hook extension - supported", mint_name);
-                    }
-                    ExtensionType::MintCloseAuthority => {
-                        msg!("{} has mint close authority - supported", mint_name);
-                    }
-                    ExtensionType::PermanentDelegate => {
-                        msg!("{} has permanent delegate - supported", mint_name);
-                    }
-                    
-                    // Potentially problematic extensions
-                    ExtensionType::NonTransferable => {
-                        msg!("WARNING: {} has non-transferable extension", mint_name);
-                        return Err(AmmError::UnsupportedExtension.into());
-                    }
-                    ExtensionType::DefaultAccountState => {
-                        // Check if accounts are frozen by default
-                        if let Ok(default_state) = mint_with_extension.get_extension::<DefaultAccountState>() {
-                            if default_state.state == u8::from(AccountState::Frozen) {
-                                msg!("WARNING: {} has default frozen state", mint_name);
-                                return Err(AmmError::UnsupportedExtension.into());
-                            }
-                        }
-                    }
-                    
-                    // Other extensions - warn but allow
-                    _ => {
-                        msg!("INFO: {} has extension {:?} - proceeding with caution", mint_name, extension_type);
-                    }
-                }
-            }
-        }
-
-        Ok(())
+        check_mint_extensions(&mint_data, mint_name)
     }
 
     fn has_transfer_fee(&self, mint: &InterfaceAccount<Mint>) -> Result<bool> {
@@ -296,7 +347,8 @@ impl<'info> Initialize<'info> {
         
         if let Ok(mint_with_extension) = StateWithExtensions::<anchor_spl::token_interface::spl_token_2022::state::Mint>::unpack(&mint_data) {
             if let Ok(transfer_hook) = mint_with_extension.get_extension::<TransferHook>() {
-                return Ok(Some(Pubkey::try_from(transfer_hook.program_id.0.as_ref()).unwrap_or_default()));
+                // A cleared program id means Token-2022 runs no hook
+                return Ok(transfer_hook.program_id.into());
             }
         }
         
@@ -321,4 +373,190 @@ impl<'info> Initialize<'info> {
         msg!("Hook program {} is approved", hook_program_id);
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+/// Require distinct mints with `mint_x < mint_y` so (X, Y) and (Y, X) can't both exist
+fn check_mint_order(mint_x: &Pubkey, mint_y: &Pubkey) -> Result<()> {
+    require!(mint_x != mint_y, AmmError::IdenticalMints);
+    require!(mint_x < mint_y, AmmError::MintOrdering);
+    Ok(())
+}
+
+/// Extensions the LP mint may carry
+///
+/// The program only mints and burns LP tokens, which run no transfer hook and charge no
+/// transfer fee. A hook or fee on the LP mint would make user-to-user LP transfers depend on
+/// accounts and amounts this program never resolves, so nothing beyond metadata is allowed.
+const SUPPORTED_LP_EXTENSIONS: [ExtensionType; 2] =
+    [ExtensionType::MetadataPointer, ExtensionType::TokenMetadata];
+
+/// Reject LP mints with extensions outside `SUPPORTED_LP_EXTENSIONS`
+fn check_lp_mint_extensions(mint_data: &[u8]) -> Result<()> {
+    use anchor_spl::token_interface::spl_token_2022::extension::{BaseStateWithExtensions, StateWithExtensions};
+
+    // Legacy SPL Token LP mints have no extensions
+    let Ok(mint) = StateWithExtensions::<anchor_spl::token_interface::spl_token_2022::state::Mint>::unpack(mint_data) else {
+        return Ok(());
+    };
+
+    for extension_type in mint.get_extension_types()? {
+        if !SUPPORTED_LP_EXTENSIONS.contains(&extension_type) {
+            msg!("LP mint has unsupported extension {:?}", extension_type);
+            return Err(AmmError::UnsupportedExtension.into());
+        }
+    }
+
+    Ok(())
+}
+
+/// Reject Token-2022 mint extensions the pool can't handle
+fn check_mint_extensions(mint_data: &[u8], mint_name: &str) -> Result<()> {
+    use anchor_spl::token_interface::spl_token_2022::extension::{StateWithExtensions, BaseStateWithExtensions, default_account_state::DefaultAccountState};
+    use anchor_spl::token_interface::spl_token_2022::state::AccountState;
+    
+    if let Ok(mint_with_extension) = StateWithExtensions::<anchor_spl::token_interface::spl_token_2022::state::Mint>::unpack(mint_data) {
+        let extension_types = mint_with_extension.get_extension_types()?;
+        
+        for extension_type in extension_types {
+            match extension_type {
+                // Supported extensions
+                ExtensionType::TransferFeeConfig => {
+                    msg!("{} has transfer fee extension - supported", mint_name);
+                }
+                ExtensionType::TransferHook => {
+                    msg!("{} has transfer hook extension - supported", mint_name);
+                }
+                ExtensionType::MintCloseAuthority => {
+                    msg!("{} has mint close authority - supported", mint_name);
+                }
+                ExtensionType::PermanentDelegate => {
+                    msg!("{} has permanent delegate - supported", mint_name);
+                }
+                ExtensionType::InterestBearingConfig => {
+                    // Raw balances don't change as interest accrues, so the curve stays consistent
+                    msg!("{} is interest bearing - supported, priced in raw base units", mint_name);
+                }
+                
+                // Potentially problematic extensions
+                ExtensionType::NonTransferable => {
+                    msg!("WARNING: {} has non-transferable extension", mint_name);
+                    return Err(AmmError::UnsupportedExtension.into());
+                }
+                ExtensionType::ConfidentialTransferMint
+                | ExtensionType::ConfidentialTransferFeeConfig
+                | ExtensionType::ConfidentialMintBurn => {
+                    // Encrypted balances can't be read back to verify vault deltas
+                    msg!("WARNING: {} has confidential transfer extension", mint_name);
+                    return Err(AmmError::UnsupportedExtension.into());
+                }
+                ExtensionType::DefaultAccountState => {
+                    // Check if accounts are frozen by default
+                    if let Ok(default_state) = mint_with_extension.get_extension::<DefaultAccountState>() {
+                        if default_state.state == u8::from(AccountState::Frozen) {
+                            msg!("WARNING: {} has default frozen state", mint_name);
+                            return Err(AmmError::UnsupportedExtension.into());
+                        }
+                    }
+                }
+                
+                // Other extensions - warn but allow
+                _ => {
+                    msg!("INFO: {} has extension {:?} - proceeding with caution", mint_name, extension_type);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_spl::token_interface::spl_token_2022::{
+        extension::{
+            confidential_transfer::ConfidentialTransferMint, metadata_pointer::MetadataPointer,
+            transfer_hook::TransferHook, BaseStateWithExtensionsMut, StateWithExtensionsMut,
+        },
+        state::Mint as MintState,
+    };
+
+    fn mint_data_with(extensions: &[ExtensionType]) -> Vec<u8> {
+        let len = ExtensionType::try_calculate_account_len::<MintState>(extensions).unwrap();
+        let mut data = vec![0u8; len];
+        let mut state = StateWithExtensionsMut::<MintState>::unpack_uninitialized(&mut data).unwrap();
+        for extension in extensions {
+            match extension {
+                ExtensionType::MetadataPointer => {
+                    state.init_extension::<MetadataPointer>(true).unwrap();
+                }
+                ExtensionType::TransferHook => {
+                    state.init_extension::<TransferHook>(true).unwrap();
+                }
+                _ => unreachable!(),
+            }
+        }
+        state.base = MintState {
+            decimals: 6,
+            is_initialized: true,
+            ..Default::default()
+        };
+        state.pack_base();
+        state.init_account_type().unwrap();
+        data
+    }
+
+    fn confidential_mint_data() -> Vec<u8> {
+        let len = ExtensionType::try_calculate_account_len::<MintState>(&[
+            ExtensionType::ConfidentialTransferMint,
+        ])
+        .unwrap();
+        let mut data = vec![0u8; len];
+        let mut state = StateWithExtensionsMut::<MintState>::unpack_uninitialized(&mut data).unwrap();
+        state.init_extension::<ConfidentialTransferMint>(true).unwrap();
+        state.base = MintState {
+            decimals: 6,
+            is_initialized: true,
+            ..Default::default()
+        };
+        state.pack_base();
+        state.init_account_type().unwrap();
+        data
+    }
+
+    #[test]
+    fn test_check_mint_order() {
+        let a = Pubkey::new_from_array([1; 32]);
+        let b = Pubkey::new_from_array([2; 32]);
+
+        assert!(check_mint_order(&a, &b).is_ok());
+        assert_eq!(check_mint_order(&b, &a).unwrap_err(), error!(AmmError::MintOrdering));
+        assert_eq!(check_mint_order(&a, &a).unwrap_err(), error!(AmmError::IdenticalMints));
+    }
+
+    #[test]
+    fn test_rejects_confidential_transfer_mint() {
+        let data = confidential_mint_data();
+
+        assert_eq!(
+            check_mint_extensions(&data, "mint_x").unwrap_err(),
+            error!(AmmError::UnsupportedExtension)
+        );
+    }
+
+    #[test]
+    fn test_lp_mint_extensions() {
+        // The LP mint initialize creates
+        assert!(check_lp_mint_extensions(&mint_data_with(&[ExtensionType::MetadataPointer])).is_ok());
+
+        // LP transfers would need hook accounts the program never resolves
+        assert_eq!(
+            check_lp_mint_extensions(&mint_data_with(&[
+                ExtensionType::MetadataPointer,
+                ExtensionType::TransferHook,
+            ]))
+            .unwrap_err(),
+            error!(AmmError::UnsupportedExtension)
+        );
+    }
+}
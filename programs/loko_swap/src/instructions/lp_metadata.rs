@@ -0,0 +1,125 @@
+use anchor_lang::{
+    prelude::*,
+    system_program::{transfer, Transfer},
+};
+use anchor_spl::{
+    token_2022::Token2022,
+    token_2022_extensions::{
+        spl_pod::optional_keys::OptionalNonZeroPubkey,
+        spl_token_metadata_interface::state::TokenMetadata, token_metadata_initialize,
+        TokenMetadataInitialize,
+    },
+    token_interface::Mint,
+};
+
+use crate::{constants::LP_SYMBOL, error::AmmError, state::Config};
+
+/// Account structure for naming the LP token through the Token-2022 metadata extension
+/// Only pools whose LP mint was created with the metadata pointer can use it
+#[derive(Accounts)]
+pub struct SetLpMetadata<'info> {
+    /// Pool authority, pays for the extra mint space
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [b"config", config.seed.to_be_bytes().as_ref()],
+        bump = config.config_bump,
+        constraint = config.authority.is_some() @ AmmError::NoAuthoritySet,
+        constraint = config.authority == Some(authority.key()) @ AmmError::InvalidAuthority
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        mut,
+        seeds = [b"lp", config.key().as_ref()],
+        bump = config.lp_bump,
+        mint::token_program = token_program,
+        extensions::metadata_pointer::authority = config,
+        extensions::metadata_pointer::metadata_address = mint_lp
+    )]
+    pub mint_lp: InterfaceAccount<'info, Mint>,
+
+    pub token_program: Program<'info, Token2022>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> SetLpMetadata<'info> {
+    pub fn set_lp_metadata(&mut self, uri: String) -> Result<()> {
+        let name = lp_name(&self.config.mint_x, &self.config.mint_y);
+        let symbol = LP_SYMBOL.to_string();
+
+        self.fund_metadata_space(&name, &symbol, &uri)?;
+
+        let seeds = &[
+            b"config",
+            &self.config.seed.to_be_bytes()[..],
+            &[self.config.config_bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        // Config is both mint authority and metadata update authority
+        token_metadata_initialize(
+            CpiContext::new_with_signer(
+                self.token_program.to_account_info(),
+                TokenMetadataInitialize {
+                    program_id: self.token_program.to_account_info(),
+                    metadata: self.mint_lp.to_account_info(),
+                    update_authority: self.config.to_account_info(),
+                    mint_authority: self.config.to_account_info(),
+                    mint: self.mint_lp.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            name.clone(),
+            symbol,
+            uri,
+        )?;
+
+        msg!("LP metadata set: {}", name);
+
+        Ok(())
+    }
+
+    /// Token-2022 grows the mint for the metadata but doesn't fund the rent
+    fn fund_metadata_space(&self, name: &str, symbol: &str, uri: &str) -> Result<()> {
+        let metadata = TokenMetadata {
+            update_authority: OptionalNonZeroPubkey(self.config.key()),
+            mint: self.mint_lp.key(),
+            name: name.to_string(),
+            symbol: symbol.to_string(),
+            uri: uri.to_string(),
+            additional_metadata: vec![],
+        };
+
+        let mint_info = self.mint_lp.to_account_info();
+        let new_len = mint_info
+            .data_len()
+            .checked_add(metadata.tlv_size_of()?)
+            .ok_or(AmmError::Overflow)?;
+        let required = Rent::get()?.minimum_balance(new_len);
+        let shortfall = required.saturating_sub(mint_info.lamports());
+
+        if shortfall > 0 {
+            transfer(
+                CpiContext::new(
+                    self.system_program.to_account_info(),
+                    Transfer {
+                        from: self.authority.to_account_info(),
+                        to: mint_info,
+                    },
+                ),
+                shortfall,
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// LP token name built from the first characters of both pool mints
+pub fn lp_name(mint_x: &Pubkey, mint_y: &Pubkey) -> String {
+    let x = mint_x.to_string();
+    let y = mint_y.to_string();
+    format!("LokoSwap {}-{} LP", &x[..4], &y[..4])
+}
@@ -0,0 +1,97 @@
+use anchor_lang::{
+    prelude::*,
+    system_program::{transfer, Transfer},
+};
+use anchor_spl::{
+    associated_token::get_associated_token_address_with_program_id,
+    token_interface::TokenAccount,
+};
+
+use crate::{error::AmmError, state::Config};
+
+/// Account structure for upgrading a config account to the current layout
+/// The config is read raw since older layouts don't decode as `Config`
+#[derive(Accounts)]
+#[instruction(seed: u64)]
+pub struct MigrateConfig<'info> {
+    /// Pool authority, pays for the extra space
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    /// CHECK: Decoded by `Config::migrate` after the seeds and owner checks
+    #[account(
+        mut,
+        seeds = [b"config", seed.to_be_bytes().as_ref()],
+        bump,
+        owner = crate::ID
+    )]
+    pub config: UncheckedAccount<'info>,
+
+    /// Vault of mint X, checked against the decoded config
+    pub vault_x: InterfaceAccount<'info, TokenAccount>,
+
+    /// Vault of mint Y, checked against the decoded config
+    pub vault_y: InterfaceAccount<'info, TokenAccount>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> MigrateConfig<'info> {
+    pub fn migrate_config(&mut self) -> Result<()> {
+        let config_info = self.config.to_account_info();
+
+        // Older pools priced off the vault balances, so those seed the tracked reserves
+        let config = Config::migrate(
+            &config_info.try_borrow_data()?,
+            self.vault_x.amount,
+            self.vault_y.amount,
+            Clock::get()?.unix_timestamp,
+        )?;
+
+        config.check_authority(&self.authority.key())?;
+        self.check_vault(&self.vault_x, &config.mint_x)?;
+        self.check_vault(&self.vault_y, &config.mint_y)?;
+
+        let new_len = 8 + Config::INIT_SPACE;
+        if config_info.data_len() < new_len {
+            let shortfall = Rent::get()?
+                .minimum_balance(new_len)
+                .saturating_sub(config_info.lamports());
+
+            if shortfall > 0 {
+                transfer(
+                    CpiContext::new(
+                        self.system_program.to_account_info(),
+                        Transfer {
+                            from: self.authority.to_account_info(),
+                            to: config_info.clone(),
+                        },
+                    ),
+                    shortfall,
+                )?;
+            }
+
+            config_info.resize(new_len)?;
+        }
+
+        let mut data = config_info.try_borrow_mut_data()?;
+        config.try_serialize(&mut &mut data[..])?;
+
+        msg!("Migrated config {} to version {}", config_info.key(), config.version);
+
+        Ok(())
+    }
+
+    /// Only the config's associated token account counts as a vault
+    fn check_vault(&self, vault: &InterfaceAccount<'info, TokenAccount>, mint: &Pubkey) -> Result<()> {
+        let vault_info = vault.to_account_info();
+        let expected = get_associated_token_address_with_program_id(
+            &self.config.key(),
+            mint,
+            vault_info.owner,
+        );
+
+        require_keys_eq!(vault.key(), expected, AmmError::InvalidTokenAccount);
+        Ok(())
+    }
+}
@@ -3,9 +3,37 @@ pub mod deposit;
 pub mod withdraw;
 pub mod swap;
 pub mod update;
+pub mod reserves;
+pub mod flash_loan;
+pub mod lp_metadata;
+pub mod view;
+pub mod migrate;
+pub mod allowlist;
+pub mod distribute_fees;
+pub mod reinvest_fees;
+pub mod protocol_lp;
+pub mod donate;
+pub mod refresh_extensions;
+pub mod hook_fee;
+#[cfg(feature = "devnet-tools")]
+pub mod hook_debug;
 
 pub use initialize::*;
 pub use deposit::*;
 pub use withdraw::*;
 pub use swap::*;
 pub use update::*;
+pub use reserves::*;
+pub use flash_loan::*;
+pub use lp_metadata::*;
+pub use view::*;
+pub use migrate::*;
+pub use allowlist::*;
+pub use distribute_fees::*;
+pub use reinvest_fees::*;
+pub use protocol_lp::*;
+pub use donate::*;
+pub use refresh_extensions::*;
+pub use hook_fee::*;
+#[cfg(feature = "devnet-tools")]
+pub use hook_debug::*;
@@ -0,0 +1,101 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{mint_to, Mint, MintTo, TokenAccount, TokenInterface};
+
+use crate::{
+    error::AmmError,
+    events::ProtocolLpMinted,
+    state::Config,
+    utils::math::{integer_sqrt, protocol_lp_for_growth},
+};
+
+/// Account structure for minting the protocol its LP share of the pool's growth
+/// Permissionless, the destination is fixed by `config.protocol_lp_destination`
+#[derive(Accounts)]
+pub struct MintProtocolLp<'info> {
+    #[account(
+        mut,
+        seeds = [b"config", config.seed.to_be_bytes().as_ref()],
+        bump = config.config_bump
+    )]
+    pub config: Box<Account<'info, Config>>,
+
+    #[account(
+        mut,
+        seeds = [b"lp", config.key().as_ref()],
+        bump = config.lp_bump
+    )]
+    pub mint_lp: Box<InterfaceAccount<'info, Mint>>,
+
+    /// LP token account of `config.protocol_lp_destination`
+    #[account(
+        mut,
+        token::mint = mint_lp,
+        token::authority = config.protocol_lp_destination
+    )]
+    pub protocol_lp: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+impl<'info> MintProtocolLp<'info> {
+    /// Mint LP for the protocol's share of the sqrt(k) growth since the last call
+    /// The first call after enabling only records where growth is measured from
+    pub fn mint_protocol_lp(&mut self) -> Result<u64> {
+        self.config.check_version()?;
+        require!(!self.config.locked, AmmError::PoolLocked);
+        require!(!self.config.borrowing, AmmError::FlashLoanActive);
+        require!(!self.config.emergency, AmmError::EmergencyMode);
+        require!(self.config.protocol_lp_share_bps > 0, AmmError::ProtocolLpDisabled);
+
+        let supply = self.mint_lp.supply;
+        let k_sqrt = integer_sqrt(self.config.reserve_x as u128 * self.config.reserve_y as u128);
+        let lp_amount = protocol_lp_for_growth(
+            supply,
+            k_sqrt,
+            self.config.last_k_sqrt,
+            self.config.last_k_lp_supply,
+            self.config.protocol_lp_share_bps,
+        )?;
+
+        if lp_amount > 0 {
+            self.mint_lp_to_protocol(lp_amount)?;
+        }
+
+        // Growth from here on is measured against the supply including what was just minted
+        self.config.last_k_sqrt = k_sqrt;
+        self.config.last_k_lp_supply = supply.checked_add(lp_amount).ok_or(AmmError::MathOverflow)?;
+
+        emit!(ProtocolLpMinted {
+            config: self.config.key(),
+            destination: self.protocol_lp.key(),
+            k_sqrt,
+            lp_minted: lp_amount,
+        });
+
+        msg!("Minted {} protocol LP at sqrt(k)={}", lp_amount, k_sqrt);
+
+        Ok(lp_amount)
+    }
+
+    fn mint_lp_to_protocol(&self, amount: u64) -> Result<()> {
+        let seeds = &[
+            b"config",
+            &self.config.seed.to_be_bytes()[..],
+            &[self.config.config_bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        mint_to(
+            CpiContext::new_with_signer(
+                self.token_program.to_account_info(),
+                MintTo {
+                    mint: self.mint_lp.to_account_info(),
+                    to: self.protocol_lp.to_account_info(),
+                    authority: self.config.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            amount,
+        )
+    }
+}
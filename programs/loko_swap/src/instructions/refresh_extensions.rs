@@ -0,0 +1,144 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::Mint;
+
+use crate::{
+    error::AmmError,
+    events::{ConfigChangeApplied, ConfigChangeQueued},
+    state::{Config, ConfigChange},
+    utils::{has_interest_bearing_extension, TokenExtensions},
+};
+
+/// Account structure for re-reading the pool mints' extensions into the config
+/// Mint authorities can add a transfer fee or hook after the pool was created
+#[derive(Accounts)]
+pub struct RefreshExtensionFlags<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config", config.seed.to_be_bytes().as_ref()],
+        bump = config.config_bump,
+        constraint = config.authority.is_some() @ AmmError::NoAuthoritySet,
+        constraint = config.authority == Some(authority.key()) @ AmmError::InvalidAuthority
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(address = config.mint_x @ AmmError::InvalidToken)]
+    pub mint_x: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(address = config.mint_y @ AmmError::InvalidToken)]
+    pub mint_y: Box<InterfaceAccount<'info, Mint>>,
+}
+
+impl<'info> RefreshExtensionFlags<'info> {
+    pub fn refresh_extension_flags(&mut self) -> Result<()> {
+        self.config.check_version()?;
+
+        let mint_x = self.mint_x.to_account_info();
+        let mint_y = self.mint_y.to_account_info();
+        let x_ext = TokenExtensions::new(&mint_x)?;
+        let y_ext = TokenExtensions::new(&mint_y)?;
+
+        self.config.supports_transfer_fees = x_ext.has_transfer_fee || y_ext.has_transfer_fee;
+        self.config.supports_transfer_hooks = x_ext.has_transfer_hook || y_ext.has_transfer_hook;
+        self.config.supports_interest_bearing =
+            has_interest_bearing_extension(&mint_x)? || has_interest_bearing_extension(&mint_y)?;
+
+        msg!(
+            "Extension flags: transfer_fees={}, transfer_hooks={}, interest_bearing={}",
+            self.config.supports_transfer_fees,
+            self.config.supports_transfer_hooks,
+            self.config.supports_interest_bearing
+        );
+
+        let Some(program) = refreshed_hook_program(
+            self.config.default_hook_program,
+            x_ext.transfer_hook_program_id,
+            y_ext.transfer_hook_program_id,
+            &self.config.approved_hook_programs,
+        ) else {
+            return Ok(());
+        };
+
+        // The hook program goes through the timelock like `update_hook_program`,
+        // without replacing a change the authority already queued
+        if self.config.pending_change.is_some() {
+            msg!("Hook program {} not queued, another change is pending", program);
+            return Ok(());
+        }
+
+        let change = ConfigChange::HookProgram { program: Some(program) };
+        match self
+            .config
+            .queue_change(change.clone(), Clock::get()?.unix_timestamp)?
+        {
+            Some(pending) => {
+                msg!("Queued config change until {}: {:?}", pending.effective_at, change);
+
+                emit!(ConfigChangeQueued {
+                    config: self.config.key(),
+                    change,
+                    effective_at: pending.effective_at,
+                });
+            }
+            None => {
+                msg!("Applied config change: {:?}", change);
+
+                emit!(ConfigChangeApplied {
+                    config: self.config.key(),
+                    change,
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Hook program the config should move to given the hooks the mints now run, if any
+///
+/// The current program stays while either mint still runs it. Otherwise the mints' hook is
+/// adopted only when both agree on one program and it is in `approved`.
+fn refreshed_hook_program(
+    current: Option<Pubkey>,
+    x_hook: Option<Pubkey>,
+    y_hook: Option<Pubkey>,
+    approved: &[Pubkey],
+) -> Option<Pubkey> {
+    if current.is_some() && (x_hook == current || y_hook == current) {
+        return None;
+    }
+
+    let candidate = x_hook.or(y_hook)?;
+    if y_hook.is_some_and(|hook| hook != candidate) || !approved.contains(&candidate) {
+        msg!("Mint hook {} not adopted as the pool's hook program", candidate);
+        return None;
+    }
+
+    Some(candidate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_refreshed_hook_program() {
+        let (approved, other) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let list = [approved];
+
+        // A hook added after creation is adopted from either mint
+        assert_eq!(refreshed_hook_program(None, Some(approved), None, &list), Some(approved));
+        assert_eq!(refreshed_hook_program(None, None, Some(approved), &list), Some(approved));
+        assert_eq!(refreshed_hook_program(Some(other), Some(approved), Some(approved), &list), Some(approved));
+
+        // Nothing changes while the current hook is still in use, or without hooks
+        assert_eq!(refreshed_hook_program(Some(approved), Some(approved), None, &list), None);
+        assert_eq!(refreshed_hook_program(Some(other), Some(approved), Some(other), &list), None);
+        assert_eq!(refreshed_hook_program(Some(approved), None, None, &list), None);
+
+        // Unapproved or disagreeing hooks are never adopted
+        assert_eq!(refreshed_hook_program(None, Some(other), None, &list), None);
+        assert_eq!(refreshed_hook_program(None, Some(approved), Some(other), &[approved, other]), None);
+    }
+}
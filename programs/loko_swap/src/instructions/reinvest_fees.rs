@@ -0,0 +1,225 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{mint_to, Mint, MintTo, TokenAccount, TokenInterface};
+
+use crate::{
+    error::AmmError,
+    events::FeesReinvested,
+    state::Config,
+    utils::{
+        math::lp_for_exact_amounts,
+        token_utils::{check_vaults_not_frozen, transfer_with_extensions, TokenExtensions},
+    },
+};
+
+/// Account structure for depositing collected transfer fees back into the pool
+/// The LP is minted to a token account of `config.fee_destination`, so it stays protocol-owned
+#[derive(Accounts)]
+pub struct ReinvestFees<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config", config.seed.to_be_bytes().as_ref()],
+        bump = config.config_bump,
+        constraint = config.authority.is_some() @ AmmError::NoAuthoritySet,
+        constraint = config.authority == Some(authority.key()) @ AmmError::InvalidAuthority
+    )]
+    pub config: Box<Account<'info, Config>>,
+
+    #[account(address = config.mint_x @ AmmError::InvalidToken)]
+    pub mint_x: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(address = config.mint_y @ AmmError::InvalidToken)]
+    pub mint_y: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        seeds = [b"fee_vault", config.key().as_ref(), mint_x.key().as_ref()],
+        bump,
+        token::mint = mint_x,
+        token::authority = config,
+        token::token_program = token_program
+    )]
+    pub fee_vault_x: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [b"fee_vault", config.key().as_ref(), mint_y.key().as_ref()],
+        bump,
+        token::mint = mint_y,
+        token::authority = config,
+        token::token_program = token_program
+    )]
+    pub fee_vault_y: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint_x,
+        associated_token::authority = config,
+        associated_token::token_program = token_program
+    )]
+    pub vault_x: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint_y,
+        associated_token::authority = config,
+        associated_token::token_program = token_program
+    )]
+    pub vault_y: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [b"lp", config.key().as_ref()],
+        bump = config.lp_bump
+    )]
+    pub mint_lp: Box<InterfaceAccount<'info, Mint>>,
+
+    /// LP token account of `config.fee_destination`
+    #[account(
+        mut,
+        token::mint = mint_lp,
+        token::authority = config.fee_destination
+    )]
+    pub protocol_lp: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    // remaining_accounts: transfer hook accounts for the pool mints, if any
+}
+
+impl<'info> ReinvestFees<'info> {
+    /// Deposit as much of both fee vaults as the pool ratio allows and mint LP for it
+    /// The side with fewer fees binds, the rest of the other side stays in its fee vault
+    pub fn reinvest_fees(&mut self, remaining_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        self.config.check_version()?;
+        require!(!self.config.locked, AmmError::PoolLocked);
+        require!(!self.config.borrowing, AmmError::FlashLoanActive);
+        require!(!self.config.emergency, AmmError::EmergencyMode);
+
+        // Persist the guard so a nested call into this pool fails on entry
+        self.config.enter()?;
+        self.config.exit(&crate::ID)?;
+
+        // Oracle sees the reserves as they were before this instruction
+        self.config.update_oracle(Clock::get()?.unix_timestamp);
+
+        check_vaults_not_frozen(&self.vault_x, &self.vault_y)?;
+
+        let supply = self.mint_lp.supply;
+        require!(supply > 0, AmmError::NoLiquidityInPool);
+
+        let x_ext = TokenExtensions::new(&self.mint_x.to_account_info())?;
+        let y_ext = TokenExtensions::new(&self.mint_y.to_account_info())?;
+
+        // Net amounts that would reach the pool vaults after transfer fees
+        let available_x = self.fee_vault_x.amount;
+        let available_y = self.fee_vault_y.amount;
+        let net_x = available_x.saturating_sub(x_ext.calculate_fee(available_x));
+        let net_y = available_y.saturating_sub(y_ext.calculate_fee(available_y));
+
+        let (lp_amount, x, y) = lp_for_exact_amounts(
+            net_x,
+            net_y,
+            self.config.reserve_x,
+            self.config.reserve_y,
+            supply,
+        )?;
+        require!(lp_amount > 0, AmmError::InvalidAmount);
+
+        let gross_x = x_ext.calculate_gross_for_net(x).min(available_x);
+        let gross_y = y_ext.calculate_gross_for_net(y).min(available_y);
+
+        let (before_x, before_y) = (self.vault_x.amount, self.vault_y.amount);
+        self.move_fees(true, gross_x, &x_ext, remaining_accounts)?;
+        self.move_fees(false, gross_y, &y_ext, remaining_accounts)?;
+
+        // Never mint more LP than what actually reached the vaults pays for
+        self.vault_x.reload()?;
+        self.vault_y.reload()?;
+        let received_x = self.vault_x.amount.checked_sub(before_x).ok_or(AmmError::MathOverflow)?;
+        let received_y = self.vault_y.amount.checked_sub(before_y).ok_or(AmmError::MathOverflow)?;
+        let (settled_lp, _, _) = lp_for_exact_amounts(
+            received_x,
+            received_y,
+            self.config.reserve_x,
+            self.config.reserve_y,
+            supply,
+        )?;
+        let lp_amount = lp_amount.min(settled_lp);
+        require!(lp_amount > 0, AmmError::InvalidAmount);
+
+        self.config.apply_vault_deltas(before_x, self.vault_x.amount, before_y, self.vault_y.amount)?;
+        self.config.check_reserves_backed(self.vault_x.amount, self.vault_y.amount)?;
+        self.mint_protocol_lp(lp_amount)?;
+
+        self.config.leave();
+
+        emit!(FeesReinvested {
+            config: self.config.key(),
+            amount_x: gross_x,
+            amount_y: gross_y,
+            lp_minted: lp_amount,
+        });
+
+        msg!("Reinvested fees: x={}, y={}, lp={}", gross_x, gross_y, lp_amount);
+
+        Ok(())
+    }
+
+    /// Move `amount` from the fee vault of one side into the pool vault of the same mint
+    fn move_fees(
+        &self,
+        is_x: bool,
+        amount: u64,
+        extensions: &TokenExtensions,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        let (from, to, mint) = if is_x {
+            (&self.fee_vault_x, &self.vault_x, &self.mint_x)
+        } else {
+            (&self.fee_vault_y, &self.vault_y, &self.mint_y)
+        };
+
+        let seeds = &[
+            b"config",
+            &self.config.seed.to_be_bytes()[..],
+            &[self.config.config_bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        transfer_with_extensions(
+            self.token_program.to_account_info(),
+            from.to_account_info(),
+            mint.to_account_info(),
+            to.to_account_info(),
+            self.config.to_account_info(),
+            extensions,
+            remaining_accounts,
+            amount,
+            mint.decimals,
+            signer_seeds,
+        )
+    }
+
+    fn mint_protocol_lp(&self, amount: u64) -> Result<()> {
+        let seeds = &[
+            b"config",
+            &self.config.seed.to_be_bytes()[..],
+            &[self.config.config_bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        mint_to(
+            CpiContext::new_with_signer(
+                self.token_program.to_account_info(),
+                MintTo {
+                    mint: self.mint_lp.to_account_info(),
+                    to: self.protocol_lp.to_account_info(),
+                    authority: self.config.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            amount,
+        )
+    }
+}
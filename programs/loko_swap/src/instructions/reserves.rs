@@ -0,0 +1,158 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+use crate::{
+    error::AmmError,
+    state::Config,
+    utils::{transfer_with_extensions, TokenExtensions},
+};
+
+/// Account structure for reconciling tracked reserves with the vault balances
+#[derive(Accounts)]
+pub struct SyncReserves<'info> {
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config", config.seed.to_be_bytes().as_ref()],
+        bump = config.config_bump
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        associated_token::mint = config.mint_x,
+        associated_token::authority = config,
+        associated_token::token_program = token_program
+    )]
+    pub vault_x: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        associated_token::mint = config.mint_y,
+        associated_token::authority = config,
+        associated_token::token_program = token_program
+    )]
+    pub vault_y: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+impl<'info> SyncReserves<'info> {
+    pub fn sync(&mut self) -> Result<()> {
+        self.config.check_version()?;
+        require!(!self.config.borrowing, AmmError::FlashLoanActive);
+
+        self.config.update_oracle(Clock::get()?.unix_timestamp);
+        self.config.reserve_x = self.vault_x.amount;
+        self.config.reserve_y = self.vault_y.amount;
+
+        msg!(
+            "Reserves synced: x={}, y={}",
+            self.config.reserve_x,
+            self.config.reserve_y
+        );
+
+        Ok(())
+    }
+}
+
+/// Account structure for sending vault balances above the tracked reserves to a destination
+#[derive(Accounts)]
+pub struct Skim<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [b"config", config.seed.to_be_bytes().as_ref()],
+        bump = config.config_bump,
+        constraint = config.authority.is_some() @ AmmError::NoAuthoritySet,
+        constraint = config.authority == Some(authority.key()) @ AmmError::InvalidAuthority
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(address = config.mint_x @ AmmError::InvalidToken)]
+    pub mint_x: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(address = config.mint_y @ AmmError::InvalidToken)]
+    pub mint_y: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint_x,
+        associated_token::authority = config,
+        associated_token::token_program = token_program
+    )]
+    pub vault_x: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint_y,
+        associated_token::authority = config,
+        associated_token::token_program = token_program
+    )]
+    pub vault_y: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    /// Receives the excess of mint X
+    #[account(mut, token::mint = mint_x)]
+    pub destination_x: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    /// Receives the excess of mint Y
+    #[account(mut, token::mint = mint_y)]
+    pub destination_y: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+impl<'info> Skim<'info> {
+    pub fn skim(&mut self, remaining_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        self.config.check_version()?;
+        require!(!self.config.borrowing, AmmError::FlashLoanActive);
+
+        let excess_x = self.vault_x.amount.saturating_sub(self.config.reserve_x);
+        let excess_y = self.vault_y.amount.saturating_sub(self.config.reserve_y);
+
+        if excess_x > 0 {
+            self.skim_tokens(true, excess_x, remaining_accounts)?;
+        }
+        if excess_y > 0 {
+            self.skim_tokens(false, excess_y, remaining_accounts)?;
+        }
+
+        msg!("Skimmed excess: x={}, y={}", excess_x, excess_y);
+
+        Ok(())
+    }
+
+    fn skim_tokens(
+        &self,
+        is_x: bool,
+        amount: u64,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        let (vault, mint, destination) = if is_x {
+            (&self.vault_x, &self.mint_x, &self.destination_x)
+        } else {
+            (&self.vault_y, &self.mint_y, &self.destination_y)
+        };
+
+        let seeds = &[
+            b"config",
+            &self.config.seed.to_be_bytes()[..],
+            &[self.config.config_bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let extensions = TokenExtensions::new(&mint.to_account_info())?;
+
+        transfer_with_extensions(
+            self.token_program.to_account_info(),
+            vault.to_account_info(),
+            mint.to_account_info(),
+            destination.to_account_info(),
+            self.config.to_account_info(),
+            &extensions,
+            remaining_accounts,
+            amount,
+            mint.decimals,
+            signer_seeds,
+        )
+    }
+}
@@ -1,19 +1,51 @@
-use anchor_lang::prelude::*;
+use anchor_lang::{
+    prelude::*,
+    solana_program::{
+        instruction::Instruction,
+        program::{get_return_data, invoke},
+    },
+};
 use anchor_spl::{
     associated_token::AssociatedToken,
-    token_interface::{
-        transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked,
-        transfer_checked_with_fee, TransferCheckedWithFee,
-    },
+    token_interface::{Mint, TokenAccount, TokenInterface},
 };
 use crate::{
+    constants::{MAX_FEE_BPS, MAX_REFERRAL_FEE_BPS, MAX_SWAP_STEPS},
     error::AmmError, 
+    events::SwapEvent,
     state::Config,
-    utils::token_utils::{TokenExtensions, invoke_transfer_checked_with_hooks},
+    utils::{
+        math::{mul_div_ceil, mul_div_floor, price_impact_bps},
+        native_sol::{unwrap_sol, wrap_sol},
+        token_utils::{
+            check_hook_account_budget, check_pool_vaults, check_vaults_not_frozen, TokenExtensions, transfer_with_extensions,
+        },
+    },
 };
 use constant_product_curve::ConstantProduct;
 use constant_product_curve::LiquidityPair;
 
+/// Side of the pool a transfer touches
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PoolSide {
+    X,
+    Y,
+}
+
+impl PoolSide {
+    /// Input side of a swap given its `is_x` flag
+    pub fn input(is_x: bool) -> Self {
+        if is_x { PoolSide::X } else { PoolSide::Y }
+    }
+
+    pub fn opposite(self) -> Self {
+        match self {
+            PoolSide::X => PoolSide::Y,
+            PoolSide::Y => PoolSide::X,
+        }
+    }
+}
+
 #[derive(Accounts)]
 pub struct Swap<'info> {
     #[account(mut)]
@@ -57,6 +89,7 @@ pub struct Swap<'info> {
     pub vault_y: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
+        mut,
         seeds = [b"config", config.seed.to_be_bytes().as_ref()],
         bump = config.config_bump
     )]
@@ -77,6 +110,16 @@ pub struct Swap<'info> {
     )]
     pub user_lp: InterfaceAccount<'info, TokenAccount>,
 
+    /// Receives the protocol share of the fee, required when `config.protocol_fee_bps > 0` and the fee switch is on
+    /// Must hold the input mint and be owned by `config.protocol_fee_destination`
+    #[account(mut)]
+    pub protocol_fee_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    /// Front-end's account for the referral share of the fee, must hold the input mint
+    /// Without it the whole fee goes to LPs and the protocol as before
+    #[account(mut)]
+    pub referral_fee_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
     pub associated_token_program: Program<'info, AssociatedToken>,
     pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
@@ -88,60 +131,214 @@ impl<'info> Swap<'info> {
         is_x: bool,
         amount: u64,
         min: u64,
+        max_price_impact_bps: u16,
+        referral_fee_bps: u16,
+        native_sol: bool,
         _remaining_accounts: &[AccountInfo<'info>]
     ) -> Result<()> {
+        self.swap_fills(is_x, &[amount], min, max_price_impact_bps, referral_fee_bps, native_sol, _remaining_accounts)
+    }
+
+    /// Fill one order as consecutive swaps of `amounts` against the pool, atomically
+    /// `min_out` bounds the total output, each fill is still bounded by `max_price_impact_bps`
+    pub fn swap_batched(
+        &mut self,
+        is_x: bool,
+        amounts: Vec<u64>,
+        min_out: u64,
+        max_price_impact_bps: u16,
+        referral_fee_bps: u16,
+        native_sol: bool,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        require!(
+            !amounts.is_empty() && amounts.len() <= MAX_SWAP_STEPS as usize,
+            AmmError::InvalidAmount
+        );
+
+        self.swap_fills(is_x, &amounts, min_out, max_price_impact_bps, referral_fee_bps, native_sol, remaining_accounts)
+    }
+
+    /// Shared entry of `swap` and `swap_batched`: checks and guards once, then one `swap_step` per fill
+    fn swap_fills(
+        &mut self,
+        is_x: bool,
+        amounts: &[u64],
+        min_out: u64,
+        max_price_impact_bps: u16,
+        referral_fee_bps: u16,
+        native_sol: bool,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        compute_trace!("swap: start");
+        self.config.check_version()?;
+        require!(!self.config.borrowing, AmmError::FlashLoanActive);
+        require!(!self.config.emergency, AmmError::EmergencyMode);
+        require!(referral_fee_bps <= MAX_REFERRAL_FEE_BPS, AmmError::InvalidFee);
+
+        // Persist the guard so a nested call into this pool fails on entry
+        self.config.enter()?;
+        self.config.exit(&crate::ID)?;
+
+        // Oracle sees the reserves as they were before this instruction
+        self.config.update_oracle(Clock::get()?.unix_timestamp);
+
         // Manual validation replacing has_one constraints
         require!(self.config.mint_x == self.mint_x.key(), AmmError::InvalidToken);
         require!(self.config.mint_y == self.mint_y.key(), AmmError::InvalidToken);
-        
-        // Get extension information for both tokens (scoped to minimize stack lifetime)
-        let (input_mint, output_mint) = if is_x {
-            (&self.mint_x, &self.mint_y)
+        check_pool_vaults(
+            &self.config.key(),
+            &self.config.mint_x,
+            &self.config.mint_y,
+            &self.vault_x,
+            &self.vault_y,
+        )?;
+        check_vaults_not_frozen(&self.vault_x, &self.vault_y)?;
+
+        // Unpack each mint's extensions once and share them with every transfer below
+        let x_extensions = TokenExtensions::new(&self.mint_x.to_account_info())?;
+        let y_extensions = TokenExtensions::new(&self.mint_y.to_account_info())?;
+        check_hook_account_budget(
+            &[self.mint_x.to_account_info(), self.mint_y.to_account_info()],
+            remaining_accounts,
+        )?;
+
+        // A failed swap reverts the wrap with everything else, so only success needs the unwrap
+        if native_sol {
+            let total_in = amounts
+                .iter()
+                .try_fold(0u64, |total, amount| total.checked_add(*amount))
+                .ok_or(AmmError::Overflow)?;
+            let user_input = match PoolSide::input(is_x) {
+                PoolSide::X => &self.user_x,
+                PoolSide::Y => &self.user_y,
+            };
+            wrap_sol(
+                self.system_program.to_account_info(),
+                self.token_program.to_account_info(),
+                self.user.to_account_info(),
+                user_input,
+                total_in,
+            )?;
+        }
+
+        // A single swap checks its `min` on the fill, before any transfer
+        let step_min = if amounts.len() == 1 { min_out } else { 0 };
+        let mut total_out: u64 = 0;
+        for amount in amounts {
+            let amount_out = self.swap_step(
+                is_x,
+                *amount,
+                step_min,
+                max_price_impact_bps,
+                referral_fee_bps,
+                &x_extensions,
+                &y_extensions,
+                remaining_accounts,
+            )?;
+            total_out = total_out.checked_add(amount_out).ok_or(AmmError::Overflow)?;
+        }
+        require!(total_out >= min_out, AmmError::SlippageExceeded);
+
+        if native_sol {
+            self.unwrap_sol_accounts()?;
+        }
+
+        self.config.leave();
+
+        compute_trace!("swap: end");
+
+        Ok(())
+    }
+
+    /// Price and settle one fill of `amount` against the current reserves, returning the output
+    fn swap_step(
+        &mut self,
+        is_x: bool,
+        amount: u64,
+        min: u64,
+        max_price_impact_bps: u16,
+        referral_fee_bps: u16,
+        x_extensions: &TokenExtensions,
+        y_extensions: &TokenExtensions,
+        _remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<u64> {
+        let input_side = PoolSide::input(is_x);
+        let output_side = input_side.opposite();
+
+        let (input_ext, output_ext) = if is_x {
+            (x_extensions, y_extensions)
         } else {
-            (&self.mint_y, &self.mint_x)
+            (y_extensions, x_extensions)
         };
 
-        // Calculate net amount that will reach the vault after input fees
-        let input_fee = {
-            let input_ext = TokenExtensions::new(&input_mint.to_account_info())?;
-            input_ext.calculate_fee(amount)
-        };
-        let net_amount_in = amount.saturating_sub(input_fee);
+        // Net amount predicted to reach the vault after input fees
+        let predicted_amount_in = amount.saturating_sub(input_ext.calculate_fee(amount));
         
-        require!(net_amount_in > 0, AmmError::InvalidAmount);
+        require!(predicted_amount_in > 0, AmmError::InvalidAmount);
 
-        // Get the actual vault amounts (accounting for any transfer fees on previous deposits)
-        let vault_x_amount = self.vault_x.amount;
-        let vault_y_amount = self.vault_y.amount;
+        // Price against tracked reserves; tokens donated to the vaults are left for `skim`
+        let vault_x_amount = self.config.reserve_x;
+        let vault_y_amount = self.config.reserve_y;
+        require!(vault_x_amount > 0 && vault_y_amount > 0, AmmError::NoLiquidityInPool);
 
         // Get dynamic fee from transfer hook (if available) or use default
-        let dynamic_fee = self.get_dynamic_fee(_remaining_accounts)
-            .unwrap_or(self.config.fee as u64) as u16;
+        // Read before the input transfer updates the hook's stats
+        let dynamic_fee = pool_swap_fee(&self.config, x_extensions, y_extensions, _remaining_accounts)?;
 
-        // Initialize the curve with current vault amounts and dynamic fee
-        let mut curve = ConstantProduct::init(
-            vault_x_amount,
-            vault_y_amount,
-            self.mint_lp.supply,
-            dynamic_fee,
-            None,
-        )
-        .map_err(|_| AmmError::MathOverflow)?;
+        let (before_x, before_y) = (self.vault_x.amount, self.vault_y.amount);
 
-        let p = match is_x {
-            true => LiquidityPair::X,
-            false => LiquidityPair::Y,
-        };
+        // Input: user pays gross amount (including fees)
+        self.deposit_tokens(input_side, amount, input_ext, _remaining_accounts)?;
 
-        // Calculate swap amounts using NET input amount (what actually reaches the vault)
-        let res = curve.swap(p, net_amount_in, min)
-            .map_err(|_| AmmError::SlippageExceeded)?;
+        // Price what the vault was actually credited, whatever the fee or hook did to the transfer
+        let net_amount_in = match input_side {
+            PoolSide::X => {
+                self.vault_x.reload()?;
+                credited_input(before_x, self.vault_x.amount, amount)?
+            }
+            PoolSide::Y => {
+                self.vault_y.reload()?;
+                credited_input(before_y, self.vault_y.amount, amount)?
+            }
+        };
+        if net_amount_in != predicted_amount_in {
+            msg!("Input credited {} instead of the predicted {}", net_amount_in, predicted_amount_in);
+        }
 
-        // For output with transfer fees, calculate gross amount needed
-        let gross_output = {
-            let output_ext = TokenExtensions::new(&output_mint.to_account_info())?;
-            output_ext.calculate_gross_for_net(res.withdraw)
+        let referral_fee_bps = match self.referral_fee_account {
+            Some(_) => referral_fee_bps,
+            None => 0,
         };
+        let SwapPricing { protocol_fee, referral_fee, amount_out } = price_swap(
+            &self.config,
+            is_x,
+            net_amount_in,
+            self.mint_lp.supply,
+            dynamic_fee,
+            referral_fee_bps,
+        )?;
+
+        // What leaves the vault and what the user nets depends on who bears the output transfer fee
+        let (gross_output, net_output) = output_amounts(amount_out, output_ext, self.config.pass_output_fee);
+        require!(net_output >= min, AmmError::SlippageExceeded);
+
+        // Dust trades against deep pools round to nothing but would still pay fees
+        require!(net_output > 0, AmmError::InvalidAmount);
+
+        // Bound the move away from the pre-swap spot price, 0 disables the check
+        if max_price_impact_bps > 0 {
+            let (reserve_in, reserve_out) = if is_x {
+                (vault_x_amount, vault_y_amount)
+            } else {
+                (vault_y_amount, vault_x_amount)
+            };
+            let impact = price_impact_bps(net_amount_in, amount_out, reserve_in, reserve_out)?;
+            require!(
+                impact <= max_price_impact_bps as u64,
+                AmmError::SlippageExceeded
+            );
+        }
 
         // Verify vault has enough tokens to cover the gross withdrawal
         let vault_balance = if is_x {
@@ -150,122 +347,168 @@ impl<'info> Swap<'info> {
             self.vault_x.amount
         };
         require!(gross_output <= vault_balance, AmmError::InsufficientFunds);
+        self.config.check_min_reserve(vault_balance - gross_output)?;
 
-        // Perform the actual transfers
-        // Input: user pays gross amount (including fees)
-        self.deposit_tokens(is_x, amount, _remaining_accounts)?;
-        // Output: vault sends gross amount (user receives net after fees)
-        self.withdraw_tokens(!is_x, gross_output, _remaining_accounts)?;
+        // Protocol share leaves the input vault before the output is paid
+        if protocol_fee > 0 {
+            self.transfer_protocol_fee(input_side, protocol_fee, input_ext, _remaining_accounts)?;
+        }
+        if referral_fee > 0 {
+            self.transfer_referral_fee(input_side, referral_fee, input_ext, _remaining_accounts)?;
+        }
+        // Output: vault sends the gross amount, the user receives net after fees
+        self.withdraw_tokens(output_side, gross_output, output_ext, _remaining_accounts)?;
+
+        // Track the net reserve changes of this swap
+        self.vault_x.reload()?;
+        self.vault_y.reload()?;
+        self.config.apply_vault_deltas(before_x, self.vault_x.amount, before_y, self.vault_y.amount)?;
+        self.config.check_reserves_backed(self.vault_x.amount, self.vault_y.amount)?;
+
+        self.config.record_swap(is_x, net_amount_in, amount_out)?;
+
+        emit!(SwapEvent {
+            config: self.config.key(),
+            user: self.user.key(),
+            is_x,
+            amount_in: amount,
+            amount_out: net_output,
+            fee_basis_points: dynamic_fee,
+            protocol_fee,
+            referral_fee,
+        });
+
+        Ok(net_output)
+    }
 
+    /// Close the user's WSOL accounts so leftover input and the output come back as lamports
+    fn unwrap_sol_accounts(&self) -> Result<()> {
+        for account in [&self.user_x, &self.user_y] {
+            unwrap_sol(self.token_program.to_account_info(), self.user.to_account_info(), account)?;
+        }
         Ok(())
     }
 
+    /// Send the protocol share of the fee from the input vault to the protocol fee account
+    fn transfer_protocol_fee(
+        &self,
+        side: PoolSide,
+        amount: u64,
+        extensions: &TokenExtensions,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        let protocol_fee_account = self
+            .protocol_fee_account
+            .as_ref()
+            .ok_or(AmmError::MissingProtocolFeeAccount)?;
+
+        let (vault, mint) = match side {
+            PoolSide::X => (&self.vault_x, &self.mint_x),
+            PoolSide::Y => (&self.vault_y, &self.mint_y),
+        };
+
+        require!(protocol_fee_account.mint == mint.key(), AmmError::InvalidTokenAccount);
+        require!(
+            protocol_fee_account.owner == self.config.protocol_fee_destination,
+            AmmError::InvalidTokenAccount
+        );
 
+        self.transfer_from_vault(vault, mint, protocol_fee_account, amount, extensions, remaining_accounts)
+    }
+
+    /// Send the referral share of the fee from the input vault to the referral fee account
+    fn transfer_referral_fee(
+        &self,
+        side: PoolSide,
+        amount: u64,
+        extensions: &TokenExtensions,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        let referral_fee_account = self
+            .referral_fee_account
+            .as_ref()
+            .ok_or(AmmError::InvalidTokenAccount)?;
+
+        let (vault, mint) = match side {
+            PoolSide::X => (&self.vault_x, &self.mint_x),
+            PoolSide::Y => (&self.vault_y, &self.mint_y),
+        };
+
+        require!(referral_fee_account.mint == mint.key(), AmmError::InvalidTokenAccount);
+
+        self.transfer_from_vault(vault, mint, referral_fee_account, amount, extensions, remaining_accounts)
+    }
+
+    fn transfer_from_vault(
+        &self,
+        vault: &InterfaceAccount<'info, TokenAccount>,
+        mint: &InterfaceAccount<'info, Mint>,
+        destination: &InterfaceAccount<'info, TokenAccount>,
+        amount: u64,
+        extensions: &TokenExtensions,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        let seeds = &[
+            b"config",
+            &self.config.seed.to_be_bytes()[..],
+            &[self.config.config_bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        transfer_with_extensions(
+            self.token_program.to_account_info(),
+            vault.to_account_info(),
+            mint.to_account_info(),
+            destination.to_account_info(),
+            self.config.to_account_info(),
+            extensions,
+            remaining_accounts,
+            amount,
+            mint.decimals,
+            signer_seeds,
+        )
+    }
+
+
+    /// Move `amount` from the user's account into the vault of `side`
     pub fn deposit_tokens(
         &mut self,
-        is_x: bool,
+        side: PoolSide,
         amount: u64,
-        _remaining_accounts: &[AccountInfo<'info>],
+        extensions: &TokenExtensions,
+        remaining_accounts: &[AccountInfo<'info>],
     ) -> Result<()> {
-        let (from, to, mint) = if is_x {
-            (
-                &self.user_x,
-                &self.vault_x,
-                &self.mint_x,
-            )
-        } else {
-            (
-                &self.user_y,
-                &self.vault_y,
-                &self.mint_y,
-            )
+        let (from, to, mint) = match side {
+            PoolSide::X => (&self.user_x, &self.vault_x, &self.mint_x),
+            PoolSide::Y => (&self.user_y, &self.vault_y, &self.mint_y),
         };
 
-        let decimals = mint.decimals;
-        let cpi_program = self.token_program.to_account_info();
-
-        // Get extension information using centralized utilities
-        let extensions = TokenExtensions::new(&mint.to_account_info())?;
-
-        match (extensions.has_transfer_fee, extensions.has_transfer_hook) {
-            // Token with transfer fee only
-            (true, false) => {
-                let cpi_accounts = TransferCheckedWithFee {
-                    source: from.to_account_info(),
-                    destination: to.to_account_info(),
-                    authority: self.user.to_account_info(),
-                    mint: mint.to_account_info(),
-                    token_program_id: cpi_program.clone(),
-                };
-                let ctx = CpiContext::new(cpi_program, cpi_accounts);
-                let expected_fee = extensions.calculate_fee(amount);
-                transfer_checked_with_fee(ctx, amount, decimals, expected_fee)?;
-            }
-            
-            // Token with BOTH transfer fee AND transfer hook - use direct Token-2022 call
-            (true, true) => {
-                invoke_transfer_checked_with_hooks(
-                    &cpi_program.key(),
-                    from.to_account_info(),
-                    mint.to_account_info(),
-                    to.to_account_info(),
-                    self.user.to_account_info(),
-                    _remaining_accounts,
-                    amount,
-                    decimals,
-                    &[], // No signer seeds needed for user authority
-                )?;
-            }
-            
-            // Token with transfer hook only - use direct Token-2022 call
-            (false, true) => {
-                invoke_transfer_checked_with_hooks(
-                    &cpi_program.key(),
-                    from.to_account_info(),
-                    mint.to_account_info(),
-                    to.to_account_info(),
-                    self.user.to_account_info(),
-                    _remaining_accounts,
-                    amount,
-                    decimals,
-                    &[], // No signer seeds needed for user authority
-                )?;
-            }
-            
-            // Standard token (no extensions)
-            (false, false) => {
-                let cpi_accounts = TransferChecked {
-                    from: from.to_account_info(),
-                    to: to.to_account_info(),
-                    authority: self.user.to_account_info(),
-                    mint: mint.to_account_info(),
-                };
-                let ctx = CpiContext::new(cpi_program, cpi_accounts);
-                transfer_checked(ctx, amount, decimals)?;
-            }
-        }
-
-        Ok(())
+        // No signer seeds needed for user authority
+        transfer_with_extensions(
+            self.token_program.to_account_info(),
+            from.to_account_info(),
+            mint.to_account_info(),
+            to.to_account_info(),
+            self.user.to_account_info(),
+            extensions,
+            remaining_accounts,
+            amount,
+            mint.decimals,
+            &[],
+        )
     }
 
+    /// Move `amount` from the vault of `side` to the user's account of the same mint
     pub fn withdraw_tokens(
         &mut self,
-        is_x: bool,
+        side: PoolSide,
         amount: u64,
-        _remaining_accounts: &[AccountInfo<'info>],
+        extensions: &TokenExtensions,
+        remaining_accounts: &[AccountInfo<'info>],
     ) -> Result<()> {
-        let (from, to, mint) = if is_x {
-            (
-                &self.vault_x,
-                &self.user_x,
-                &self.mint_x,
-            )
-        } else {
-            (
-                &self.vault_y,
-                &self.user_y,
-                &self.mint_y,
-            )
+        let (from, to, mint) = match side {
+            PoolSide::X => (&self.vault_x, &self.user_x, &self.mint_x),
+            PoolSide::Y => (&self.vault_y, &self.user_y, &self.mint_y),
         };
 
         let seeds = &[
@@ -275,148 +518,468 @@ impl<'info> Swap<'info> {
         ];
         let signer_seeds = &[&seeds[..]];
 
-        let decimals = mint.decimals;
-        let cpi_program = self.token_program.to_account_info();
-
-        // Get extension information using centralized utilities
-        let extensions = TokenExtensions::new(&mint.to_account_info())?;
-
-        match (extensions.has_transfer_fee, extensions.has_transfer_hook) {
-            // Token with transfer fee only
-            (true, false) => {
-                let cpi_accounts = TransferCheckedWithFee {
-                    source: from.to_account_info(),
-                    destination: to.to_account_info(),
-                    authority: self.config.to_account_info(),
-                    mint: mint.to_account_info(),
-                    token_program_id: cpi_program.clone(),
-                };
-                let ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
-                let expected_fee = extensions.calculate_fee(amount);
-                transfer_checked_with_fee(ctx, amount, decimals, expected_fee)?;
-            }
-            
-            // Token with BOTH transfer fee AND transfer hook - use direct Token-2022 call
-            (true, true) => {
-                invoke_transfer_checked_with_hooks(
-                    &cpi_program.key(),
-                    from.to_account_info(),
-                    mint.to_account_info(),
-                    to.to_account_info(),
-                    self.config.to_account_info(),
-                    _remaining_accounts,
-                    amount,
-                    decimals,
-                    signer_seeds,
-                )?;
-            }
-            
-            // Token with transfer hook only - use direct Token-2022 call
-            (false, true) => {
-                invoke_transfer_checked_with_hooks(
-                    &cpi_program.key(),
-                    from.to_account_info(),
-                    mint.to_account_info(),
-                    to.to_account_info(),
-                    self.config.to_account_info(),
-                    _remaining_accounts,
-                    amount,
-                    decimals,
-                    signer_seeds,
-                )?;
-            }
-            
-            // Standard token (no extensions)
-            (false, false) => {
-                let cpi_accounts = TransferChecked {
-                    from: from.to_account_info(),
-                    to: to.to_account_info(),
-                    authority: self.config.to_account_info(),
-                    mint: mint.to_account_info(),
-                };
-                let ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
-                transfer_checked(ctx, amount, decimals)?;
-            }
-        }
+        transfer_with_extensions(
+            self.token_program.to_account_info(),
+            from.to_account_info(),
+            mint.to_account_info(),
+            to.to_account_info(),
+            self.config.to_account_info(),
+            extensions,
+            remaining_accounts,
+            amount,
+            mint.decimals,
+            signer_seeds,
+        )
+    }
+}
 
-        Ok(())
+/// Amount the vault pays out and the trader nets for a curve output of `amount_out`
+/// Pools absorbing the output mint's transfer fee gross the payout up so the trader nets
+/// `amount_out`; pass-through pools pay `amount_out` and the trader nets it less the fee
+pub(crate) fn output_amounts(amount_out: u64, output_ext: &TokenExtensions, pass_output_fee: bool) -> (u64, u64) {
+    if pass_output_fee {
+        (amount_out, amount_out.saturating_sub(output_ext.calculate_fee(amount_out)))
+    } else {
+        (output_ext.calculate_gross_for_net(amount_out), amount_out)
     }
+}
 
-    /// Reads dynamic fee from transfer hook fee stats account
-    /// Returns None if hook is not available or fee stats cannot be read
-    fn get_dynamic_fee(&self, remaining_accounts: &[AccountInfo]) -> Option<u64> {
-        // Check which token has transfer hook extension
-        let x_extensions = TokenExtensions::new(&self.mint_x.to_account_info()).ok()?;
-        let y_extensions = TokenExtensions::new(&self.mint_y.to_account_info()).ok()?;
-        
-        let hook_program_id = if x_extensions.has_transfer_hook {
-            x_extensions.transfer_hook_program_id?
-        } else if y_extensions.has_transfer_hook {
-            y_extensions.transfer_hook_program_id?
-        } else {
-            return None; // No hook token in this pool
-        };
+/// Input a fill actually credited to the input vault, which the curve prices instead of the prediction
+/// Fails when nothing arrived or more arrived than the trader sent, neither of which a transfer can do
+fn credited_input(before: u64, after: u64, amount: u64) -> Result<u64> {
+    let credited = after.checked_sub(before).ok_or(AmmError::InvalidAmount)?;
+    require!(credited > 0 && credited <= amount, AmmError::InvalidAmount);
+    Ok(credited)
+}
 
-        // Verify hook program is whitelisted
-        if let Some(expected_hook_program) = self.config.default_hook_program {
-            if hook_program_id != expected_hook_program {
-                return None; // Unauthorized hook program
-            }
-        }
+/// Fee in basis points a swap against the pool charges right now
+/// The approved transfer hooks' fee within the pool's dynamic fee bounds when they report one,
+/// `config.fee` otherwise
+pub(crate) fn pool_swap_fee(
+    config: &Config,
+    x_extensions: &TokenExtensions,
+    y_extensions: &TokenExtensions,
+    remaining_accounts: &[AccountInfo],
+) -> Result<u16> {
+    let dynamic_fee = get_dynamic_fee(config, x_extensions, y_extensions, remaining_accounts)?;
+    Ok(match dynamic_fee {
+        Some(fee_bps) => config.bound_dynamic_fee(clamp_dynamic_fee(fee_bps)),
+        None => clamp_dynamic_fee(config.fee as u64),
+    })
+}
 
-        // Look for fee stats account in remaining accounts (index 7 based on hook structure)
-        if remaining_accounts.len() >= 8 {
-            if let Some(fee_stats_account) = remaining_accounts.get(7) {
-                if let Ok(fee_stats) = self.parse_dynamic_fee_stats(fee_stats_account) {
-                    let dynamic_fee_bp = fee_stats.current_fee_basis_points as u64;
-                    msg!("Dynamic fee: {}bp from hook {}", dynamic_fee_bp, hook_program_id);
-                    return Some(dynamic_fee_bp);
-                }
-            }
+/// Reads the dynamic fee from the transfer hooks through their `get_current_fee` instruction
+/// Returns None if the pool has no approved hook, or if a hook is unapproved or its fee can't
+/// be read and `config.strict_hooks` is off
+fn get_dynamic_fee(
+    config: &Config,
+    x_extensions: &TokenExtensions,
+    y_extensions: &TokenExtensions,
+    remaining_accounts: &[AccountInfo],
+) -> Result<Option<u64>> {
+    // Hooks flagged unsafe aren't called for their fee, swaps price at the pool fee
+    if config.hook_unsafe {
+        return Ok(None);
+    }
+
+    let hook_of = |extensions: &TokenExtensions| {
+        extensions
+            .transfer_hook_program_id
+            .filter(|_| extensions.has_transfer_hook)
+    };
+
+    select_dynamic_fee(
+        hook_of(x_extensions),
+        hook_of(y_extensions),
+        config.default_hook_program,
+        config.strict_hooks,
+        |hook_program_id| hook_dynamic_fee(hook_program_id, remaining_accounts),
+    )
+}
+
+/// Amounts of one fill priced against the tracked reserves
+pub(crate) struct SwapPricing {
+    pub protocol_fee: u64,
+    pub referral_fee: u64,
+    /// Output the trader receives, before the output mint's transfer fee is grossed up
+    pub amount_out: u64,
+}
+
+/// Price a fill of `net_amount_in` that already reached the input vault at `fee_bps`
+/// Shared by `swap` and `quote_swap` so a quote prices exactly like the swap it predicts
+pub(crate) fn price_swap(
+    config: &Config,
+    is_x: bool,
+    net_amount_in: u64,
+    lp_supply: u64,
+    fee_bps: u16,
+    referral_fee_bps: u16,
+) -> Result<SwapPricing> {
+    // Carve the protocol and referral shares out of the fee before the curve sees the input
+    let protocol_fee_bps = config.protocol_fee_bps_for(fee_bps);
+    let referral_fee_bps = std::cmp::min(referral_fee_bps, fee_bps - protocol_fee_bps);
+    let (carved_fee, curve_amount_in, lp_fee) =
+        split_protocol_fee(net_amount_in, fee_bps, protocol_fee_bps + referral_fee_bps)?;
+    let referral_fee = split_referral_fee(carved_fee, protocol_fee_bps, referral_fee_bps)?;
+    let protocol_fee = carved_fee - referral_fee;
+
+    // Initialize the curve with current vault amounts and the LP share of the fee
+    // x·y = k holds in raw base units whatever each mint's decimals, so no normalization applies
+    let mut curve = ConstantProduct::init(
+        config.reserve_x,
+        config.reserve_y,
+        lp_supply,
+        lp_fee,
+        None,
+    )
+    .map_err(|_| AmmError::MathOverflow)?;
+
+    let p = match is_x {
+        true => LiquidityPair::X,
+        false => LiquidityPair::Y,
+    };
+
+    // Calculate swap amounts using NET input amount (what actually reaches the vault)
+    // The curve gets no minimum so its failures are arithmetic, slippage is checked by callers
+    let res = curve.swap(p, curve_amount_in, 0)
+        .map_err(|_| AmmError::CurveError)?;
+
+    Ok(SwapPricing { protocol_fee, referral_fee, amount_out: res.withdraw })
+}
+
+/// Dynamic fee of a pool given the hook program of each mint
+///
+/// Each hooked leg reads its own hook's fee through `read_fee`. When both mints carry an approved hook the
+/// higher fee applies, so the fee doesn't depend on the swap direction and LPs are never
+/// undercharged by whichever hook reports less.
+fn select_dynamic_fee(
+    x_hook: Option<Pubkey>,
+    y_hook: Option<Pubkey>,
+    default_hook_program: Option<Pubkey>,
+    strict_hooks: bool,
+    read_fee: impl Fn(&Pubkey) -> Result<Option<u64>>,
+) -> Result<Option<u64>> {
+    let mut dynamic_fee = None;
+
+    for hook_program_id in [x_hook, y_hook].into_iter().flatten() {
+        // Only the approved hook program sets the fee
+        if default_hook_program.is_some_and(|expected| expected != hook_program_id) {
+            require!(!strict_hooks, AmmError::UnauthorizedHookProgram);
+            msg!("Ignoring fee of unapproved hook {}", hook_program_id);
+            continue;
         }
 
-        None
+        let fee = read_fee(&hook_program_id)?;
+        require!(fee.is_some() || !strict_hooks, AmmError::FeeStatsNotFound);
+        dynamic_fee = dynamic_fee.max(fee);
     }
 
-    /// Parse dynamic fee stats from account data
-    /// This is a simplified parser - in production would use proper deserialization
-    fn parse_dynamic_fee_stats(&self, account: &AccountInfo) -> Result<DynamicFeeStatsView> {
-        let data = account.try_borrow_data()?;
-        
-        // Skip discriminator (8 bytes) and parse key fields
-        if data.len() < 32 {
-            return Err(AmmError::InvalidAccountData.into());
-        }
+    Ok(dynamic_fee)
+}
 
-        // Parse key fields from the account data
-        // This is a simplified version - real implementation would use proper Borsh deserialization
-        let current_fee_basis_points = u16::from_le_bytes([data[32], data[33]]);
-        let base_fee_basis_points = u16::from_le_bytes([data[34], data[35]]);
-        
-        // Parse recent transfers array (simplified)
-        let mut recent_transfers = [0u64; 6];
-        for i in 0..6 {
-            let offset = 44 + i * 8;
-            if data.len() >= offset + 8 {
-                recent_transfers[i] = u64::from_le_bytes([
-                    data[offset], data[offset+1], data[offset+2], data[offset+3],
-                    data[offset+4], data[offset+5], data[offset+6], data[offset+7]
-                ]);
-            }
-        }
+/// Bound the fee a swap charges to what a pool could be configured with
+/// A hook reporting more is misbehaving, so the clamp is logged
+fn clamp_dynamic_fee(fee_bps: u64) -> u16 {
+    if fee_bps > MAX_FEE_BPS as u64 {
+        msg!("Dynamic fee {}bp clamped to {}bp", fee_bps, MAX_FEE_BPS);
+        return MAX_FEE_BPS;
+    }
+    fee_bps as u16
+}
 
-        Ok(DynamicFeeStatsView {
-            current_fee_basis_points,
-            base_fee_basis_points,
-            recent_transfers,
-        })
+/// Discriminator of the hook's `get_current_fee` instruction, `sha256("global:get_current_fee")[..8]`
+const GET_CURRENT_FEE_DISCRIMINATOR: [u8; 8] = [142, 93, 192, 243, 25, 248, 76, 90];
+
+/// Fee a hook returns from `get_current_fee`, so the pool doesn't depend on its storage layout
+/// Needs the hook program and its fee stats PDA among the remaining accounts. Without them
+/// there is no fee to read and None is returned. A failed CPI can't be caught on Solana, so
+/// the accounts are checked before it and anything going wrong after them aborts the swap
+fn hook_dynamic_fee(hook_program_id: &Pubkey, remaining_accounts: &[AccountInfo]) -> Result<Option<u64>> {
+    let Some((fee_stats, hook_program)) = hook_fee_accounts(hook_program_id, remaining_accounts) else {
+        return Ok(None);
+    };
+
+    let instruction = Instruction {
+        program_id: *hook_program_id,
+        accounts: vec![AccountMeta::new_readonly(fee_stats.key(), false)],
+        data: GET_CURRENT_FEE_DISCRIMINATOR.to_vec(),
+    };
+    invoke(&instruction, &[fee_stats.clone(), hook_program.clone()])?;
+
+    // Return data is only trusted from the hook itself, and is a Borsh `u16`
+    let dynamic_fee_bp = get_return_data()
+        .filter(|(program_id, _)| program_id == hook_program_id)
+        .and_then(|(_, data)| <[u8; 2]>::try_from(data.as_slice()).ok())
+        .map(u16::from_le_bytes)
+        .ok_or(AmmError::HookExecutionFailed)? as u64;
+
+    msg!("Dynamic fee: {}bp from hook {}", dynamic_fee_bp, hook_program_id);
+    Ok(Some(dynamic_fee_bp))
+}
+
+/// Fee stats PDA and program of a hook among the remaining accounts, if `get_current_fee` can run
+/// The fee stats must be initialized and owned by the hook, and the program deployed
+fn hook_fee_accounts<'a, 'info>(
+    hook_program_id: &Pubkey,
+    remaining_accounts: &'a [AccountInfo<'info>],
+) -> Option<(&'a AccountInfo<'info>, &'a AccountInfo<'info>)> {
+    let (fee_stats_address, _) = Pubkey::find_program_address(&[b"fee_stats"], hook_program_id);
+    let fee_stats = remaining_accounts.iter().find(|account| {
+        account.key == &fee_stats_address && account.owner == hook_program_id && !account.data_is_empty()
+    })?;
+    let hook_program = remaining_accounts
+        .iter()
+        .find(|account| account.key == hook_program_id && account.executable)?;
+
+    Some((fee_stats, hook_program))
+}
+
+/// Split the protocol share off the input amount
+/// Returns (protocol_fee, curve_amount_in, lp_fee_bps) where the curve charges `lp_fee_bps`
+/// on the remainder so the trader still pays `fee_bps` in total
+fn split_protocol_fee(amount_in: u64, fee_bps: u16, protocol_fee_bps: u16) -> Result<(u64, u64, u16)> {
+    if protocol_fee_bps == 0 {
+        return Ok((0, amount_in, fee_bps));
+    }
+
+    let protocol_fee = mul_div_floor(amount_in, protocol_fee_bps as u64, 10_000)?;
+    let curve_amount_in = amount_in
+        .checked_sub(protocol_fee)
+        .ok_or(AmmError::Underflow)?;
+
+    // (1 - p)(1 - f') = (1 - f)  =>  f' = (f - p) / (1 - p), rounded up in favor of LPs
+    let lp_fee = mul_div_ceil((fee_bps - protocol_fee_bps) as u64, 10_000, 10_000 - protocol_fee_bps as u64)?;
+
+    Ok((protocol_fee, curve_amount_in, lp_fee as u16))
+}
+
+/// Referral part of the fee carved out for the protocol and the referrer together
+/// Both shares come from the same `carved_fee`, so the protocol keeps the rounding dust
+fn split_referral_fee(carved_fee: u64, protocol_fee_bps: u16, referral_fee_bps: u16) -> Result<u64> {
+    if referral_fee_bps == 0 {
+        return Ok(0);
     }
+
+    mul_div_floor(
+        carved_fee,
+        referral_fee_bps as u64,
+        protocol_fee_bps as u64 + referral_fee_bps as u64,
+    )
 }
 
-/// Simplified view of dynamic fee stats for parsing
-#[derive(Debug)]
-struct DynamicFeeStatsView {
-    pub current_fee_basis_points: u16,
-    pub base_fee_basis_points: u16,
-    pub recent_transfers: [u64; 6],
-}
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_referral_fee_carved_from_fee() {
+        // 30bp fee with 5bp protocol and 10bp referral shares on 1_000_000
+        let (carved, curve_in, lp_fee) = split_protocol_fee(1_000_000, 30, 15).unwrap();
+        let referral = split_referral_fee(carved, 5, 10).unwrap();
+
+        assert_eq!(carved, 1_500);
+        assert_eq!(referral, 1_000);
+        assert_eq!(carved - referral, 500);
+
+        // Principal reaching the curve is only reduced by the carved shares
+        assert_eq!(curve_in, 1_000_000 - 1_500);
+        assert!(lp_fee < 30);
+
+        // No referral keeps the whole carve for the protocol
+        assert_eq!(split_referral_fee(carved, 15, 0).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_price_swap_fee_shares() {
+        let config = Config {
+            reserve_x: 1_000_000_000,
+            reserve_y: 1_000_000_000,
+            protocol_fee_bps: 5,
+            fee_enabled: true,
+            ..Config::default()
+        };
+
+        // Same carve as swapping with a referral account
+        let pricing = price_swap(&config, true, 1_000_000, 1_000_000_000, 30, 10).unwrap();
+        assert_eq!(pricing.protocol_fee, 500);
+        assert_eq!(pricing.referral_fee, 1_000);
+
+        // Quotes pass no referral, the protocol share is unchanged
+        let pricing = price_swap(&config, true, 1_000_000, 1_000_000_000, 30, 0).unwrap();
+        assert_eq!(pricing.protocol_fee, 500);
+        assert_eq!(pricing.referral_fee, 0);
+
+        // The referral share is capped at what's left of the fee after the protocol's
+        let pricing = price_swap(&config, false, 1_000_000, 1_000_000_000, 30, 100).unwrap();
+        assert_eq!(pricing.protocol_fee + pricing.referral_fee, 3_000);
+    }
+
+    #[test]
+    fn test_credited_input_prices_fee_token_swaps() {
+        // A 1% fee token: 10_050 sent is predicted as 10_050 - ceil(100.5) = 9_949 net
+        let config = Config {
+            reserve_x: 1_000_000_000,
+            reserve_y: 1_000_000_000,
+            ..Config::default()
+        };
+        let fee_ext = TokenExtensions {
+            has_transfer_fee: true,
+            has_transfer_hook: false,
+            transfer_hook_program_id: None,
+            transfer_fee_basis_points: 100,
+            transfer_fee_maximum: u64::MAX,
+        };
+        assert_eq!(fee_ext.calculate_fee(10_050), 101);
+        let predicted = 10_050 - fee_ext.calculate_fee(10_050);
+        assert_eq!(credited_input(1_000, 1_000 + predicted, 10_050).unwrap(), predicted);
+
+        // A vault credited one unit less prices exactly that, not the prediction
+        let credited = credited_input(1_000, 1_000 + predicted - 1, 10_050).unwrap();
+        assert_eq!(credited, predicted - 1);
+        let priced = price_swap(&config, true, credited, 1_000_000_000, 30, 0).unwrap();
+        let predicted_pricing = price_swap(&config, true, predicted, 1_000_000_000, 30, 0).unwrap();
+        assert!(priced.amount_out <= predicted_pricing.amount_out);
+
+        // Nothing arriving, more than was sent, or a shrinking vault are all rejected
+        assert!(credited_input(1_000, 1_000, 10_050).is_err());
+        assert!(credited_input(1_000, 1_000 + 10_051, 10_050).is_err());
+        assert!(credited_input(1_000, 999, 10_050).is_err());
+    }
+
+    #[test]
+    fn test_output_fee_absorbed_or_passed_through() {
+        use anchor_spl::token_interface::spl_token_2022::extension::transfer_fee::TransferFee;
+
+        let fee_ext = TokenExtensions {
+            has_transfer_fee: true,
+            has_transfer_hook: false,
+            transfer_hook_program_id: None,
+            transfer_fee_basis_points: 100,
+            transfer_fee_maximum: u64::MAX,
+        };
+
+        // What the trader receives once Token-2022 has charged its fee
+        let token_fee = TransferFee {
+            epoch: 0.into(),
+            maximum_fee: u64::MAX.into(),
+            transfer_fee_basis_points: 100.into(),
+        };
+
+        // Absorbing: the pool pays ceil(9_950 / 0.99) = 10_051 so the trader nets the curve output
+        let (gross, net) = output_amounts(9_950, &fee_ext, false);
+        assert_eq!((gross, net), (10_051, 9_950));
+        assert_eq!(token_fee.calculate_post_fee_amount(gross).unwrap(), net);
+
+        // Passing through: the pool pays the curve output and the trader bears ceil(99.5) = 100
+        let (gross, net) = output_amounts(9_950, &fee_ext, true);
+        assert_eq!((gross, net), (9_950, 9_850));
+        assert_eq!(token_fee.calculate_post_fee_amount(gross).unwrap(), net);
+
+        // Without a transfer fee both modes pay and net the same
+        let plain = TokenExtensions { has_transfer_fee: false, transfer_fee_basis_points: 0, ..fee_ext };
+        assert_eq!(output_amounts(9_900, &plain, false), (9_900, 9_900));
+        assert_eq!(output_amounts(9_900, &plain, true), (9_900, 9_900));
+    }
+
+    #[test]
+    fn test_pool_side() {
+        // X -> Y pays into vault X and out of vault Y
+        assert_eq!(PoolSide::input(true), PoolSide::X);
+        assert_eq!(PoolSide::input(true).opposite(), PoolSide::Y);
+
+        // Y -> X pays into vault Y and out of vault X
+        assert_eq!(PoolSide::input(false), PoolSide::Y);
+        assert_eq!(PoolSide::input(false).opposite(), PoolSide::X);
+    }
+
+    #[test]
+    fn test_clamp_dynamic_fee() {
+        assert_eq!(clamp_dynamic_fee(0), 0);
+        assert_eq!(clamp_dynamic_fee(MAX_FEE_BPS as u64), MAX_FEE_BPS);
+        assert_eq!(clamp_dynamic_fee(MAX_FEE_BPS as u64 + 1), MAX_FEE_BPS);
+        // Would have wrapped to 1 through a plain cast
+        assert_eq!(clamp_dynamic_fee(u16::MAX as u64 + 2), MAX_FEE_BPS);
+    }
+
+    #[test]
+    fn test_dual_hook_pool_fee() {
+        let (x_hook, y_hook) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let fee_of = |x_fee: Option<u64>, y_fee: Option<u64>| {
+            move |hook: &Pubkey| Ok(if *hook == x_hook { x_fee } else { y_fee })
+        };
+        let both = fee_of(Some(40), Some(70));
+
+        // The higher fee applies whichever hook reports it
+        assert_eq!(select_dynamic_fee(Some(x_hook), Some(y_hook), None, false, both).unwrap(), Some(70));
+        assert_eq!(
+            select_dynamic_fee(Some(x_hook), Some(y_hook), None, false, fee_of(Some(70), Some(40))).unwrap(),
+            Some(70)
+        );
+
+        // With an approved hook only that leg's fee counts, strict mode rejects the other hook
+        assert_eq!(select_dynamic_fee(Some(x_hook), Some(y_hook), Some(x_hook), false, both).unwrap(), Some(40));
+        assert_eq!(
+            select_dynamic_fee(Some(x_hook), Some(y_hook), Some(x_hook), true, both).unwrap_err(),
+            error!(AmmError::UnauthorizedHookProgram)
+        );
+        assert_eq!(select_dynamic_fee(Some(x_hook), None, Some(x_hook), true, both).unwrap(), Some(40));
+
+        // A fee that can't be read for either leg falls back or fails in strict mode
+        let x_only = fee_of(Some(40), None);
+        assert_eq!(select_dynamic_fee(Some(x_hook), Some(y_hook), None, false, x_only).unwrap(), Some(40));
+        assert_eq!(
+            select_dynamic_fee(Some(x_hook), Some(y_hook), None, true, x_only).unwrap_err(),
+            error!(AmmError::FeeStatsNotFound)
+        );
+
+        // A hook whose fee CPI failed aborts the swap instead of falling back
+        let failing = |_: &Pubkey| err!(AmmError::HookExecutionFailed);
+        assert_eq!(
+            select_dynamic_fee(Some(x_hook), None, None, false, failing).unwrap_err(),
+            error!(AmmError::HookExecutionFailed)
+        );
+    }
+
+    #[test]
+    fn test_hook_fee_accounts() {
+        let hook_program_id = Pubkey::new_unique();
+        let fee_stats_key = Pubkey::find_program_address(&[b"fee_stats"], &hook_program_id).0;
+        let loader = Pubkey::new_unique();
+        let other = Pubkey::new_unique();
+        let (mut fee_stats_lamports, mut program_lamports) = (1, 1);
+        let mut fee_stats_data = vec![0u8; 8];
+        let mut program_data = vec![];
+
+        let fee_stats = AccountInfo::new(
+            &fee_stats_key, false, false, &mut fee_stats_lamports, &mut fee_stats_data,
+            &hook_program_id, false, 0,
+        );
+        let program = AccountInfo::new(
+            &hook_program_id, false, false, &mut program_lamports, &mut program_data,
+            &loader, true, 0,
+        );
+
+        let accounts = [program.clone(), fee_stats.clone()];
+        let found = hook_fee_accounts(&hook_program_id, &accounts).unwrap();
+        assert_eq!((found.0.key, found.1.key), (&fee_stats_key, &hook_program_id));
+
+        // Missing either account, or fee stats the hook doesn't own, fall back before any CPI
+        assert!(hook_fee_accounts(&hook_program_id, std::slice::from_ref(&fee_stats)).is_none());
+        assert!(hook_fee_accounts(&hook_program_id, std::slice::from_ref(&program)).is_none());
+        let mut foreign = fee_stats.clone();
+        foreign.owner = &other;
+        assert!(hook_fee_accounts(&hook_program_id, &[program.clone(), foreign]).is_none());
+
+        // Uninitialized fee stats or a program that isn't deployed too
+        let mut empty_data = vec![];
+        let mut empty_lamports = 1;
+        let empty = AccountInfo::new(
+            &fee_stats_key, false, false, &mut empty_lamports, &mut empty_data,
+            &hook_program_id, false, 0,
+        );
+        assert!(hook_fee_accounts(&hook_program_id, &[program.clone(), empty]).is_none());
+        let mut undeployed = program.clone();
+        undeployed.executable = false;
+        assert!(hook_fee_accounts(&hook_program_id, &[undeployed, fee_stats]).is_none());
+    }
+}
@@ -1,12 +1,25 @@
 use anchor_lang::prelude::*;
 use anchor_spl::{
-    token_interface::{Mint, TokenAccount, TokenInterface},
+    token_interface::{
+        spl_token_2022::{
+            extension::{transfer_fee::TransferFeeAmount, BaseStateWithExtensions, StateWithExtensions},
+            state::Account as TokenAccountState,
+        },
+        Mint, TokenAccount, TokenInterface,
+    },
     token_2022_extensions::transfer_fee::{
-        withdraw_withheld_tokens_from_accounts, WithdrawWithheldTokensFromAccounts,
+        withdraw_withheld_tokens_from_accounts, withdraw_withheld_tokens_from_mint,
+        WithdrawWithheldTokensFromAccounts, WithdrawWithheldTokensFromMint,
     },
 };
 
-use crate::{error::AmmError, state::Config};
+use crate::{
+    constants::MAX_FEE_SOURCES,
+    error::AmmError,
+    events::{ConfigChangeApplied, ConfigChangeQueued, FeesCollected, TradingFeeUpdated},
+    state::{Config, ConfigChange},
+    utils::validate_transfer_fee_config,
+};
 
 #[derive(Accounts)]
 pub struct Update<'info> {
@@ -22,14 +35,17 @@ pub struct Update<'info> {
 }
 
 /// Account structure for collecting transfer fees from Token-2022 mints 
+/// Fees go to the fee vault, or to `fee_destination` when the pool collects directly
 #[derive(Accounts)]
 pub struct CollectFees<'info> {
     #[account(mut)]
     pub authority: Signer<'info>,
 
     #[account(
+        mut,
         seeds = [b"config", config.seed.to_be_bytes().as_ref()],
         bump = config.config_bump,
+        constraint = config.authority.is_some() @ AmmError::NoAuthoritySet,
         constraint = config.authority == Some(authority.key()) @ AmmError::InvalidAuthority
     )]
     pub config: Account<'info, Config>,
@@ -38,19 +54,30 @@ pub struct CollectFees<'info> {
     #[account(mut)]
     pub mint: InterfaceAccount<'info, Mint>,
 
-    /// Destination account for collected fees
+    /// Destination account for collected fees, used when `config.direct_fee_collection` is set
     #[account(mut)]
-    pub fee_destination: InterfaceAccount<'info, TokenAccount>,
+    pub fee_destination: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    /// Program-owned account holding collected fees until `distribute_fees`
+    #[account(
+        init_if_needed,
+        payer = authority,
+        seeds = [b"fee_vault", config.key().as_ref(), mint.key().as_ref()],
+        bump,
+        token::mint = mint,
+        token::authority = config,
+        token::token_program = token_program
+    )]
+    pub fee_vault: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
 
     pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
     // remaining_accounts: accounts from which to withdraw fees
 }
 impl<'info> Update<'info> {
+    /// Not timelocked, locking only stops activity and must work at once during an incident
     pub fn lock(&mut self) -> Result<()> {
-        require!(
-            self.config.authority == Some(self.user.key()),
-            AmmError::InvalidAuthority
-        );
+        self.config.check_authority(&self.user.key())?;
 
         self.config.locked = true;
 
@@ -58,32 +85,217 @@ impl<'info> Update<'info> {
     }
 
     pub fn unlock(&mut self) -> Result<()> {
-        require!(
-            self.config.authority == Some(self.user.key()),
-            AmmError::InvalidAuthority
-        );
+        self.config.check_authority(&self.user.key())?;
 
         self.config.locked = false;
 
         Ok(())
     }
+
+    /// Update the protocol share of the fee and where it goes, subject to the timelock
+    pub fn update_protocol_fee(
+        &mut self,
+        protocol_fee_bps: u16,
+        protocol_fee_destination: Pubkey,
+    ) -> Result<()> {
+        self.queue_change(ConfigChange::ProtocolFee {
+            basis_points: protocol_fee_bps,
+            destination: protocol_fee_destination,
+        })
+    }
+
+    /// Turn the protocol fee on or off, subject to the timelock
+    pub fn set_fee_enabled(&mut self, fee_enabled: bool) -> Result<()> {
+        self.queue_change(ConfigChange::FeeEnabled { enabled: fee_enabled })
+    }
+
+    /// Not timelocked, emergency mode only stops activity and must work at once during an incident
+    pub fn set_emergency(&mut self, emergency: bool) -> Result<()> {
+        self.config.check_authority(&self.user.key())?;
+
+        self.config.emergency = emergency;
+
+        msg!("Emergency mode: {}", emergency);
+
+        Ok(())
+    }
+
+    /// Set the range a hook's dynamic fee is clamped into, subject to the timelock
+    /// The bounds must keep containing the trading fee, checked again when the change applies
+    /// Flag the pool's hooks as unsafe to call, or clear the flag
+    /// Not timelocked, it is an incident tool and only stops the pool from calling hooks
+    pub fn set_hook_unsafe(&mut self, hook_unsafe: bool) -> Result<()> {
+        self.config.check_authority(&self.user.key())?;
+
+        self.config.hook_unsafe = hook_unsafe;
+
+        msg!("Hooks flagged unsafe: {}", hook_unsafe);
+
+        Ok(())
+    }
+
+    /// Set the range a hook's dynamic fee is clamped into, subject to the timelock
+    pub fn set_dynamic_fee_bounds(&mut self, min_dynamic_fee_bps: u16, max_dynamic_fee_bps: u16) -> Result<()> {
+        self.queue_change(ConfigChange::DynamicFeeBounds {
+            min_bps: min_dynamic_fee_bps,
+            max_bps: max_dynamic_fee_bps,
+        })
+    }
+
+    /// Set who receives protocol LP for sqrt(k) growth and how much of the growth it takes,
+    /// subject to the timelock. Once applied, growth is measured afresh from the next
+    /// `mint_protocol_lp`, nothing accrued before is minted
+    pub fn set_protocol_lp(&mut self, destination: Pubkey, share_bps: u16) -> Result<()> {
+        self.queue_change(ConfigChange::ProtocolLp { destination, share_bps })
+    }
+
+    /// Not timelocked, the flash fee is paid by borrowers and never touches LP funds
+    pub fn update_flash_fee(&mut self, flash_fee_bps: u16) -> Result<()> {
+        self.config.check_authority(&self.user.key())?;
+        require!(flash_fee_bps <= 10_000, AmmError::InvalidFee);
+
+        self.config.flash_fee_bps = flash_fee_bps;
+
+        msg!("Updated flash loan fee: {} basis points", flash_fee_bps);
+
+        Ok(())
+    }
+
+    /// Not timelocked, the floor can only make swaps fail, never move LP funds
+    pub fn update_min_reserve(&mut self, min_reserve: u64) -> Result<()> {
+        self.config.check_authority(&self.user.key())?;
+
+        self.config.min_reserve = min_reserve;
+
+        msg!("Updated minimum reserve: {}", min_reserve);
+
+        Ok(())
+    }
+
+    /// Send collected fees straight to `fee_destination`, subject to the timelock
+    pub fn update_direct_fee_collection(&mut self, direct_fee_collection: bool) -> Result<()> {
+        self.queue_change(ConfigChange::DirectFeeCollection {
+            enabled: direct_fee_collection,
+        })
+    }
+
+    /// Require the hook's fee stats on swaps, subject to the timelock
+    pub fn update_strict_hooks(&mut self, strict_hooks: bool) -> Result<()> {
+        self.queue_change(ConfigChange::StrictHooks { enabled: strict_hooks })
+    }
+
+    /// Update transfer fee configuration (if the mint supports it), subject to the timelock
+    pub fn update_transfer_fee_config(&mut self, new_fee_basis_points: u16, new_max_fee: u64) -> Result<()> {
+        validate_transfer_fee_config(new_fee_basis_points, new_max_fee)?;
+
+        self.queue_change(ConfigChange::TransferFeeConfig {
+            basis_points: new_fee_basis_points,
+            max_fee: new_max_fee,
+        })
+    }
+
+    /// Update the fee destination account, subject to the timelock
+    pub fn update_fee_destination(&mut self, new_destination: Pubkey) -> Result<()> {
+        self.queue_change(ConfigChange::FeeDestination {
+            destination: new_destination,
+        })
+    }
+
+    /// Update the default hook program, subject to the timelock
+    pub fn update_hook_program(&mut self, new_hook_program: Option<Pubkey>) -> Result<()> {
+        self.queue_change(ConfigChange::HookProgram {
+            program: new_hook_program,
+        })
+    }
+
+    /// Update the base trading fee, subject to the timelock so LPs see it coming
+    /// Same bound as initialize, and it must stay within the dynamic fee bounds and above the
+    /// protocol share, checked again when the change applies
+    pub fn update_trading_fee(&mut self, new_fee_bps: u16) -> Result<()> {
+        self.queue_change(ConfigChange::TradingFee { fee: new_fee_bps })
+    }
+
+    /// Apply the pending change once its timelock has elapsed
+    /// Permissionless, the authority already approved the change by queueing it
+    pub fn apply_pending_change(&mut self) -> Result<()> {
+        let old_fee = self.config.fee;
+        let change = self
+            .config
+            .apply_pending_change(Clock::get()?.unix_timestamp)?;
+
+        self.emit_applied(change, old_fee);
+
+        Ok(())
+    }
+
+    fn queue_change(&mut self, change: ConfigChange) -> Result<()> {
+        self.config.check_authority(&self.user.key())?;
+
+        let old_fee = self.config.fee;
+        match self
+            .config
+            .queue_change(change.clone(), Clock::get()?.unix_timestamp)?
+        {
+            Some(pending) => {
+                msg!("Queued config change until {}: {:?}", pending.effective_at, change);
+
+                emit!(ConfigChangeQueued {
+                    config: self.config.key(),
+                    change,
+                    effective_at: pending.effective_at,
+                });
+            }
+            None => self.emit_applied(change, old_fee),
+        }
+
+        Ok(())
+    }
+
+    fn emit_applied(&self, change: ConfigChange, old_fee: u16) {
+        msg!("Applied config change: {:?}", change);
+
+        if let ConfigChange::TradingFee { fee } = change {
+            emit!(TradingFeeUpdated {
+                config: self.config.key(),
+                old_fee,
+                new_fee: fee,
+            });
+        }
+
+        emit!(ConfigChangeApplied {
+            config: self.config.key(),
+            change,
+        });
+    }
 }
 
 impl<'info> CollectFees<'info> {
     /// Collect withheld transfer fees from specified token accounts
-    /// This function can collect fees from multiple accounts in a single transaction
-    pub fn collect_fees(&mut self, remaining_accounts: &[AccountInfo<'info>]) -> Result<()> {
+    /// Processes at most `max_accounts` of them and reports how many are left, so large
+    /// sweeps can be paginated across transactions. Accounts with nothing withheld are
+    /// skipped without using up the batch.
+    pub fn collect_fees(&mut self, max_accounts: u8, remaining_accounts: &[AccountInfo<'info>]) -> Result<u64> {
         require!(
             !remaining_accounts.is_empty(),
             AmmError::InvalidAmount
         );
 
+        // Only the first batch is collected, the caller resubmits the rest
+        let limit = fee_batch_len(max_accounts, remaining_accounts.len());
+        let (batch, skipped, rest) = select_fee_sources(&self.mint.key(), remaining_accounts, limit)?;
+        check_fee_sources(&self.mint.key(), &batch)?;
+
+        self.config.check_version()?;
+
         // Verify the config has fee collection authority
         require!(
             self.config.fee_withdraw_authority == self.config.key(),
             AmmError::InvalidAuthority
         );
 
+        let destination = self.collection_target()?;
+        let before = destination.amount;
+
         // Set up the CPI context with signer (config PDA)
         let seeds = &[
             b"config",
@@ -93,7 +305,7 @@ impl<'info> CollectFees<'info> {
         let signer_seeds = &[&seeds[..]];
 
         let cpi_accounts = WithdrawWithheldTokensFromAccounts {
-            destination: self.fee_destination.to_account_info(),
+            destination: destination.to_account_info(),
             authority: self.config.to_account_info(),
             mint: self.mint.to_account_info(),
             token_program_id: self.token_program.to_account_info(),
@@ -103,50 +315,334 @@ impl<'info> CollectFees<'info> {
             self.token_program.to_account_info(),
             cpi_accounts,
             signer_seeds,
-        ).with_remaining_accounts(remaining_accounts.to_vec());
+        ).with_remaining_accounts(batch.clone());
 
-        // Execute the fee collection
-        let sources = remaining_accounts.to_vec();
-        withdraw_withheld_tokens_from_accounts(cpi_ctx, sources)?;
+        // Execute the fee collection, unless every scanned account was empty
+        if !batch.is_empty() {
+            withdraw_withheld_tokens_from_accounts(cpi_ctx, batch.clone())?;
+        }
 
-        msg!("Successfully collected transfer fees from {} accounts", remaining_accounts.len());
+        let collected = self.record_collection(destination, before)?;
+
+        emit!(FeesCollected {
+            config: self.config.key(),
+            mint: self.mint.key(),
+            amount: collected,
+            accounts_processed: batch.len() as u32,
+            accounts_skipped: skipped as u32,
+            accounts_remaining: rest.len() as u32,
+            sources: batch.iter().map(|source| source.key()).collect(),
+        });
+
+        msg!(
+            "Successfully collected {} in transfer fees from {} accounts, {} empty skipped, {} left",
+            collected,
+            batch.len(),
+            skipped,
+            rest.len()
+        );
         
-        Ok(())
+        Ok(collected)
     }
 
-    /// Update transfer fee configuration (if the mint supports it)
-    pub fn update_transfer_fee_config(&mut self, new_fee_basis_points: u16, new_max_fee: u64) -> Result<()> {
-        require!(new_fee_basis_points <= 10000, AmmError::InvalidFee);
-        
-        // Update the config's default values
-        self.config.default_transfer_fee_basis_points = new_fee_basis_points;
-        self.config.default_transfer_fee_max = new_max_fee;
+    /// Collect withheld transfer fees that have been harvested to the mint itself
+    /// Anyone can harvest account fees to the mint, after which only the withdraw
+    /// authority can sweep them from there
+    pub fn collect_fees_from_mint(&mut self) -> Result<u64> {
+        self.config.check_version()?;
 
-        msg!("Updated default transfer fee config: {} basis points, max {}", 
-             new_fee_basis_points, new_max_fee);
+        // Verify the config has fee collection authority
+        require!(
+            self.config.fee_withdraw_authority == self.config.key(),
+            AmmError::InvalidAuthority
+        );
 
-        Ok(())
+        let destination = self.collection_target()?;
+        let before = destination.amount;
+
+        let seeds = &[
+            b"config",
+            &self.config.seed.to_be_bytes()[..],
+            &[self.config.config_bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let cpi_accounts = WithdrawWithheldTokensFromMint {
+            token_program_id: self.token_program.to_account_info(),
+            mint: self.mint.to_account_info(),
+            destination: destination.to_account_info(),
+            authority: self.config.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        );
+
+        withdraw_withheld_tokens_from_mint(cpi_ctx)?;
+
+        let collected = self.record_collection(destination, before)?;
+
+        emit!(FeesCollected {
+            config: self.config.key(),
+            mint: self.mint.key(),
+            amount: collected,
+            accounts_processed: 0,
+            accounts_skipped: 0,
+            accounts_remaining: 0,
+            sources: Vec::new(),
+        });
+
+        msg!("Successfully collected {} in withheld transfer fees from mint {}", collected, self.mint.key());
+
+        Ok(collected)
     }
 
-    /// Update the fee destination account
-    pub fn update_fee_destination(&mut self, new_destination: Pubkey) -> Result<()> {
-        self.config.fee_destination = new_destination;
-        
-        msg!("Updated fee destination to: {}", new_destination);
-        
-        Ok(())
+    /// Account the withheld fees are sent to
+    fn collection_target(&self) -> Result<Box<InterfaceAccount<'info, TokenAccount>>> {
+        let target = if self.config.direct_fee_collection {
+            self.fee_destination.as_ref().ok_or(AmmError::InvalidTokenAccount)?
+        } else {
+            self.fee_vault.as_ref().ok_or(AmmError::MissingFeeVault)?
+        };
+
+        // Fail clearly here instead of with an opaque token program error
+        check_destination_mint(&target.mint, &self.mint.key())?;
+
+        Ok(target.clone())
     }
 
-    /// Update the default hook program
-    pub fn update_hook_program(&mut self, new_hook_program: Option<Pubkey>) -> Result<()> {
-        self.config.default_hook_program = new_hook_program;
-        
-        if let Some(program_id) = new_hook_program {
-            msg!("Updated default hook program to: {}", program_id);
+    /// Add what reached `destination` since `before` to the pool's collected totals
+    fn record_collection(
+        &mut self,
+        mut destination: Box<InterfaceAccount<'info, TokenAccount>>,
+        before: u64,
+    ) -> Result<u64> {
+        destination.reload()?;
+        let collected = destination
+            .amount
+            .checked_sub(before)
+            .ok_or(AmmError::Underflow)?;
+
+        self.config.record_collected_fees(&self.mint.key(), collected)?;
+        Ok(collected)
+    }
+}
+
+/// Withheld fees of a mint can only be collected into an account of that mint
+fn check_destination_mint(destination_mint: &Pubkey, mint: &Pubkey) -> Result<()> {
+    require!(destination_mint == mint, AmmError::InvalidToken);
+    Ok(())
+}
+
+/// Number of fee sources one `collect_fees` call processes
+/// `max_accounts` of 0 means the cap, anything above it is clamped to `MAX_FEE_SOURCES`
+fn fee_batch_len(max_accounts: u8, available: usize) -> usize {
+    let limit = match max_accounts {
+        0 => MAX_FEE_SOURCES,
+        n => n.min(MAX_FEE_SOURCES),
+    };
+    available.min(limit as usize)
+}
+
+/// Scan `accounts` in order for up to `limit` fee sources, returning them with the number
+/// of empty accounts skipped on the way and the accounts left unscanned
+/// Accounts that aren't token accounts of `mint` are kept for `check_fee_sources` to reject
+fn select_fee_sources<'a, 'info>(
+    mint: &Pubkey,
+    accounts: &'a [AccountInfo<'info>],
+    limit: usize,
+) -> Result<(Vec<AccountInfo<'info>>, usize, &'a [AccountInfo<'info>])> {
+    let mut batch = Vec::with_capacity(limit);
+    let mut skipped = 0;
+    let mut scanned = 0;
+
+    for source in accounts {
+        if batch.len() == limit {
+            break;
+        }
+        scanned += 1;
+
+        if withheld_fees(mint, source)? == Some(0) {
+            skipped += 1;
         } else {
-            msg!("Removed default hook program");
+            batch.push(source.clone());
         }
-        
-        Ok(())
+    }
+
+    Ok((batch, skipped, &accounts[scanned..]))
+}
+
+/// Transfer fees withheld in `source`, None when it isn't a Token-2022 account of `mint`
+fn withheld_fees(mint: &Pubkey, source: &AccountInfo) -> Result<Option<u64>> {
+    if source.owner != &anchor_spl::token_interface::spl_token_2022::ID {
+        return Ok(None);
+    }
+
+    let data = source.try_borrow_data()?;
+    let Ok(account) = StateWithExtensions::<TokenAccountState>::unpack(&data) else {
+        return Ok(None);
+    };
+    if account.base.mint != *mint {
+        return Ok(None);
+    }
+
+    Ok(Some(
+        account
+            .get_extension::<TransferFeeAmount>()
+            .map(|fees| u64::from(fees.withheld_amount))
+            .unwrap_or(0),
+    ))
+}
+
+/// Require distinct Token-2022 accounts of `mint`, at most `MAX_FEE_SOURCES` of them
+/// A bad source would otherwise fail the withdraw CPI partway with an opaque error
+fn check_fee_sources(mint: &Pubkey, sources: &[AccountInfo]) -> Result<()> {
+    require!(
+        sources.len() <= MAX_FEE_SOURCES as usize,
+        AmmError::TooManyFeeSources
+    );
+
+    for (index, source) in sources.iter().enumerate() {
+        if sources[..index].iter().any(|other| other.key == source.key) {
+            msg!("Fee source {} ({}) is a duplicate", index, source.key);
+            return err!(AmmError::DuplicateFeeSource);
+        }
+
+        let is_mint_account = source.owner == &anchor_spl::token_interface::spl_token_2022::ID
+            && StateWithExtensions::<TokenAccountState>::unpack(&source.try_borrow_data()?)
+                .is_ok_and(|account| account.base.mint == *mint);
+        if !is_mint_account {
+            msg!("Fee source {} ({}) is not a token account of mint {}", index, source.key, mint);
+            return err!(AmmError::InvalidFeeSource);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::solana_program::program_pack::Pack;
+    use anchor_spl::token_interface::spl_token_2022::state::AccountState;
+
+    fn token_account_data(mint: &Pubkey) -> Vec<u8> {
+        let mut data = vec![0u8; TokenAccountState::LEN];
+        TokenAccountState {
+            mint: *mint,
+            owner: Pubkey::new_unique(),
+            state: AccountState::Initialized,
+            ..Default::default()
+        }
+        .pack_into_slice(&mut data);
+        data
+    }
+
+    #[test]
+    fn test_fee_batch_len() {
+        assert_eq!(fee_batch_len(0, 5), 5);
+        assert_eq!(fee_batch_len(0, 40), MAX_FEE_SOURCES as usize);
+        assert_eq!(fee_batch_len(8, 40), 8);
+        assert_eq!(fee_batch_len(u8::MAX, 40), MAX_FEE_SOURCES as usize);
+    }
+
+    #[test]
+    fn test_check_fee_sources() {
+        let mint = Pubkey::new_unique();
+        let token_program = anchor_spl::token_interface::spl_token_2022::ID;
+        let keys = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let (mut lamports_a, mut lamports_b, mut lamports_c) = (0, 0, 0);
+        let mut data_a = token_account_data(&mint);
+        let mut data_b = token_account_data(&Pubkey::new_unique());
+        let mut data_c = token_account_data(&mint);
+
+        let source = AccountInfo::new(&keys[0], false, true, &mut lamports_a, &mut data_a, &token_program, false, 0);
+        let other_mint = AccountInfo::new(&keys[1], false, true, &mut lamports_b, &mut data_b, &token_program, false, 0);
+        let duplicate = AccountInfo::new(&keys[0], false, true, &mut lamports_c, &mut data_c, &token_program, false, 0);
+
+        assert!(check_fee_sources(&mint, std::slice::from_ref(&source)).is_ok());
+        assert_eq!(
+            check_fee_sources(&mint, &[source.clone(), other_mint]).unwrap_err(),
+            error!(AmmError::InvalidFeeSource)
+        );
+        assert_eq!(
+            check_fee_sources(&mint, &[source.clone(), duplicate]).unwrap_err(),
+            error!(AmmError::DuplicateFeeSource)
+        );
+        assert_eq!(
+            check_fee_sources(&mint, &vec![source; MAX_FEE_SOURCES as usize + 1]).unwrap_err(),
+            error!(AmmError::TooManyFeeSources)
+        );
+    }
+
+    fn withheld_account_data(mint: &Pubkey, withheld: u64) -> Vec<u8> {
+        use anchor_spl::token_interface::spl_token_2022::extension::{
+            BaseStateWithExtensionsMut, ExtensionType, StateWithExtensionsMut,
+        };
+
+        let len = ExtensionType::try_calculate_account_len::<TokenAccountState>(&[ExtensionType::TransferFeeAmount])
+            .unwrap();
+        let mut data = vec![0u8; len];
+        let mut state = StateWithExtensionsMut::<TokenAccountState>::unpack_uninitialized(&mut data).unwrap();
+        state.init_extension::<TransferFeeAmount>(true).unwrap().withheld_amount = withheld.into();
+        state.base = TokenAccountState {
+            mint: *mint,
+            owner: Pubkey::new_unique(),
+            state: AccountState::Initialized,
+            ..Default::default()
+        };
+        state.pack_base();
+        state.init_account_type().unwrap();
+        data
+    }
+
+    #[test]
+    fn test_select_fee_sources_skips_empty_accounts() {
+        let mint = Pubkey::new_unique();
+        let token_program = anchor_spl::token_interface::spl_token_2022::ID;
+        let withheld = [0, 5, 0, 0, 7, 9, 0, 3];
+        let keys: Vec<Pubkey> = withheld.iter().map(|_| Pubkey::new_unique()).collect();
+        let mut lamports = vec![0u64; withheld.len()];
+        let mut data: Vec<Vec<u8>> = withheld.iter().map(|amount| withheld_account_data(&mint, *amount)).collect();
+        let accounts: Vec<AccountInfo> = keys
+            .iter()
+            .zip(lamports.iter_mut())
+            .zip(data.iter_mut())
+            .map(|((key, lamports), data)| AccountInfo::new(key, false, true, lamports, data, &token_program, false, 0))
+            .collect();
+
+        // Two sources fill the batch, the empty accounts before them don't count
+        let (batch, skipped, rest) = select_fee_sources(&mint, &accounts, 2).unwrap();
+        assert_eq!(batch.iter().map(|a| *a.key).collect::<Vec<_>>(), vec![keys[1], keys[4]]);
+        assert_eq!(skipped, 3);
+        assert_eq!(rest.len(), 3);
+
+        // The next page picks up where the last one stopped
+        let (batch, skipped, rest) = select_fee_sources(&mint, rest, 2).unwrap();
+        assert_eq!(batch.iter().map(|a| *a.key).collect::<Vec<_>>(), vec![keys[5], keys[7]]);
+        assert_eq!((skipped, rest.len()), (1, 0));
+
+        // Only empty accounts leave nothing to collect
+        let (batch, skipped, rest) = select_fee_sources(&mint, &accounts[2..4], 20).unwrap();
+        assert!(batch.is_empty());
+        assert_eq!((skipped, rest.len()), (2, 0));
+
+        // An account of another mint isn't skipped, so validation still rejects it
+        let other = withheld_fees(&Pubkey::new_unique(), &accounts[0]).unwrap();
+        assert_eq!(other, None);
+        assert_eq!(withheld_fees(&mint, &accounts[4]).unwrap(), Some(7));
+    }
+
+    #[test]
+    fn test_rejects_mismatched_fee_destination() {
+        let mint = Pubkey::new_unique();
+
+        assert!(check_destination_mint(&mint, &mint).is_ok());
+        assert_eq!(
+            check_destination_mint(&Pubkey::new_unique(), &mint).unwrap_err(),
+            error!(AmmError::InvalidToken)
+        );
     }
 }
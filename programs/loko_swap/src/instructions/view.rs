@@ -0,0 +1,290 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount};
+
+use crate::{
+    error::AmmError,
+    events::{HealthReport, LpValue, PoolInfo, SpotPrice, SwapQuote},
+    instructions::swap::{output_amounts, pool_swap_fee, price_swap},
+    state::Config,
+    utils::{
+        math::{mul_div_floor, spot_price_q64, withdraw_amount_for_lp},
+        token_utils::TokenExtensions,
+    },
+};
+
+/// Read-only accounts describing a pool
+#[derive(Accounts)]
+pub struct PoolInfoView<'info> {
+    #[account(
+        seeds = [b"config", config.seed.to_be_bytes().as_ref()],
+        bump = config.config_bump
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        seeds = [b"lp", config.key().as_ref()],
+        bump = config.lp_bump
+    )]
+    pub mint_lp: InterfaceAccount<'info, Mint>,
+
+    #[account(token::mint = config.mint_x, token::authority = config)]
+    pub vault_x: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(token::mint = config.mint_y, token::authority = config)]
+    pub vault_y: InterfaceAccount<'info, TokenAccount>,
+}
+
+impl<'info> PoolInfoView<'info> {
+    pub fn pool_info(&self) -> Result<PoolInfo> {
+        let info = PoolInfo {
+            config: self.config.key(),
+            reserve_x: self.config.reserve_x,
+            reserve_y: self.config.reserve_y,
+            lp_supply: self.mint_lp.supply,
+            fee: self.config.fee,
+            protocol_fee_bps: self.config.protocol_fee_bps,
+            fee_enabled: self.config.fee_enabled,
+            locked: self.config.locked,
+            supports_transfer_fees: self.config.supports_transfer_fees,
+            supports_transfer_hooks: self.config.supports_transfer_hooks,
+            supports_metadata: self.config.supports_metadata,
+            supports_interest_bearing: self.config.supports_interest_bearing,
+            cumulative_volume_x: self.config.cumulative_volume_x,
+            cumulative_volume_y: self.config.cumulative_volume_y,
+            swap_count: self.config.swap_count,
+        };
+
+        // Tokens sent directly to the vaults aren't part of the reserves until `sync`
+        msg!(
+            "Vault balances: x={}, y={}",
+            self.vault_x.amount,
+            self.vault_y.amount
+        );
+
+        emit!(info.clone());
+
+        Ok(info)
+    }
+
+    pub fn check_invariants(&self) -> Result<HealthReport> {
+        let report = health_report(
+            self.config.key(),
+            (self.config.reserve_x, self.config.reserve_y),
+            (self.vault_x.amount, self.vault_y.amount),
+        );
+
+        msg!(
+            "Health: drift_x={}, drift_y={}, k={}, healthy={}",
+            report.drift_x,
+            report.drift_y,
+            report.k,
+            report.healthy
+        );
+
+        emit!(report.clone());
+
+        Ok(report)
+    }
+}
+
+/// Drift of the vaults from the tracked reserves and the `k` the curve prices against
+fn health_report(config: Pubkey, (reserve_x, reserve_y): (u64, u64), (vault_x, vault_y): (u64, u64)) -> HealthReport {
+    let drift_x = vault_x as i128 - reserve_x as i128;
+    let drift_y = vault_y as i128 - reserve_y as i128;
+
+    HealthReport {
+        config,
+        reserve_x,
+        reserve_y,
+        vault_x,
+        vault_y,
+        drift_x,
+        drift_y,
+        k: reserve_x as u128 * reserve_y as u128,
+        healthy: drift_x >= 0 && drift_y >= 0,
+    }
+}
+
+/// Read-only accounts needed to price a pool
+#[derive(Accounts)]
+pub struct SpotPriceView<'info> {
+    #[account(
+        seeds = [b"config", config.seed.to_be_bytes().as_ref()],
+        bump = config.config_bump
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(address = config.mint_x @ AmmError::InvalidToken)]
+    pub mint_x: InterfaceAccount<'info, Mint>,
+
+    #[account(address = config.mint_y @ AmmError::InvalidToken)]
+    pub mint_y: InterfaceAccount<'info, Mint>,
+}
+
+impl<'info> SpotPriceView<'info> {
+    pub fn spot_price(&self) -> Result<SpotPrice> {
+        let price = SpotPrice {
+            config: self.config.key(),
+            price_x_in_y_q64: spot_price_q64(
+                self.config.reserve_x,
+                self.config.reserve_y,
+                self.mint_x.decimals,
+                self.mint_y.decimals,
+            )?,
+            decimals_x: self.mint_x.decimals,
+            decimals_y: self.mint_y.decimals,
+        };
+
+        emit!(price.clone());
+
+        Ok(price)
+    }
+}
+
+/// Read-only accounts needed to value LP tokens
+#[derive(Accounts)]
+pub struct LpValueView<'info> {
+    #[account(
+        seeds = [b"config", config.seed.to_be_bytes().as_ref()],
+        bump = config.config_bump
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        seeds = [b"lp", config.key().as_ref()],
+        bump = config.lp_bump
+    )]
+    pub mint_lp: InterfaceAccount<'info, Mint>,
+}
+
+impl<'info> LpValueView<'info> {
+    pub fn lp_to_tokens(&self, lp_amount: u64) -> Result<LpValue> {
+        let supply = self.mint_lp.supply;
+
+        // Same rounding as a withdrawal, so the value is never more than burning would pay
+        let value = LpValue {
+            config: self.config.key(),
+            lp_amount,
+            amount_x: withdraw_amount_for_lp(self.config.reserve_x, supply, lp_amount)?,
+            amount_y: withdraw_amount_for_lp(self.config.reserve_y, supply, lp_amount)?,
+        };
+
+        emit!(value.clone());
+
+        Ok(value)
+    }
+}
+
+/// Read-only accounts needed to quote a swap
+#[derive(Accounts)]
+pub struct SwapQuoteView<'info> {
+    #[account(
+        seeds = [b"config", config.seed.to_be_bytes().as_ref()],
+        bump = config.config_bump
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(address = config.mint_x @ AmmError::InvalidToken)]
+    pub mint_x: InterfaceAccount<'info, Mint>,
+
+    #[account(address = config.mint_y @ AmmError::InvalidToken)]
+    pub mint_y: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        seeds = [b"lp", config.key().as_ref()],
+        bump = config.lp_bump
+    )]
+    pub mint_lp: InterfaceAccount<'info, Mint>,
+
+    #[account(token::mint = config.mint_x, token::authority = config)]
+    pub vault_x: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(token::mint = config.mint_y, token::authority = config)]
+    pub vault_y: InterfaceAccount<'info, TokenAccount>,
+    // remaining_accounts: hook program and fee stats PDA, for pools with a dynamic fee
+}
+
+impl<'info> SwapQuoteView<'info> {
+    /// Price a swap of `amount` with the same math as `swap`, without moving tokens
+    /// Fails where the swap would, except for slippage and price impact bounds
+    pub fn quote_swap(
+        &self,
+        is_x: bool,
+        amount: u64,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<SwapQuote> {
+        self.config.check_version()?;
+
+        let x_extensions = TokenExtensions::new(&self.mint_x.to_account_info())?;
+        let y_extensions = TokenExtensions::new(&self.mint_y.to_account_info())?;
+        let (input_ext, output_ext, output_vault) = if is_x {
+            (&x_extensions, &y_extensions, &self.vault_y)
+        } else {
+            (&y_extensions, &x_extensions, &self.vault_x)
+        };
+
+        let transfer_fee_in = input_ext.calculate_fee(amount);
+        let net_amount_in = amount.saturating_sub(transfer_fee_in);
+        require!(net_amount_in > 0, AmmError::InvalidAmount);
+        require!(
+            self.config.reserve_x > 0 && self.config.reserve_y > 0,
+            AmmError::NoLiquidityInPool
+        );
+
+        let fee_basis_points = pool_swap_fee(&self.config, &x_extensions, &y_extensions, remaining_accounts)?;
+        let pricing = price_swap(
+            &self.config,
+            is_x,
+            net_amount_in,
+            self.mint_lp.supply,
+            fee_basis_points,
+            0,
+        )?;
+        let (gross_output, net_output) =
+            output_amounts(pricing.amount_out, output_ext, self.config.pass_output_fee);
+        require!(net_output > 0, AmmError::InvalidAmount);
+
+        require!(gross_output <= output_vault.amount, AmmError::InsufficientFunds);
+        self.config.check_min_reserve(output_vault.amount - gross_output)?;
+
+        let quote = SwapQuote {
+            config: self.config.key(),
+            is_x,
+            amount_in: amount,
+            amount_out: net_output,
+            fee_basis_points,
+            fee: mul_div_floor(net_amount_in, fee_basis_points as u64, 10_000)?,
+            transfer_fee_in,
+        };
+
+        emit!(quote.clone());
+
+        Ok(quote)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_health_report() {
+        let config = Pubkey::new_unique();
+
+        // Donated tokens show up as positive drift on a healthy pool
+        let report = health_report(config, (1_000, 4_000), (1_250, 4_000));
+        assert_eq!((report.drift_x, report.drift_y), (250, 0));
+        assert_eq!(report.k, 4_000_000);
+        assert!(report.healthy);
+
+        // A vault below its tracked reserve is unhealthy
+        let report = health_report(config, (1_000, 4_000), (1_000, 3_999));
+        assert_eq!(report.drift_y, -1);
+        assert!(!report.healthy);
+
+        // Full-range balances don't overflow
+        let report = health_report(config, (u64::MAX, u64::MAX), (0, u64::MAX));
+        assert_eq!(report.drift_x, -(u64::MAX as i128));
+        assert_eq!(report.k, u64::MAX as u128 * u64::MAX as u128);
+    }
+}
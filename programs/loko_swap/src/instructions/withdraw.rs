@@ -2,14 +2,20 @@ use anchor_lang::prelude::*;
 use anchor_spl::{
     associated_token::AssociatedToken,
     token_interface::{
-        burn, transfer_checked, Burn, Mint, TokenAccount, TokenInterface, TransferChecked,
-        transfer_checked_with_fee, TransferCheckedWithFee,
+        burn, Burn, Mint, TokenAccount, TokenInterface,
     },
 };
 use crate::{
+    constants::MIN_LP_SUPPLY,
     error::AmmError, 
     state::Config,
-    utils::token_utils::{TokenExtensions, invoke_transfer_checked_with_hooks},
+    utils::{
+        math::{mul_div_ceil, withdraw_amount_for_lp},
+        native_sol::unwrap_sol,
+        token_utils::{
+            check_hook_account_budget, check_pool_vaults, check_vaults_not_frozen, TokenExtensions, transfer_with_extensions,
+        },
+    },
 };
 use constant_product_curve::ConstantProduct;
 
@@ -22,7 +28,8 @@ pub struct Withdraw<'info> {
     pub mint_y: InterfaceAccount<'info, Mint>,
 
     #[account(
-        mut,
+        init_if_needed,
+        payer = user,
         associated_token::mint = mint_x,
         associated_token::authority = user,
         associated_token::token_program = token_program
@@ -30,7 +37,8 @@ pub struct Withdraw<'info> {
     pub user_x: Box<InterfaceAccount<'info, TokenAccount>>,
 
     #[account(
-        mut,
+        init_if_needed,
+        payer = user,
         associated_token::mint = mint_y,
         associated_token::authority = user,
         associated_token::token_program = token_program
@@ -54,8 +62,9 @@ pub struct Withdraw<'info> {
     pub vault_y: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
+        mut,
         seeds = [b"config", config.seed.to_be_bytes().as_ref()],
-        bump = config.config_bump,
+        bump = config.config_bump
     )]
     pub config: Account<'info, Config>,
 
@@ -85,36 +94,65 @@ impl<'info> Withdraw<'info> {
         amount: u64,
         min_x: u64,
         min_y: u64,
+        native_sol: bool,
         _remaining_accounts: &[AccountInfo<'info>],
     ) -> Result<()> {
+        compute_trace!("withdraw: start");
+        self.config.check_version()?;
+        require!(!self.config.borrowing, AmmError::FlashLoanActive);
+
+        // Persist the guard so a nested call into this pool fails on entry
+        self.config.enter()?;
+        self.config.exit(&crate::ID)?;
+
+        // Oracle sees the reserves as they were before this instruction
+        self.config.update_oracle(Clock::get()?.unix_timestamp);
+
         require!(amount > 0, AmmError::InvalidAmount);
         require!(self.user_lp.amount >= amount, AmmError::InsufficientFunds);
+        check_remaining_lp_supply(self.mint_lp.supply, amount)?;
         
         // Manual validation replacing has_one constraints
         require!(self.config.mint_x == self.mint_x.key(), AmmError::InvalidToken);
         require!(self.config.mint_y == self.mint_y.key(), AmmError::InvalidToken);
+        check_pool_vaults(
+            &self.config.key(),
+            &self.config.mint_x,
+            &self.config.mint_y,
+            &self.vault_x,
+            &self.vault_y,
+        )?;
+        check_vaults_not_frozen(&self.vault_x, &self.vault_y)?;
+        check_hook_account_budget(
+            &[self.mint_x.to_account_info(), self.mint_y.to_account_info()],
+            _remaining_accounts,
+        )?;
         
 
         // Calculate base withdrawal amounts
         let amounts = ConstantProduct::xy_withdraw_amounts_from_l(
-            self.vault_x.amount,
-            self.vault_y.amount,
+            self.config.reserve_x,
+            self.config.reserve_y,
             self.mint_lp.supply,
             amount,
-            6,
+            self.mint_lp.decimals as u32,
         )
         .map_err(|_| AmmError::MathOverflow)?;
 
+        // Never pay out more than the exact share, whichever way the curve rounded
+        let out_x = amounts.x.min(withdraw_amount_for_lp(self.config.reserve_x, self.mint_lp.supply, amount)?);
+        let out_y = amounts.y.min(withdraw_amount_for_lp(self.config.reserve_y, self.mint_lp.supply, amount)?);
+
         // Calculate transfer fees that will be deducted from withdrawn amounts (scoped)
         let (x_transfer_fee, y_transfer_fee) = {
             let x_ext = TokenExtensions::new(&self.mint_x.to_account_info())?;
             let y_ext = TokenExtensions::new(&self.mint_y.to_account_info())?;
-            (x_ext.calculate_fee(amounts.x), y_ext.calculate_fee(amounts.y))
+            (x_ext.calculate_fee(out_x), y_ext.calculate_fee(out_y))
         };
 
         // Net amounts user will actually receive (after fees)
-        let net_x = amounts.x.saturating_sub(x_transfer_fee);
-        let net_y = amounts.y.saturating_sub(y_transfer_fee);
+        let net_x = out_x.saturating_sub(x_transfer_fee);
+        let net_y = out_y.saturating_sub(y_transfer_fee);
 
         // Check slippage on net amounts (what user actually receives)
         require!(
@@ -122,18 +160,171 @@ impl<'info> Withdraw<'info> {
             AmmError::SlippageExceeded
         );
 
+        self.pay_out(amount, out_x, out_y, native_sol, _remaining_accounts)?;
+
+        compute_trace!("withdraw: end");
+
+        Ok(())
+    }
+
+    /// Withdraw at least `desired_x` and `desired_y` after transfer fees, burning at most `max_lp_in`
+    /// LP burned is what the binding side needs, the other side pays out its full share of it
+    pub fn withdraw_exact_tokens(
+        &mut self,
+        desired_x: u64,
+        desired_y: u64,
+        max_lp_in: u64,
+        native_sol: bool,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        self.config.check_version()?;
+        require!(!self.config.borrowing, AmmError::FlashLoanActive);
+
+        // Persist the guard so a nested call into this pool fails on entry
+        self.config.enter()?;
+        self.config.exit(&crate::ID)?;
+
+        // Oracle sees the reserves as they were before this instruction
+        self.config.update_oracle(Clock::get()?.unix_timestamp);
+
+        require!(desired_x > 0 || desired_y > 0, AmmError::InvalidAmount);
+
+        require!(self.config.mint_x == self.mint_x.key(), AmmError::InvalidToken);
+        require!(self.config.mint_y == self.mint_y.key(), AmmError::InvalidToken);
+        check_pool_vaults(
+            &self.config.key(),
+            &self.config.mint_x,
+            &self.config.mint_y,
+            &self.vault_x,
+            &self.vault_y,
+        )?;
+        check_vaults_not_frozen(&self.vault_x, &self.vault_y)?;
+        check_hook_account_budget(
+            &[self.mint_x.to_account_info(), self.mint_y.to_account_info()],
+            remaining_accounts,
+        )?;
+
+        let x_ext = TokenExtensions::new(&self.mint_x.to_account_info())?;
+        let y_ext = TokenExtensions::new(&self.mint_y.to_account_info())?;
+
+        let (lp_amount, out_x, out_y) = plan_exact_withdrawal(
+            desired_x,
+            desired_y,
+            &x_ext,
+            &y_ext,
+            self.config.reserve_x,
+            self.config.reserve_y,
+            self.mint_lp.supply,
+        )?;
+
+        require!(lp_amount <= max_lp_in, AmmError::SlippageExceeded);
+        require!(self.user_lp.amount >= lp_amount, AmmError::InsufficientFunds);
+        check_remaining_lp_supply(self.mint_lp.supply, lp_amount)?;
+
+        self.pay_out(lp_amount, out_x, out_y, native_sol, remaining_accounts)
+    }
+
+    /// Burn `amount` LP for its exact share of the tracked reserves while the pool is in emergency mode
+    /// Skips the curve, slippage, oracle and minimum LP supply; Token-2022 still runs each mint's transfer hook
+    /// unless the authority flagged hooks unsafe, then hooked mints aren't paid out at all and their
+    /// share stays with the remaining LPs
+    pub fn emergency_withdraw(
+        &mut self,
+        amount: u64,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        self.config.check_version()?;
+        require!(self.config.emergency, AmmError::NotInEmergency);
+        require!(!self.config.borrowing, AmmError::FlashLoanActive);
+
+        // Persist the guard so a nested call into this pool fails on entry
+        self.config.enter()?;
+        self.config.exit(&crate::ID)?;
+
+        require!(amount > 0, AmmError::InvalidAmount);
+        require!(self.user_lp.amount >= amount, AmmError::InsufficientFunds);
+
+        require!(self.config.mint_x == self.mint_x.key(), AmmError::InvalidToken);
+        require!(self.config.mint_y == self.mint_y.key(), AmmError::InvalidToken);
+        check_pool_vaults(
+            &self.config.key(),
+            &self.config.mint_x,
+            &self.config.mint_y,
+            &self.vault_x,
+            &self.vault_y,
+        )?;
+        check_vaults_not_frozen(&self.vault_x, &self.vault_y)?;
+
+        // Token-2022 can't move a hooked mint without calling its hook, so unsafe hooks are avoided
+        // by not paying those mints out, and no hook accounts are forwarded
+        let hook_unsafe = self.config.hook_unsafe;
+        let remaining_accounts = if hook_unsafe { &[] } else { remaining_accounts };
+        if !hook_unsafe {
+            check_hook_account_budget(
+                &[self.mint_x.to_account_info(), self.mint_y.to_account_info()],
+                remaining_accounts,
+            )?;
+        }
+
+        let (out_x, out_y) = emergency_payout(
+            self.config.reserve_x,
+            self.config.reserve_y,
+            self.mint_lp.supply,
+            amount,
+        )?;
+        let x_hooked = TokenExtensions::new(&self.mint_x.to_account_info())?.has_transfer_hook;
+        let y_hooked = TokenExtensions::new(&self.mint_y.to_account_info())?.has_transfer_hook;
+        let out_x = skip_unsafe_hook(out_x, x_hooked, hook_unsafe);
+        let out_y = skip_unsafe_hook(out_y, y_hooked, hook_unsafe);
+        require!(out_x > 0 || out_y > 0 || !hook_unsafe, AmmError::InvalidAmount);
+
+        msg!("Emergency withdrawal: lp={}, x={}, y={}", amount, out_x, out_y);
+
+        self.pay_out(amount, out_x, out_y, false, remaining_accounts)
+    }
+
+    /// Burn `lp_amount` and send `out_x`/`out_y` from the vaults to the user
+    fn pay_out(
+        &mut self,
+        lp_amount: u64,
+        out_x: u64,
+        out_y: u64,
+        native_sol: bool,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
         // Ensure vault has sufficient balance
         require!(
-            self.vault_x.amount >= amounts.x && self.vault_y.amount >= amounts.y,
+            self.vault_x.amount >= out_x && self.vault_y.amount >= out_y,
             AmmError::InsufficientFunds
         );
 
+        // Burn LP tokens first so destroying the shares gates the payout
+        self.burn_lp_tokens(lp_amount)?;
+
         // Perform withdrawals (transfer fees will be deducted automatically)
-        self.withdraw_tokens(true, amounts.x, _remaining_accounts)?;
-        self.withdraw_tokens(false, amounts.y, _remaining_accounts)?;
+        // Nothing owed on a side means no transfer, and so no hook call either
+        let (before_x, before_y) = (self.vault_x.amount, self.vault_y.amount);
+        if out_x > 0 {
+            self.withdraw_tokens(true, out_x, remaining_accounts)?;
+        }
+        if out_y > 0 {
+            self.withdraw_tokens(false, out_y, remaining_accounts)?;
+        }
+
+        // Track what actually left the vaults
+        self.vault_x.reload()?;
+        self.vault_y.reload()?;
+        self.config.apply_vault_deltas(before_x, self.vault_x.amount, before_y, self.vault_y.amount)?;
+        self.config.check_reserves_backed(self.vault_x.amount, self.vault_y.amount)?;
+
+        // Paid out as lamports; a failed withdrawal reverts before anything is closed
+        if native_sol {
+            for account in [&self.user_x, &self.user_y] {
+                unwrap_sol(self.token_program.to_account_info(), self.user.to_account_info(), account)?;
+            }
+        }
 
-        // Burn LP tokens
-        self.burn_lp_tokens(amount)?;
+        self.config.leave();
 
         Ok(())
     }
@@ -143,7 +334,7 @@ impl<'info> Withdraw<'info> {
         &mut self,
         is_x: bool,
         amount: u64,
-        _remaining_accounts: &[AccountInfo<'info>],
+        remaining_accounts: &[AccountInfo<'info>],
     ) -> Result<()> {
         let (from, to, mint) = if is_x {
             (
@@ -166,77 +357,20 @@ impl<'info> Withdraw<'info> {
         ];
         let signer_seeds = &[&seeds[..]];
 
-        let decimals = mint.decimals;
-        let cpi_program = self.token_program.to_account_info();
-
-        // Get extension information using centralized utilities
         let extensions = TokenExtensions::new(&mint.to_account_info())?;
 
-        match (extensions.has_transfer_fee, extensions.has_transfer_hook) {
-            // Token 2022 with transfer fee only
-            (true, false) => {
-                msg!("Withdraw: Using Token 2022 transfer_checked_with_fee (fee only, no hooks)");
-                let cpi_accounts = TransferCheckedWithFee {
-                    source: from.to_account_info(),
-                    destination: to.to_account_info(),
-                    authority: self.config.to_account_info(),
-                    mint: mint.to_account_info(),
-                    token_program_id: cpi_program.clone(),
-                };
-                let ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
-                let expected_fee = extensions.calculate_fee(amount);
-                transfer_checked_with_fee(ctx, amount, decimals, expected_fee)?;
-            }
-            
-            // Token with BOTH transfer fee AND transfer hook - use Token-2022 
-            (true, true) => {
-                msg!("Withdraw: Using direct spl_token_2022::onchain::invoke_transfer_checked with PDA authority and hooks");
-                
-                invoke_transfer_checked_with_hooks(
-                    &cpi_program.key(),
-                    from.to_account_info(),
-                    mint.to_account_info(),
-                    to.to_account_info(),
-                    self.config.to_account_info(),
-                    _remaining_accounts,
-                    amount,
-                    decimals,
-                    signer_seeds,
-                )?;
-            }
-            
-            // Token with transfer hook only - use Token-2022 
-            (false, true) => {
-                msg!("Withdraw: Using direct spl_token_2022::onchain::invoke_transfer_checked with PDA authority and hooks (no fees)");
-                
-                invoke_transfer_checked_with_hooks(
-                    &cpi_program.key(),
-                    from.to_account_info(),
-                    mint.to_account_info(),
-                    to.to_account_info(),
-                    self.config.to_account_info(),
-                    _remaining_accounts,
-                    amount,
-                    decimals,
-                    signer_seeds,
-                )?;
-            }
-            
-            // Standard token (no extensions)
-            (false, false) => {
-                msg!("Withdraw: Using standard transfer_checked (no extensions)");
-                let cpi_accounts = TransferChecked {
-                    from: from.to_account_info(),
-                    to: to.to_account_info(),
-                    authority: self.config.to_account_info(),
-                    mint: mint.to_account_info(),
-                };
-                let ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
-                transfer_checked(ctx, amount, decimals)?;
-            }
-        }
-
-        Ok(())
+        transfer_with_extensions(
+            self.token_program.to_account_info(),
+            from.to_account_info(),
+            mint.to_account_info(),
+            to.to_account_info(),
+            self.config.to_account_info(),
+            &extensions,
+            remaining_accounts,
+            amount,
+            mint.decimals,
+            signer_seeds,
+        )
     }
 
     pub fn burn_lp_tokens(&mut self, amount: u64) -> Result<()> {
@@ -250,3 +384,243 @@ impl<'info> Withdraw<'info> {
         burn(ctx, amount)
     }
 }
+
+/// Fail a burn of `lp_amount` that would leave a dust LP supply behind
+/// The last LPs can always leave together, anything short of that keeps `MIN_LP_SUPPLY`
+/// so the pool never prices against a handful of LP units
+fn check_remaining_lp_supply(supply: u64, lp_amount: u64) -> Result<()> {
+    let remaining = supply.checked_sub(lp_amount).ok_or(AmmError::InsufficientFunds)?;
+    require!(remaining == 0 || remaining >= MIN_LP_SUPPLY, AmmError::LpSupplyTooLow);
+    Ok(())
+}
+
+/// LP to burn and gross payouts so the user receives at least `desired_x` and `desired_y` net
+///
+/// Each side is grossed up for its transfer fee, then the LP that side needs is rounded up in
+/// favour of the pool. The larger of the two binds and both sides pay out their share of it.
+fn plan_exact_withdrawal(
+    desired_x: u64,
+    desired_y: u64,
+    x_ext: &TokenExtensions,
+    y_ext: &TokenExtensions,
+    reserve_x: u64,
+    reserve_y: u64,
+    supply: u64,
+) -> Result<(u64, u64, u64)> {
+    require!(supply > 0, AmmError::NoLiquidityInPool);
+
+    let gross_x = if desired_x > 0 { x_ext.calculate_gross_for_net(desired_x) } else { 0 };
+    let gross_y = if desired_y > 0 { y_ext.calculate_gross_for_net(desired_y) } else { 0 };
+    require!(
+        gross_x <= reserve_x && gross_y <= reserve_y,
+        AmmError::InsufficientFunds
+    );
+
+    let lp_for_x = if gross_x > 0 { mul_div_ceil(gross_x, supply, reserve_x)? } else { 0 };
+    let lp_for_y = if gross_y > 0 { mul_div_ceil(gross_y, supply, reserve_y)? } else { 0 };
+    let lp_amount = lp_for_x.max(lp_for_y);
+
+    let out_x = withdraw_amount_for_lp(reserve_x, supply, lp_amount)?;
+    let out_y = withdraw_amount_for_lp(reserve_y, supply, lp_amount)?;
+
+    Ok((lp_amount, out_x, out_y))
+}
+
+/// Pro-rata share of both reserves for `lp_amount`, rounded down in favour of the pool
+fn emergency_payout(reserve_x: u64, reserve_y: u64, supply: u64, lp_amount: u64) -> Result<(u64, u64)> {
+    require!(supply > 0, AmmError::NoLiquidityInPool);
+    require!(lp_amount <= supply, AmmError::InsufficientFunds);
+
+    Ok((
+        withdraw_amount_for_lp(reserve_x, supply, lp_amount)?,
+        withdraw_amount_for_lp(reserve_y, supply, lp_amount)?,
+    ))
+}
+
+/// Payout of one side of an emergency withdrawal, nothing for a hooked mint while hooks are unsafe
+/// The LP burned still covers that side, so what isn't paid out stays with the remaining LPs
+fn skip_unsafe_hook(amount: u64, hooked: bool, hook_unsafe: bool) -> u64 {
+    if hooked && hook_unsafe {
+        0
+    } else {
+        amount
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fee_extensions(transfer_fee_basis_points: u16, transfer_fee_maximum: u64) -> TokenExtensions {
+        TokenExtensions {
+            has_transfer_fee: transfer_fee_basis_points > 0,
+            has_transfer_hook: false,
+            transfer_hook_program_id: None,
+            transfer_fee_basis_points,
+            transfer_fee_maximum,
+        }
+    }
+
+    #[test]
+    fn test_plan_exact_withdrawal() {
+        let plain = fee_extensions(0, 0);
+
+        // Pool at 1:2, asking for the pool ratio burns exactly that share
+        let (lp, out_x, out_y) =
+            plan_exact_withdrawal(1_000, 2_000, &plain, &plain, 100_000, 200_000, 50_000).unwrap();
+        assert_eq!((lp, out_x, out_y), (500, 1_000, 2_000));
+
+        // Y binds, X pays out its full share of the LP burned
+        let (lp, out_x, out_y) =
+            plan_exact_withdrawal(100, 2_000, &plain, &plain, 100_000, 200_000, 50_000).unwrap();
+        assert_eq!((lp, out_x, out_y), (500, 1_000, 2_000));
+
+        // One-sided requests still burn for both sides
+        let (lp, _, out_y) = plan_exact_withdrawal(0, 3, &plain, &plain, 100_000, 200_000, 50_000).unwrap();
+        assert_eq!(lp, 1);
+        assert!(out_y >= 3);
+
+        assert!(plan_exact_withdrawal(100_001, 0, &plain, &plain, 100_000, 200_000, 50_000).is_err());
+        assert!(plan_exact_withdrawal(1, 1, &plain, &plain, 0, 0, 0).is_err());
+    }
+
+    #[test]
+    fn test_plan_exact_withdrawal_with_transfer_fee() {
+        use anchor_spl::token_interface::spl_token_2022::extension::transfer_fee::TransferFee;
+
+        // 1% fee on X, capped at 5 tokens on Y
+        let x_ext = fee_extensions(100, u64::MAX);
+        let y_ext = fee_extensions(100, 5);
+        let (reserve_x, reserve_y, supply) = (1_000_000, 3_000_000, 777_777);
+
+        // Token-2022 charges ceil(amount * 1%), capped, on each payout
+        let fee = |maximum_fee: u64| TransferFee {
+            epoch: 0.into(),
+            maximum_fee: maximum_fee.into(),
+            transfer_fee_basis_points: 100.into(),
+        };
+        let (x_fee, y_fee) = (fee(u64::MAX), fee(5));
+
+        // 12_345 net needs ceil(12_345 / 0.99) = 12_470 gross, whose 124.7 fee rounds up to 125
+        let (lp, out_x, out_y) =
+            plan_exact_withdrawal(12_345, 1, &x_ext, &y_ext, reserve_x, reserve_y, supply).unwrap();
+        assert_eq!((lp, out_x, out_y), (9_699, 12_470, 37_410));
+        assert_eq!(x_fee.calculate_post_fee_amount(out_x).unwrap(), 12_345);
+
+        // A gross of 12_469 would have left the user a unit short
+        assert_eq!(x_fee.calculate_post_fee_amount(12_469).unwrap(), 12_344);
+
+        for (desired_x, desired_y) in [(9_900, 1), (1, 29_999), (12_345, 67_890), (500_000, 5)] {
+            let (lp, out_x, out_y) =
+                plan_exact_withdrawal(desired_x, desired_y, &x_ext, &y_ext, reserve_x, reserve_y, supply)
+                    .unwrap();
+
+            // Net of the transfer fees the user still gets what they asked for
+            assert!(x_fee.calculate_post_fee_amount(out_x).unwrap() >= desired_x);
+            assert!(y_fee.calculate_post_fee_amount(out_y).unwrap() >= desired_y);
+
+            // One LP less would not cover the grossed-up amount of the binding side
+            let short_x = withdraw_amount_for_lp(reserve_x, supply, lp - 1).unwrap();
+            let short_y = withdraw_amount_for_lp(reserve_y, supply, lp - 1).unwrap();
+            assert!(
+                x_fee.calculate_post_fee_amount(short_x).unwrap() < desired_x
+                    || y_fee.calculate_post_fee_amount(short_y).unwrap() < desired_y
+            );
+        }
+    }
+
+    #[test]
+    fn test_check_remaining_lp_supply() {
+        // Full exits and withdrawals leaving the minimum are fine
+        check_remaining_lp_supply(5_000, 5_000).unwrap();
+        check_remaining_lp_supply(5_000, 4_000).unwrap();
+        check_remaining_lp_supply(MIN_LP_SUPPLY, MIN_LP_SUPPLY).unwrap();
+
+        // Leaving a sliver of supply is refused, down to a single unit
+        assert_eq!(
+            check_remaining_lp_supply(5_000, 4_001).unwrap_err(),
+            error!(AmmError::LpSupplyTooLow)
+        );
+        assert_eq!(
+            check_remaining_lp_supply(5_000, 4_999).unwrap_err(),
+            error!(AmmError::LpSupplyTooLow)
+        );
+
+        // A pool that never reached the minimum can still only be emptied
+        assert!(check_remaining_lp_supply(500, 500).is_ok());
+        assert!(check_remaining_lp_supply(500, 100).is_err());
+
+        assert_eq!(
+            check_remaining_lp_supply(5_000, 5_001).unwrap_err(),
+            error!(AmmError::InsufficientFunds)
+        );
+    }
+
+    #[test]
+    fn test_emergency_payout_is_pro_rata() {
+        let (reserve_x, reserve_y, supply) = (1_000_000, 3_000_001, 777_777);
+
+        // The whole supply takes exactly the reserves
+        assert_eq!(emergency_payout(reserve_x, reserve_y, supply, supply).unwrap(), (reserve_x, reserve_y));
+
+        // Any share rounds down, never above lp / supply of either reserve
+        for lp in [1, 2, 3, 999, 12_345, 388_888, supply - 1] {
+            let (x, y) = emergency_payout(reserve_x, reserve_y, supply, lp).unwrap();
+            assert!(x as u128 * supply as u128 <= reserve_x as u128 * lp as u128);
+            assert!(y as u128 * supply as u128 <= reserve_y as u128 * lp as u128);
+        }
+
+        // Dust LP against a deep supply gets nothing
+        assert_eq!(emergency_payout(10, 10, 1_000, 1).unwrap(), (0, 0));
+
+        assert!(emergency_payout(reserve_x, reserve_y, supply, supply + 1).is_err());
+        assert!(emergency_payout(reserve_x, reserve_y, 0, 0).is_err());
+    }
+
+    #[test]
+    fn test_skip_unsafe_hook() {
+        // Only a hooked side is held back, and only once hooks are flagged unsafe
+        assert_eq!(skip_unsafe_hook(1_000, true, true), 0);
+        assert_eq!(skip_unsafe_hook(1_000, true, false), 1_000);
+        assert_eq!(skip_unsafe_hook(1_000, false, true), 1_000);
+        assert_eq!(skip_unsafe_hook(1_000, false, false), 1_000);
+
+        // Burning the whole supply with a hooked X leaves all of X behind and still drains Y
+        let (x, y) = emergency_payout(1_000, 2_000, 500, 500).unwrap();
+        assert_eq!((skip_unsafe_hook(x, true, true), skip_unsafe_hook(y, false, true)), (0, 2_000));
+    }
+
+    #[test]
+    fn test_emergency_payout_split_exits_never_beat_one_exit() {
+        let (reserve_x, reserve_y, supply) = (1_000_003u64, 2_999_999u64, 500_001u64);
+        let holding = 250_000;
+        let (whole_x, whole_y) = emergency_payout(reserve_x, reserve_y, supply, holding).unwrap();
+
+        // Withdrawing the same LP in small pieces, the pool shrinking after each, pays no more
+        for piece in [1, 7, 333, 99_999] {
+            let (mut rx, mut ry, mut s) = (reserve_x, reserve_y, supply);
+            let (mut got_x, mut got_y, mut left) = (0u64, 0u64, holding);
+            while left > 0 {
+                let lp = piece.min(left);
+                let (x, y) = emergency_payout(rx, ry, s, lp).unwrap();
+                got_x += x;
+                got_y += y;
+                rx -= x;
+                ry -= y;
+                s -= lp;
+                left -= lp;
+            }
+            assert!(got_x <= whole_x && got_y <= whole_y);
+        }
+
+        // Every LP exiting one by one drains the pool without ever overdrawing it
+        let (mut rx, mut ry, mut s) = (1_000u64, 2_000u64, 7u64);
+        while s > 0 {
+            let (x, y) = emergency_payout(rx, ry, s, 1).unwrap();
+            rx = rx.checked_sub(x).unwrap();
+            ry = ry.checked_sub(y).unwrap();
+            s -= 1;
+        }
+        assert_eq!((rx, ry), (0, 0));
+    }
+}
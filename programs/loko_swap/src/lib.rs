@@ -1,8 +1,23 @@
 #![allow(unexpected_cfgs)]
 #[warn(deprecated)]
 
+/// Log the remaining compute units under `label`
+/// Expands to nothing unless the `compute-trace` feature is enabled
+macro_rules! compute_trace {
+    ($label:expr) => {
+        #[cfg(feature = "compute-trace")]
+        {
+            anchor_lang::prelude::msg!($label);
+            anchor_lang::solana_program::log::sol_log_compute_units();
+        }
+    };
+}
+
+#[cfg(any(feature = "client", test))]
+pub mod client;
 pub mod constants;
 pub mod error;
+pub mod events;
 pub mod instructions;
 pub mod services;
 pub mod state;
@@ -25,10 +40,18 @@ pub mod loko_swap {
     /// # Arguments
     /// * `seed` - Unique seed for this pool
     /// * `fee` - Trading fee in basis points (max 1000 = 10%)
-    /// * `authority` - Optional authority for pool management
-    /// * `transfer_fee_basis_points` - Default transfer fee for new tokens (basis points)
-    /// * `max_transfer_fee` - Maximum transfer fee in base units
+    /// * `authority` - Optional authority for pool management, without one the pool is
+    ///   permanently permissionless and authority-gated instructions fail with `NoAuthoritySet`
+    /// * `transfer_fee_basis_points` - Default transfer fee for new tokens (basis points, below 10000)
+    /// * `max_transfer_fee` - Maximum transfer fee in base units, zero exactly when the fee is zero
     /// * `hook_program_id` - Optional default hook program for transfers
+    /// * `protocol_fee_bps` - Portion of `fee` sent to the protocol (must be <= fee)
+    /// * `protocol_fee_destination` - Owner of the token accounts receiving the protocol fee
+    /// * `lp_decimals` - Decimals of the LP mint, at most 9 (defaults to 6)
+    /// * `timelock_delay` - Seconds fee, fee routing and hook changes wait (0 = immediate)
+    /// * `permissioned` - Only pair mints from the `mint_allowlist` account
+    /// * `pass_output_fee` - Swappers bear the output mint's transfer fee, `min_out` applies to
+    ///   what they net; false has the pool pay it on top of the output
     pub fn initialize<'info>(
         ctx: Context<'_, '_, 'info, 'info, Initialize<'info>>,
         seed: u64,
@@ -37,6 +60,12 @@ pub mod loko_swap {
         transfer_fee_basis_points: u16,
         max_transfer_fee: u64,
         hook_program_id: Option<Pubkey>,
+        protocol_fee_bps: u16,
+        protocol_fee_destination: Pubkey,
+        lp_decimals: Option<u8>,
+        timelock_delay: i64,
+        permissioned: bool,
+        pass_output_fee: bool,
     ) -> Result<()> {
         ctx.accounts.initialize(
             seed, 
@@ -45,6 +74,12 @@ pub mod loko_swap {
             transfer_fee_basis_points,
             max_transfer_fee,
             hook_program_id,
+            protocol_fee_bps,
+            protocol_fee_destination,
+            lp_decimals,
+            timelock_delay,
+            permissioned,
+            pass_output_fee,
             &ctx.bumps,
             ctx.remaining_accounts
         )
@@ -54,9 +89,14 @@ pub mod loko_swap {
     /// Handles Token 2022 extensions including transfer fees and hooks
     /// 
     /// # Arguments
-    /// * `amount` - Amount of LP tokens to mint
+    /// * `amount` - Amount of LP tokens to mint, the minimum to mint on the pool's first deposit
+    ///   The first deposit mints sqrt(x * y) of the net amounts reaching the vaults
     /// * `max_x` - Maximum amount of token X to deposit (including fees)
     /// * `max_y` - Maximum amount of token Y to deposit (including fees)
+    /// * `min_lp_out` - Minimum amount of LP tokens to receive, 0 to disable
+    ///   Fewer than `amount` are minted if less than expected reaches the vaults
+    /// * `deadline` - Unix timestamp after which the deposit fails with `OfferExpired`, 0 to disable
+    /// * `native_sol` - Wrap WSOL inputs from the user's lamports and close the WSOL accounts after
     /// 
     /// # Transfer Hook Support
     /// Token-2022 handles all hook account resolution automatically.
@@ -66,8 +106,52 @@ pub mod loko_swap {
         amount: u64,
         max_x: u64,
         max_y: u64,
+        min_lp_out: u64,
+        deadline: i64,
+        native_sol: bool,
+    ) -> Result<()> {
+        ctx.accounts.deposit(amount, max_x, max_y, min_lp_out, deadline, native_sol, ctx.remaining_accounts)
+    }
+
+    /// Deposit exact token amounts into the AMM pool to receive LP tokens
+    /// The side that mints fewer LP tokens binds, the other is only taken at the pool ratio
+    /// 
+    /// # Arguments
+    /// * `desired_x` - Maximum amount of token X to deposit (including fees)
+    /// * `desired_y` - Maximum amount of token Y to deposit (including fees)
+    /// * `min_lp_out` - Minimum amount of LP tokens to receive, 0 to disable
+    /// * `deadline` - Unix timestamp after which the deposit fails with `OfferExpired`, 0 to disable
+    /// * `native_sol` - Wrap WSOL inputs from the user's lamports and close the WSOL accounts after
+    pub fn deposit_exact_tokens<'info>(
+        ctx: Context<'_, '_, 'info, 'info, Deposit<'info>>,
+        desired_x: u64,
+        desired_y: u64,
+        min_lp_out: u64,
+        deadline: i64,
+        native_sol: bool,
+    ) -> Result<()> {
+        ctx.accounts.deposit_exact_tokens(desired_x, desired_y, min_lp_out, deadline, native_sol, ctx.remaining_accounts)
+    }
+
+    /// Deposit a single token into the AMM pool to receive LP tokens
+    /// The optimal share of the input is swapped against the pool before the deposit, at the fee
+    /// and protocol share a `swap` pays; pass `protocol_fee_account` when the protocol fee is on
+    /// 
+    /// # Arguments
+    /// * `is_x` - True if depositing token X, false if depositing token Y
+    /// * `amount` - Amount of input tokens to deposit (including transfer fees)
+    /// * `min_lp_out` - Minimum amount of LP tokens to receive, 0 to disable
+    /// * `deadline` - Unix timestamp after which the deposit fails with `OfferExpired`, 0 to disable
+    /// * `native_sol` - Wrap a WSOL input from the user's lamports and close the WSOL accounts after
+    pub fn deposit_single_sided<'info>(
+        ctx: Context<'_, '_, 'info, 'info, Deposit<'info>>,
+        is_x: bool,
+        amount: u64,
+        min_lp_out: u64,
+        deadline: i64,
+        native_sol: bool,
     ) -> Result<()> {
-        ctx.accounts.deposit(amount, max_x, max_y, ctx.remaining_accounts)
+        ctx.accounts.deposit_single_sided(is_x, amount, min_lp_out, deadline, native_sol, ctx.remaining_accounts)
     }
 
     /// Withdraw tokens from the AMM pool by burning LP tokens
@@ -77,7 +161,7 @@ pub mod loko_swap {
     /// * `amount` - Amount of LP tokens to burn
     /// * `min_x` - Minimum amount of token X to receive (after fees)
     /// * `min_y` - Minimum amount of token Y to receive (after fees)
-    /// 
+    /// * `native_sol` - Close the user's WSOL accounts so WSOL is paid out as lamports
     /// # Transfer Hook Support
     /// Token-2022 handles all hook account resolution automatically.
     /// No additional accounts need to be provided via remaining_accounts.
@@ -86,8 +170,40 @@ pub mod loko_swap {
         amount: u64,
         min_x: u64,
         min_y: u64,
+        native_sol: bool,
+    ) -> Result<()> {
+        ctx.accounts.withdraw(amount, min_x, min_y, native_sol, ctx.remaining_accounts)
+    }
+
+    /// Withdraw exact token amounts from the AMM pool, burning the LP tokens they need
+    /// The side needing more LP binds, the other side pays out its full share of it
+    /// 
+    /// # Arguments
+    /// * `desired_x` - Amount of token X to receive (after fees)
+    /// * `desired_y` - Amount of token Y to receive (after fees)
+    /// * `max_lp_in` - Maximum amount of LP tokens to burn
+    /// * `native_sol` - Close the user's WSOL accounts so WSOL is paid out as lamports
+    pub fn withdraw_exact_tokens<'info>(
+        ctx: Context<'_, '_, 'info, 'info, Withdraw<'info>>,
+        desired_x: u64,
+        desired_y: u64,
+        max_lp_in: u64,
+        native_sol: bool,
     ) -> Result<()> {
-        ctx.accounts.withdraw(amount, min_x, min_y, ctx.remaining_accounts)
+        ctx.accounts.withdraw_exact_tokens(desired_x, desired_y, max_lp_in, native_sol, ctx.remaining_accounts)
+    }
+
+    /// Burn LP tokens for their exact share of the reserves while the pool is in emergency mode
+    /// No curve, slippage or oracle, WSOL is paid out as tokens
+    /// With hooks flagged unsafe, the share of a hooked mint is left to the remaining LPs
+    /// 
+    /// # Arguments
+    /// * `amount` - Amount of LP tokens to burn
+    pub fn emergency_withdraw<'info>(
+        ctx: Context<'_, '_, 'info, 'info, Withdraw<'info>>,
+        amount: u64,
+    ) -> Result<()> {
+        ctx.accounts.emergency_withdraw(amount, ctx.remaining_accounts)
     }
 
     /// Swap tokens in the AMM pool
@@ -97,11 +213,22 @@ pub mod loko_swap {
     /// * `amount` - Amount of input tokens to swap
     /// * `is_x` - True if swapping X for Y, false if swapping Y for X
     /// * `min` - Minimum amount of output tokens to receive (after fees)
+    /// * `max_price_impact_bps` - Maximum move of the execution price away from the spot price (0 to disable)
+    /// * `referral_fee_bps` - Share of the trading fee sent to `referral_fee_account` (max 50)
+    /// * `native_sol` - Wrap a WSOL input from the user's lamports and pay a WSOL output as lamports
     /// 
     /// # Transfer Fee Handling
     /// For input tokens with transfer fees: The specified amount includes fees
     /// For output tokens with transfer fees: The AMM pays the fees to ensure user receives `min` amount
     /// 
+    /// # Protocol Fee
+    /// When `protocol_fee_bps` is set, that share of the trading fee is sent to
+    /// `protocol_fee_account` (input mint, owned by `protocol_fee_destination`)
+    /// 
+    /// # Referral Fee
+    /// When `referral_fee_account` (input mint) is passed, `referral_fee_bps` of the
+    /// trading fee is sent to it. Ignored without the account.
+    /// 
     /// # Transfer Hook Support
     /// Token-2022 handles all hook account resolution automatically.
     /// No additional accounts need to be provided via remaining_accounts.
@@ -110,8 +237,60 @@ pub mod loko_swap {
         amount: u64,
         is_x: bool,
         min: u64,
+        max_price_impact_bps: u16,
+        referral_fee_bps: u16,
+        native_sol: bool,
+    ) -> Result<()> {
+        ctx.accounts.swap(is_x, amount, min, max_price_impact_bps, referral_fee_bps, native_sol, ctx.remaining_accounts)
+    }
+
+    /// Swap one order as consecutive fills against the pool in a single transaction
+    /// Each fill runs the full `swap` path, so fees and events are per fill
+    /// 
+    /// # Arguments
+    /// * `amounts` - Input amount of each fill (including fees), at most `MAX_SWAP_STEPS`
+    /// * `is_x` - True if swapping X for Y, false if swapping Y for X
+    /// * `min_out` - Minimum total amount of output tokens to receive (after fees)
+    /// * `max_price_impact_bps` - Maximum price impact of each fill (0 to disable)
+    /// * `referral_fee_bps` - Share of the trading fee sent to `referral_fee_account` (max 50)
+    /// * `native_sol` - Wrap a WSOL input from the user's lamports and pay a WSOL output as lamports
+    pub fn swap_batched<'info>(
+        ctx: Context<'_, '_, 'info, 'info, Swap<'info>>,
+        amounts: Vec<u64>,
+        is_x: bool,
+        min_out: u64,
+        max_price_impact_bps: u16,
+        referral_fee_bps: u16,
+        native_sol: bool,
     ) -> Result<()> {
-        ctx.accounts.swap(is_x, amount, min, ctx.remaining_accounts)
+        ctx.accounts.swap_batched(
+            is_x,
+            amounts,
+            min_out,
+            max_price_impact_bps,
+            referral_fee_bps,
+            native_sol,
+            ctx.remaining_accounts,
+        )
+    }
+
+    /// Borrow tokens from a vault and repay them within the same instruction
+    /// 
+    /// # Arguments
+    /// * `amount` - Amount of tokens to borrow from the vault of `mint`
+    /// * `data` - Instruction data forwarded to `callback_program`
+    /// 
+    /// # Callback
+    /// `callback_program` is invoked with [borrower, borrower_token_account, vault, mint,
+    /// token_program] followed by all remaining_accounts, and must leave the vault holding
+    /// its previous balance plus `flash_fee_bps` of `amount`.
+    /// Deposits, withdrawals and swaps on this pool fail until the loan is repaid.
+    pub fn flash_loan<'info>(
+        ctx: Context<'_, '_, 'info, 'info, FlashLoan<'info>>,
+        amount: u64,
+        data: Vec<u8>,
+    ) -> Result<()> {
+        ctx.accounts.flash_loan(amount, data, ctx.remaining_accounts)
     }
 
     /// Lock the pool to prevent deposits, withdrawals, and swaps
@@ -126,26 +305,237 @@ pub mod loko_swap {
         ctx.accounts.unlock()
     }
 
+    /// Update the protocol share of the trading fee
+    /// Only callable by the pool authority, queued behind the timelock when the pool has one
+    /// 
+    /// # Arguments
+    /// * `protocol_fee_bps` - New protocol fee (basis points, must be <= pool fee)
+    /// * `protocol_fee_destination` - New owner of the protocol fee token accounts
+    pub fn update_protocol_fee(
+        ctx: Context<Update>,
+        protocol_fee_bps: u16,
+        protocol_fee_destination: Pubkey,
+    ) -> Result<()> {
+        ctx.accounts.update_protocol_fee(protocol_fee_bps, protocol_fee_destination)
+    }
+
+    /// Turn the protocol fee on or off without changing its rate
+    /// Only callable by the pool authority, queued behind the timelock when the pool has one
+    /// 
+    /// # Arguments
+    /// * `fee_enabled` - False to leave the whole trading fee with LPs
+    pub fn set_fee_enabled(ctx: Context<Update>, fee_enabled: bool) -> Result<()> {
+        ctx.accounts.set_fee_enabled(fee_enabled)
+    }
+
+    /// Put the pool into or out of emergency mode
+    /// Swaps, deposits, flash loans and fee reinvestment stop while it is on
+    /// Only callable by the pool authority
+    /// 
+    /// # Arguments
+    /// * `emergency` - True to only allow `emergency_withdraw` and `withdraw`
+    pub fn set_emergency(ctx: Context<Update>, emergency: bool) -> Result<()> {
+        ctx.accounts.set_emergency(emergency)
+    }
+
+    /// Flag the pool's transfer hooks as unsafe during an incident, applies at once
+    /// Swaps then use the pool fee without calling the hook, and `emergency_withdraw` leaves
+    /// the share of hooked mints in the vaults rather than run their hooks
+    /// Only callable by the pool authority
+    /// 
+    /// # Arguments
+    /// * `hook_unsafe` - True to stop the pool from calling transfer hooks where it can
+    pub fn set_hook_unsafe(ctx: Context<Update>, hook_unsafe: bool) -> Result<()> {
+        ctx.accounts.set_hook_unsafe(hook_unsafe)
+    }
+
+    /// Set the range a transfer hook's dynamic fee is clamped into before a swap uses it
+    /// Only callable by the pool authority, queued behind the timelock when the pool has one
+    /// 
+    /// # Arguments
+    /// * `min_dynamic_fee_bps` - Lowest fee a hook can set, defaults to the pool fee
+    /// * `max_dynamic_fee_bps` - Highest fee a hook can set, at most 1000 (10%)
+    pub fn set_dynamic_fee_bounds(
+        ctx: Context<Update>,
+        min_dynamic_fee_bps: u16,
+        max_dynamic_fee_bps: u16,
+    ) -> Result<()> {
+        ctx.accounts.set_dynamic_fee_bounds(min_dynamic_fee_bps, max_dynamic_fee_bps)
+    }
+
+    /// Set the recipient and share of the LP `mint_protocol_lp` mints on sqrt(k) growth
+    /// Growth is measured again from the next `mint_protocol_lp` after the change applies
+    /// Only callable by the pool authority, queued behind the timelock when the pool has one
+    /// 
+    /// # Arguments
+    /// * `destination` - Owner of the LP token account receiving protocol LP
+    /// * `share_bps` - Share of the growth taken, at most 5000 (50%), 0 turns it off
+    pub fn set_protocol_lp(ctx: Context<Update>, destination: Pubkey, share_bps: u16) -> Result<()> {
+        ctx.accounts.set_protocol_lp(destination, share_bps)
+    }
+
+    /// Update the premium charged on flash loans
+    /// Only callable by the pool authority
+    /// 
+    /// # Arguments
+    /// * `flash_fee_bps` - New flash loan fee (basis points)
+    pub fn update_flash_fee(ctx: Context<Update>, flash_fee_bps: u16) -> Result<()> {
+        ctx.accounts.update_flash_fee(flash_fee_bps)
+    }
+
+    /// Set the balance a swap must leave in its output vault
+    /// Only callable by the pool authority
+    /// 
+    /// # Arguments
+    /// * `min_reserve` - Minimum output vault balance after a swap (0 to disable)
+    pub fn update_min_reserve(ctx: Context<Update>, min_reserve: u64) -> Result<()> {
+        ctx.accounts.update_min_reserve(min_reserve)
+    }
+
+    /// Require the hook's fee stats account on swaps instead of falling back to the pool fee
+    /// Strict pools also reject swaps of mints whose hook isn't the approved hook program
+    /// Only callable by the pool authority, queued behind the timelock when the pool has one
+    /// 
+    /// # Arguments
+    /// * `strict_hooks` - True to fail swaps when the fee stats account is missing or the hook is unapproved
+    pub fn update_strict_hooks(ctx: Context<Update>, strict_hooks: bool) -> Result<()> {
+        ctx.accounts.update_strict_hooks(strict_hooks)
+    }
+
     /// Collect transfer fees from Token-2022 accounts
     /// Only callable by the pool authority
+    /// Fees go to the pool's fee vault, or to `fee_destination` with direct fee collection
     /// 
     /// # Arguments
+    /// * `max_accounts` - Most source accounts to process, 0 for the cap of `MAX_FEE_SOURCES` (20)
+    /// 
     /// Additional accounts from which to collect fees should be passed via remaining_accounts.
-    /// These accounts must contain withheld transfer fees for the specified mint.
+    /// These accounts must be token accounts of the specified mint.
+    /// Accounts with nothing withheld are skipped without counting toward the batch.
+    /// Only the first batch is processed; `FeesCollected` lists its sources and how many are left.
+    /// Batches of 20 or fewer are recommended so a sweep stays inside the compute budget.
+    /// Returns the amount collected so CPI callers can read it.
     pub fn collect_fees<'info>(
         ctx: Context<'_, '_, 'info, 'info, CollectFees<'info>>,
+        max_accounts: u8,
+    ) -> Result<u64> {
+        ctx.accounts.collect_fees(max_accounts, ctx.remaining_accounts)
+    }
+
+    /// Collect transfer fees that have been harvested to the mint
+    /// Only callable by the pool authority
+    /// 
+    /// Withheld fees end up on the mint once anyone calls `harvest_withheld_tokens_to_mint`,
+    /// so this sweeps them from the mint into the fee vault or fee destination.
+    /// Emits `FeesCollected` and returns the amount collected.
+    pub fn collect_fees_from_mint(ctx: Context<CollectFees>) -> Result<u64> {
+        ctx.accounts.collect_fees_from_mint()
+    }
+
+    /// Pay the fee vault of a mint out to the pool's fee destination
+    /// Permissionless, emits `FeesDistributed`
+    /// 
+    /// Transfer hook accounts for the mint are passed via remaining_accounts.
+    pub fn distribute_fees<'info>(
+        ctx: Context<'_, '_, 'info, 'info, DistributeFees<'info>>,
     ) -> Result<()> {
-        ctx.accounts.collect_fees(ctx.remaining_accounts)
+        ctx.accounts.distribute_fees(ctx.remaining_accounts)
     }
 
-    /// Update transfer fee configuration for the pool
+    /// Deposit the fee vaults back into the pool at the current reserve ratio
+    /// LP is minted to the fee destination's LP account, emits `FeesReinvested`
+    /// Only callable by the pool authority
+    /// 
+    /// Transfer hook accounts for the pool mints are passed via remaining_accounts.
+    pub fn reinvest_fees<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ReinvestFees<'info>>,
+    ) -> Result<()> {
+        ctx.accounts.reinvest_fees(ctx.remaining_accounts)
+    }
+
+    /// Mint LP to `protocol_lp_destination` for its share of the pool's sqrt(k) growth
+    /// Growth is per LP, so deposits and withdrawals don't count. Permissionless,
+    /// emits `ProtocolLpMinted` and returns the LP minted
+    pub fn mint_protocol_lp(ctx: Context<MintProtocolLp>) -> Result<u64> {
+        ctx.accounts.mint_protocol_lp()
+    }
+
+    /// Add tokens to the reserves without minting LP, gifting them to the existing LPs
+    /// Only allowed once the pool has liquidity, emits `LiquidityDonated`
+    /// 
+    /// # Arguments
+    /// * `amount_x` - Amount of token X to donate (before fees)
+    /// * `amount_y` - Amount of token Y to donate (before fees)
+    pub fn donate_liquidity<'info>(
+        ctx: Context<'_, '_, 'info, 'info, DonateLiquidity<'info>>,
+        amount_x: u64,
+        amount_y: u64,
+    ) -> Result<()> {
+        ctx.accounts.donate_liquidity(amount_x, amount_y, ctx.remaining_accounts)
+    }
+
+    /// Send collected transfer fees straight to `fee_destination` instead of the fee vault
+    /// Only callable by the pool authority, queued behind the timelock when the pool has one
+    /// 
+    /// # Arguments
+    /// * `direct_fee_collection` - True to skip the fee vault and `distribute_fees`
+    pub fn update_direct_fee_collection(
+        ctx: Context<Update>,
+        direct_fee_collection: bool,
+    ) -> Result<()> {
+        ctx.accounts.update_direct_fee_collection(direct_fee_collection)
+    }
+
+    /// Update the base trading fee of the pool
+    /// Only callable by the pool authority, queued behind the timelock when the pool has one
+    /// 
+    /// # Arguments
+    /// * `new_fee_bps` - New trading fee (basis points, max 1000, at least the protocol fee)
+    pub fn update_trading_fee(ctx: Context<Update>, new_fee_bps: u16) -> Result<()> {
+        ctx.accounts.update_trading_fee(new_fee_bps)
+    }
+
+    /// Re-read both mints' extensions into the config's `supports_*` flags
+    /// A hook the mints now agree on is adopted as the hook program through the timelock,
+    /// if it is an approved hook program
+    /// Only callable by the pool authority
+    pub fn refresh_extension_flags(ctx: Context<RefreshExtensionFlags>) -> Result<()> {
+        ctx.accounts.refresh_extension_flags()
+    }
+
+    /// Wrap SOL into the config's WSOL account and approve the hook's fee delegate for its balance
+    /// The dynamic fee hook charges the sender's WSOL account on every transfer, and transfers out
+    /// of the vaults are sent by the config. Emits `HookFeeFunded`
     /// Only callable by the pool authority
     /// 
     /// # Arguments
-    /// * `new_fee_basis_points` - New default transfer fee (basis points, max 10000)
-    /// * `new_max_fee` - New maximum transfer fee in base units
+    /// * `amount` - Lamports to wrap, 0 to only renew the approval
+    pub fn fund_hook_fee(ctx: Context<FundHookFee>, amount: u64) -> Result<()> {
+        ctx.accounts.fund_hook_fee(amount)
+    }
+
+    /// Resolve the extra accounts a hook transfer of `mint` needs and emit them as `HookAccountsResolved`
+    /// Devnet helper for checking an `ExtraAccountMetaList`, moves no tokens
+    /// Only built with the `devnet-tools` feature
+    /// 
+    /// # Arguments
+    /// * `amount` - Transfer amount passed to the hook's Execute instruction
+    #[cfg(feature = "devnet-tools")]
+    pub fn simulate_hook_resolution<'info>(
+        ctx: Context<'_, '_, 'info, 'info, SimulateHookResolution<'info>>,
+        amount: u64,
+    ) -> Result<()> {
+        ctx.accounts.simulate_hook_resolution(amount, ctx.remaining_accounts)
+    }
+
+    /// Update transfer fee configuration for the pool
+    /// Only callable by the pool authority, queued behind the timelock when the pool has one
+    /// 
+    /// # Arguments
+    /// * `new_fee_basis_points` - New default transfer fee (basis points, below 10000)
+    /// * `new_max_fee` - New maximum transfer fee in base units, zero exactly when the fee is zero
     pub fn update_transfer_fee_config(
-        ctx: Context<CollectFees>,
+        ctx: Context<Update>,
         new_fee_basis_points: u16,
         new_max_fee: u64,
     ) -> Result<()> {
@@ -153,26 +543,128 @@ pub mod loko_swap {
     }
 
     /// Update the fee destination account
-    /// Only callable by the pool authority
+    /// Only callable by the pool authority, queued behind the timelock when the pool has one
     /// 
     /// # Arguments
     /// * `new_destination` - New account to receive collected fees
     pub fn update_fee_destination(
-        ctx: Context<CollectFees>,
+        ctx: Context<Update>,
         new_destination: Pubkey,
     ) -> Result<()> {
         ctx.accounts.update_fee_destination(new_destination)
     }
 
     /// Update the default hook program
-    /// Only callable by the pool authority
+    /// Only callable by the pool authority, queued behind the timelock when the pool has one
     /// 
     /// # Arguments
     /// * `new_hook_program` - New default hook program (None to remove)
     pub fn update_hook_program(
-        ctx: Context<CollectFees>,
+        ctx: Context<Update>,
         new_hook_program: Option<Pubkey>,
     ) -> Result<()> {
         ctx.accounts.update_hook_program(new_hook_program)
     }
+
+    /// Apply the queued authority change once `timelock_delay` has passed since it was queued
+    /// Permissionless
+    pub fn apply_pending_change(ctx: Context<Update>) -> Result<()> {
+        ctx.accounts.apply_pending_change()
+    }
+
+    /// Reset the tracked reserves to the actual vault balances
+    /// Permissionless, used to absorb tokens sent directly to the vaults into the pool
+    pub fn sync(ctx: Context<SyncReserves>) -> Result<()> {
+        ctx.accounts.sync()
+    }
+
+    /// Send vault balances above the tracked reserves to the destination accounts
+    /// Only callable by the pool authority
+    /// 
+    /// # Transfer Hook Support
+    /// Hook accounts for either mint should be passed via remaining_accounts.
+    pub fn skim<'info>(ctx: Context<'_, '_, 'info, 'info, Skim<'info>>) -> Result<()> {
+        ctx.accounts.skim(ctx.remaining_accounts)
+    }
+
+    /// Name the LP token through the Token-2022 metadata extension
+    /// Only callable by the pool authority, which pays for the extra mint space
+    /// 
+    /// # Arguments
+    /// * `uri` - URI of the off-chain metadata JSON
+    /// 
+    /// The name is derived from the pool mints and the config PDA is the update authority.
+    /// Requires an LP mint created with the metadata pointer extension.
+    pub fn set_lp_metadata(ctx: Context<SetLpMetadata>, uri: String) -> Result<()> {
+        ctx.accounts.set_lp_metadata(uri)
+    }
+
+    /// Upgrade a pool's config account to the current layout
+    /// Only callable by the pool authority, which pays for the extra space
+    /// 
+    /// # Arguments
+    /// * `seed` - Seed of the pool being migrated
+    /// 
+    /// New fields get their defaults and the tracked reserves start at the vault balances.
+    /// Deposits, withdrawals, swaps and flash loans fail until an older pool is migrated.
+    pub fn migrate_config(ctx: Context<MigrateConfig>, _seed: u64) -> Result<()> {
+        ctx.accounts.migrate_config()
+    }
+
+    /// Create a mint allowlist for permissioned pools, owned by the signer
+    pub fn create_allowlist(ctx: Context<CreateAllowlist>) -> Result<()> {
+        ctx.accounts.create_allowlist(&ctx.bumps)
+    }
+
+    /// Allow a mint in permissioned pools using this allowlist
+    /// Only callable by the allowlist authority
+    pub fn add_allowed_mint(ctx: Context<UpdateAllowlist>, mint: Pubkey) -> Result<()> {
+        ctx.accounts.add_allowed_mint(mint)
+    }
+
+    /// Remove a mint from the allowlist, existing pools are unaffected
+    /// Only callable by the allowlist authority
+    pub fn remove_allowed_mint(ctx: Context<UpdateAllowlist>, mint: Pubkey) -> Result<()> {
+        ctx.accounts.remove_allowed_mint(mint)
+    }
+
+    /// Report reserves, LP supply, fees, lock status, extension flags and swap stats in one call
+    /// Emits a `PoolInfo` event and returns it so other programs can read it via CPI
+    pub fn pool_info(ctx: Context<PoolInfoView>) -> Result<events::PoolInfo> {
+        ctx.accounts.pool_info()
+    }
+
+    /// Compare the tracked reserves with the live vault balances and recompute `k`
+    /// Read-only, emits a `HealthReport` with the drift of each vault and returns it for CPI callers
+    pub fn check_invariants(ctx: Context<PoolInfoView>) -> Result<events::HealthReport> {
+        ctx.accounts.check_invariants()
+    }
+
+    /// Price of one whole X token in whole Y tokens, normalized for both mints' decimals
+    /// Emits a `SpotPrice` event with the Q64.64 price and returns it for CPI callers
+    /// Fails with `NoLiquidityInPool` while either reserve is empty
+    pub fn spot_price(ctx: Context<SpotPriceView>) -> Result<events::SpotPrice> {
+        ctx.accounts.spot_price()
+    }
+
+    /// Reserves `lp_amount` LP tokens are worth at the current pool ratio, before transfer fees
+    /// Emits an `LpValue` event and returns it for CPI callers
+    /// Fails with `NoLiquidityInPool` while no LP exists, and `InsufficientFunds` above the supply
+    pub fn lp_to_tokens(ctx: Context<LpValueView>, lp_amount: u64) -> Result<events::LpValue> {
+        ctx.accounts.lp_to_tokens(lp_amount)
+    }
+
+    /// Expected output and fee of swapping `amount`, priced exactly like `swap` but without executing
+    /// Emits a `SwapQuote` event and returns it so other programs can read it via CPI
+    ///
+    /// # Arguments
+    /// * `is_x` - true to quote X in for Y out, false for Y in for X out
+    /// * `amount` - Input amount before the input mint's transfer fee
+    pub fn quote_swap<'info>(
+        ctx: Context<'_, '_, 'info, 'info, SwapQuoteView<'info>>,
+        is_x: bool,
+        amount: u64,
+    ) -> Result<events::SwapQuote> {
+        ctx.accounts.quote_swap(is_x, amount, ctx.remaining_accounts)
+    }
 }
\ No newline at end of file
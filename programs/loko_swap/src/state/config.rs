@@ -1,7 +1,13 @@
 use anchor_lang::prelude::*;
 
+use crate::{
+    constants::{CONFIG_VERSION, DEFAULT_FLASH_FEE_BPS, MAX_FEE_BPS, MAX_PROTOCOL_LP_SHARE_BPS},
+    error::AmmError,
+    utils::math::price_q64,
+};
+
 #[account]
-#[derive(InitSpace)]
+#[derive(InitSpace, Default)]
 pub struct Config {
     pub seed: u64,
     pub authority: Option<Pubkey>,
@@ -28,4 +34,868 @@ pub struct Config {
     // Whitelisted hook programs for security
     #[max_len(10)]
     pub approved_hook_programs: Vec<Pubkey>,
+
+    // Protocol share of the trading fee
+    pub protocol_fee_bps: u16,                       // Carved out of `fee`, must be <= fee
+    pub protocol_fee_destination: Pubkey,            // Owner of the protocol fee token accounts
+
+    // Reserves tracked by the program, excluding tokens sent directly to the vaults
+    pub reserve_x: u64,
+    pub reserve_y: u64,
+
+    // Flash loans
+    pub borrowing: bool,                             // Set while a flash loan callback runs
+    pub flash_fee_bps: u16,                          // Premium owed on top of the borrowed amount
+
+    // Set for the duration of deposit, withdraw, swap and flash_loan
+    pub locked_for_reentrancy: bool,
+
+    // TWAP oracle accumulators, Q64.64 prices summed over seconds and allowed to wrap
+    pub price_x_cumulative: u128,                    // Price of X in Y
+    pub price_y_cumulative: u128,                    // Price of Y in X
+    pub last_oracle_timestamp: i64,
+
+    // Fail swaps instead of using `fee` when the hook's fee stats account is missing
+    // or a mint's hook isn't `default_hook_program`
+    pub strict_hooks: bool,
+
+    // Timelock for every `ConfigChange`, 0 applies them at once. Exempt are the switches that
+    // only stop activity (lock, emergency) since they must act at once during an incident,
+    // and the flash fee and min reserve, which bind borrowers and swappers but not LP funds
+    pub timelock_delay: i64,                         // Seconds between queueing and applying
+    pub pending_change: Option<PendingChange>,
+
+    // Layout version, new fields go after it
+    pub version: u8,
+
+    // Permissioned pools only pair mints from `mint_allowlist`
+    pub permissioned: bool,
+    pub mint_allowlist: Pubkey,
+
+    // Swaps must leave at least this much in the output vault, 0 disables the floor
+    pub min_reserve: u64,
+
+    // Withheld transfer fees go to the fee vault PDA unless the pool pays `fee_destination` directly
+    pub direct_fee_collection: bool,
+    pub collected_fees_x: u64,                       // Running total collected for mint X
+    pub collected_fees_y: u64,                       // Running total collected for mint Y
+
+    // Protocol fee switch, when off the whole trading fee stays with LPs
+    pub fee_enabled: bool,
+
+    // Lifetime swap stats, volume counts each side's tokens in and out of the curve
+    pub cumulative_volume_x: u128,
+    pub cumulative_volume_y: u128,
+    pub swap_count: u64,
+
+    // Emergency mode, only `emergency_withdraw` and admin updates run while set
+    pub emergency: bool,
+
+    // Range a transfer hook's dynamic fee is clamped into before a swap uses it
+    pub min_dynamic_fee_bps: u16,
+    pub max_dynamic_fee_bps: u16,
+
+    // Protocol LP minted on sqrt(k) growth, `mint_protocol_lp` is off while the share is 0
+    pub protocol_lp_destination: Pubkey,             // Owner of the LP account receiving it
+    pub protocol_lp_share_bps: u16,                  // Share of the growth taken
+    pub last_k_sqrt: u128,                           // sqrt(reserve_x · reserve_y) at the last mint
+    pub last_k_lp_supply: u64,                       // LP supply at the last mint
+
+    // Swaps pay the curve output and the trader bears the output mint's transfer fee,
+    // otherwise the pool grosses the payout up and absorbs the fee
+    pub pass_output_fee: bool,
+
+    // Set during an incident: swaps stop reading hook fees and `emergency_withdraw`
+    // leaves hooked mints in the vaults instead of running their hooks
+    pub hook_unsafe: bool,
+}
+
+/// Layout of pools created before `Config` was versioned
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, InitSpace)]
+pub struct ConfigV0 {
+    pub seed: u64,
+    pub authority: Option<Pubkey>,
+    pub mint_x: Pubkey,
+    pub mint_y: Pubkey,
+    pub fee: u16,
+    pub locked: bool,
+    pub lp_bump: u8,
+    pub config_bump: u8,
+    pub fee_destination: Pubkey,
+    pub default_transfer_fee_basis_points: u16,
+    pub default_transfer_fee_max: u64,
+    pub fee_withdraw_authority: Pubkey,
+    pub default_hook_program: Option<Pubkey>,
+    pub supports_transfer_fees: bool,
+    pub supports_transfer_hooks: bool,
+    pub supports_metadata: bool,
+    pub supports_interest_bearing: bool,
+    #[max_len(10)]
+    pub approved_hook_programs: Vec<Pubkey>,
+}
+
+/// Authority change subject to the pool's timelock
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, InitSpace)]
+pub enum ConfigChange {
+    TransferFeeConfig { basis_points: u16, max_fee: u64 },
+    FeeDestination { destination: Pubkey },
+    HookProgram { program: Option<Pubkey> },
+    TradingFee { fee: u16 },
+    ProtocolFee { basis_points: u16, destination: Pubkey },
+    ProtocolLp { destination: Pubkey, share_bps: u16 },
+    FeeEnabled { enabled: bool },
+    DynamicFeeBounds { min_bps: u16, max_bps: u16 },
+    DirectFeeCollection { enabled: bool },
+    StrictHooks { enabled: bool },
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, InitSpace)]
+pub struct PendingChange {
+    pub change: ConfigChange,
+    pub effective_at: i64,
+}
+
+impl Config {
+    /// Fail until an older pool has gone through `migrate_config`
+    pub fn check_version(&self) -> Result<()> {
+        require!(self.version == CONFIG_VERSION, AmmError::ConfigOutdated);
+        Ok(())
+    }
+
+    /// Require `signer` to be the pool authority
+    /// Pools created without one are permissionless for good and fail with `NoAuthoritySet`
+    pub fn check_authority(&self, signer: &Pubkey) -> Result<()> {
+        let authority = self.authority.ok_or(AmmError::NoAuthoritySet)?;
+        require_keys_eq!(authority, *signer, AmmError::InvalidAuthority);
+        Ok(())
+    }
+
+    /// Upgrade raw config account data of an older layout to the current one
+    ///
+    /// Fields are only ever appended, so older data padded with zeros decodes with every
+    /// newer field zeroed. Fields whose default isn't zero are backfilled per version,
+    /// with the tracked reserves taken from the vaults.
+    pub fn migrate(data: &[u8], reserve_x: u64, reserve_y: u64, now: i64) -> Result<Self> {
+        require!(
+            data.len() >= 8 && data[..8] == *Config::DISCRIMINATOR,
+            AmmError::InvalidAccountData
+        );
+
+        let mut padded = data.to_vec();
+        padded.resize(std::cmp::max(data.len(), 8 + Config::INIT_SPACE), 0);
+        let mut config = Config::try_deserialize(&mut &padded[..])?;
+        require!(config.version < CONFIG_VERSION, AmmError::ConfigAlreadyMigrated);
+
+        // Version 1: protocol fee, tracked reserves, flash loans, oracle, timelock
+        if config.version < 1 {
+            config.protocol_fee_destination = config.fee_destination;
+            config.reserve_x = reserve_x;
+            config.reserve_y = reserve_y;
+            config.flash_fee_bps = DEFAULT_FLASH_FEE_BPS;
+            config.last_oracle_timestamp = now;
+        }
+
+        // Version 2: mint allowlist, pools stay open
+        // Version 3: minimum reserve floor, disabled
+
+        // Version 4: fee vault, older pools keep collecting into `fee_destination`
+        if config.version < 4 {
+            config.direct_fee_collection = true;
+        }
+
+        // Version 5: protocol fee switch, on so existing protocol fees keep flowing
+        if config.version < 5 {
+            config.fee_enabled = true;
+        }
+
+        // Version 6: swap stats, counted from the migration on
+        // Version 7: emergency mode, off
+
+        // Version 8: dynamic fee bounds, from the pool fee up to the highest trading fee
+        if config.version < 8 {
+            config.min_dynamic_fee_bps = config.fee;
+            config.max_dynamic_fee_bps = MAX_FEE_BPS;
+        }
+
+        // Version 9: protocol LP minting, off
+        // Version 10: output transfer fee pass-through, off so pools keep absorbing it
+        // Version 11: protocol fee changes are timelocked, the account grows to hold them pending
+        // Version 12: hook unsafe flag, off
+
+        config.version = CONFIG_VERSION;
+
+        Ok(config)
+    }
+
+    /// Apply the observed vault balance changes of an instruction to the tracked reserves
+    pub fn apply_vault_deltas(
+        &mut self,
+        before_x: u64,
+        after_x: u64,
+        before_y: u64,
+        after_y: u64,
+    ) -> Result<()> {
+        self.reserve_x = apply_delta(self.reserve_x, before_x, after_x)?;
+        self.reserve_y = apply_delta(self.reserve_y, before_y, after_y)?;
+        Ok(())
+    }
+
+    /// Require the tracked reserves to be backed by the vaults
+    /// Vaults may hold more (donations, pending `skim`) but never less than the pool prices against
+    pub fn check_reserves_backed(&self, vault_x: u64, vault_y: u64) -> Result<()> {
+        require!(
+            self.reserve_x <= vault_x && self.reserve_y <= vault_y,
+            AmmError::ReservesExceedVault
+        );
+        Ok(())
+    }
+
+    /// Mark the pool as inside a mutating instruction, failing if it already is
+    ///
+    /// Defence in depth: the runtime already refuses a CPI back into the pool from a flash loan
+    /// callback or transfer hook. The flag is written with `exit` before those CPIs so it would
+    /// still hold if a nested call got through. Separate instructions never see it set, each one
+    /// clears it before returning and a failed one reverts with the transaction
+    pub fn enter(&mut self) -> Result<()> {
+        require!(!self.locked_for_reentrancy, AmmError::ReentrancyDetected);
+        self.locked_for_reentrancy = true;
+        Ok(())
+    }
+
+    /// Require a swap to leave at least `min_reserve` in its output vault
+    /// Keeps the price defined instead of letting a swap drain one side
+    pub fn check_min_reserve(&self, remaining: u64) -> Result<()> {
+        require!(
+            self.min_reserve == 0 || remaining >= self.min_reserve,
+            AmmError::NoLiquidityInPool
+        );
+        Ok(())
+    }
+
+    /// Clamp a transfer hook's dynamic fee into the pool's configured bounds
+    /// A broken or compromised hook can't take swaps below the floor or above the ceiling
+    pub fn bound_dynamic_fee(&self, fee_bps: u16) -> u16 {
+        fee_bps.max(self.min_dynamic_fee_bps).min(self.max_dynamic_fee_bps)
+    }
+
+    /// Protocol share of a swap charging `fee_bps`, zero while the fee switch is off
+    pub fn protocol_fee_bps_for(&self, fee_bps: u16) -> u16 {
+        if self.fee_enabled {
+            std::cmp::min(self.protocol_fee_bps, fee_bps)
+        } else {
+            0
+        }
+    }
+
+    /// Count a swap of `amount_in` on the input side for `amount_out` on the other
+    pub fn record_swap(&mut self, is_x: bool, amount_in: u64, amount_out: u64) -> Result<()> {
+        let (volume_in, volume_out) = if is_x {
+            (&mut self.cumulative_volume_x, &mut self.cumulative_volume_y)
+        } else {
+            (&mut self.cumulative_volume_y, &mut self.cumulative_volume_x)
+        };
+
+        *volume_in = volume_in
+            .checked_add(amount_in as u128)
+            .ok_or(AmmError::Overflow)?;
+        *volume_out = volume_out
+            .checked_add(amount_out as u128)
+            .ok_or(AmmError::Overflow)?;
+        self.swap_count = self.swap_count.checked_add(1).ok_or(AmmError::Overflow)?;
+
+        Ok(())
+    }
+
+    /// Add withheld transfer fees collected for `mint` to its running total
+    pub fn record_collected_fees(&mut self, mint: &Pubkey, amount: u64) -> Result<()> {
+        let total = if *mint == self.mint_x {
+            &mut self.collected_fees_x
+        } else if *mint == self.mint_y {
+            &mut self.collected_fees_y
+        } else {
+            return err!(AmmError::InvalidToken);
+        };
+
+        *total = total.checked_add(amount).ok_or(AmmError::Overflow)?;
+        Ok(())
+    }
+
+    /// Clear the reentrancy guard at the end of a mutating instruction
+    pub fn leave(&mut self) {
+        self.locked_for_reentrancy = false;
+    }
+
+    /// Accumulate the spot price held since the last update, must run before reserves change
+    /// Consumers derive a TWAP from two snapshots: (cumulative_b - cumulative_a) / (t_b - t_a)
+    pub fn update_oracle(&mut self, now: i64) {
+        let elapsed = now.saturating_sub(self.last_oracle_timestamp);
+
+        // The first update only starts the clock
+        if self.last_oracle_timestamp != 0
+            && elapsed > 0
+            && self.reserve_x > 0
+            && self.reserve_y > 0
+        {
+            let price_x = price_q64(self.reserve_x, self.reserve_y);
+            let price_y = price_q64(self.reserve_y, self.reserve_x);
+
+            self.price_x_cumulative = self
+                .price_x_cumulative
+                .wrapping_add(price_x.wrapping_mul(elapsed as u128));
+            self.price_y_cumulative = self
+                .price_y_cumulative
+                .wrapping_add(price_y.wrapping_mul(elapsed as u128));
+        }
+
+        if elapsed > 0 || self.last_oracle_timestamp == 0 {
+            self.last_oracle_timestamp = now;
+        }
+    }
+}
+
+impl Config {
+    /// Queue `change` behind the timelock, or apply it right away when there is none
+    /// Returns the queued change, replacing any change still pending
+    pub fn queue_change(&mut self, change: ConfigChange, now: i64) -> Result<Option<PendingChange>> {
+        self.check_change(&change)?;
+        if self.timelock_delay == 0 {
+            self.apply_change(&change);
+            return Ok(None);
+        }
+
+        let pending = PendingChange {
+            change,
+            effective_at: now
+                .checked_add(self.timelock_delay)
+                .ok_or(AmmError::Overflow)?,
+        };
+        self.pending_change = Some(pending.clone());
+
+        Ok(Some(pending))
+    }
+
+    /// Apply the pending change once its timelock has elapsed
+    pub fn apply_pending_change(&mut self, now: i64) -> Result<ConfigChange> {
+        let pending = self
+            .pending_change
+            .clone()
+            .ok_or(AmmError::NoPendingChange)?;
+        require!(now >= pending.effective_at, AmmError::TimelockNotElapsed);
+
+        self.check_change(&pending.change)?;
+        self.apply_change(&pending.change);
+        self.pending_change = None;
+
+        Ok(pending.change)
+    }
+
+    /// Reject a change that doesn't fit the rest of the config
+    /// Checked when queued and again when applied, the config may have moved in between
+    fn check_change(&self, change: &ConfigChange) -> Result<()> {
+        match *change {
+            ConfigChange::TradingFee { fee } => {
+                // The protocol share must fit inside the fee, and a hook's fee is bounded around it
+                require!(
+                    fee <= MAX_FEE_BPS
+                        && fee >= self.protocol_fee_bps
+                        && self.min_dynamic_fee_bps <= fee
+                        && fee <= self.max_dynamic_fee_bps,
+                    AmmError::InvalidFee
+                );
+            }
+            ConfigChange::ProtocolFee { basis_points, .. } => {
+                require!(basis_points <= self.fee, AmmError::InvalidFee);
+            }
+            ConfigChange::ProtocolLp { share_bps, .. } => {
+                require!(share_bps <= MAX_PROTOCOL_LP_SHARE_BPS, AmmError::InvalidFee);
+            }
+            ConfigChange::DynamicFeeBounds { min_bps, max_bps } => {
+                // The bounds must keep containing the trading fee
+                require!(
+                    min_bps <= self.fee && self.fee <= max_bps && max_bps <= MAX_FEE_BPS,
+                    AmmError::InvalidFee
+                );
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn apply_change(&mut self, change: &ConfigChange) {
+        match *change {
+            ConfigChange::TransferFeeConfig { basis_points, max_fee } => {
+                self.default_transfer_fee_basis_points = basis_points;
+                self.default_transfer_fee_max = max_fee;
+            }
+            ConfigChange::FeeDestination { destination } => {
+                self.fee_destination = destination;
+            }
+            ConfigChange::HookProgram { program } => {
+                self.default_hook_program = program;
+            }
+            ConfigChange::TradingFee { fee } => {
+                self.fee = fee;
+            }
+            ConfigChange::ProtocolFee { basis_points, destination } => {
+                self.protocol_fee_bps = basis_points;
+                self.protocol_fee_destination = destination;
+            }
+            ConfigChange::ProtocolLp { destination, share_bps } => {
+                // Growth is measured afresh from the next `mint_protocol_lp`
+                self.protocol_lp_destination = destination;
+                self.protocol_lp_share_bps = share_bps;
+                self.last_k_sqrt = 0;
+                self.last_k_lp_supply = 0;
+            }
+            ConfigChange::FeeEnabled { enabled } => {
+                self.fee_enabled = enabled;
+            }
+            ConfigChange::DynamicFeeBounds { min_bps, max_bps } => {
+                self.min_dynamic_fee_bps = min_bps;
+                self.max_dynamic_fee_bps = max_bps;
+            }
+            ConfigChange::DirectFeeCollection { enabled } => {
+                self.direct_fee_collection = enabled;
+            }
+            ConfigChange::StrictHooks { enabled } => {
+                self.strict_hooks = enabled;
+            }
+        }
+    }
+}
+
+fn apply_delta(reserve: u64, before: u64, after: u64) -> Result<u64> {
+    if after >= before {
+        reserve
+            .checked_add(after - before)
+            .ok_or(AmmError::Overflow.into())
+    } else {
+        reserve
+            .checked_sub(before - after)
+            .ok_or(AmmError::Underflow.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bound_dynamic_fee() {
+        let config = Config {
+            min_dynamic_fee_bps: 30,
+            max_dynamic_fee_bps: MAX_FEE_BPS,
+            ..Config::default()
+        };
+
+        // A hook reporting nothing or too much can't move the fee out of the bounds
+        assert_eq!(config.bound_dynamic_fee(0), 30);
+        assert_eq!(config.bound_dynamic_fee(30), 30);
+        assert_eq!(config.bound_dynamic_fee(120), 120);
+        assert_eq!(config.bound_dynamic_fee(MAX_FEE_BPS), MAX_FEE_BPS);
+        assert_eq!(config.bound_dynamic_fee(u16::MAX), MAX_FEE_BPS);
+    }
+
+    #[test]
+    fn test_check_authority() {
+        let authority = Pubkey::new_unique();
+
+        // Initialized with an authority, only that key passes
+        let config = Config { authority: Some(authority), ..Config::default() };
+        assert!(config.check_authority(&authority).is_ok());
+        assert_eq!(
+            config.check_authority(&Pubkey::new_unique()).unwrap_err(),
+            error!(AmmError::InvalidAuthority)
+        );
+
+        // Initialized without one, nobody can act as the authority
+        let config = Config { authority: None, ..Config::default() };
+        assert_eq!(config.check_authority(&authority).unwrap_err(), error!(AmmError::NoAuthoritySet));
+        assert_eq!(
+            config.check_authority(&Pubkey::default()).unwrap_err(),
+            error!(AmmError::NoAuthoritySet)
+        );
+    }
+
+    #[test]
+    fn test_reentrancy_guard() {
+        let mut config = Config::default();
+
+        // Outer instruction takes the guard
+        config.enter().unwrap();
+        assert!(config.locked_for_reentrancy);
+
+        // A nested deposit/withdraw/swap on the same pool sees the persisted flag and fails
+        assert_eq!(
+            config.enter().unwrap_err(),
+            error!(AmmError::ReentrancyDetected)
+        );
+
+        // Once the outer instruction finishes the pool is usable again
+        config.leave();
+        assert!(!config.locked_for_reentrancy);
+        config.enter().unwrap();
+    }
+
+    #[test]
+    fn test_check_reserves_backed() {
+        let config = Config {
+            reserve_x: 1_000,
+            reserve_y: 2_000,
+            ..Default::default()
+        };
+
+        assert!(config.check_reserves_backed(1_000, 2_000).is_ok());
+        // Donations sit above the reserves until synced
+        assert!(config.check_reserves_backed(5_000, 2_001).is_ok());
+        assert_eq!(
+            config.check_reserves_backed(999, 2_000).unwrap_err(),
+            error!(AmmError::ReservesExceedVault)
+        );
+        assert!(config.check_reserves_backed(1_000, 1_999).is_err());
+    }
+
+    #[test]
+    fn test_min_reserve_floor() {
+        let mut config = Config::default();
+
+        // Disabled by default, a swap may take the whole output vault
+        assert!(config.check_min_reserve(0).is_ok());
+
+        config.min_reserve = 1_000;
+        assert!(config.check_min_reserve(1_000).is_ok());
+        assert_eq!(
+            config.check_min_reserve(999).unwrap_err(),
+            error!(AmmError::NoLiquidityInPool)
+        );
+    }
+
+    #[test]
+    fn test_protocol_fee_switch() {
+        let mut config = Config {
+            protocol_fee_bps: 10,
+            ..Default::default()
+        };
+
+        // Switched off, LPs keep the whole fee
+        assert_eq!(config.protocol_fee_bps_for(30), 0);
+
+        config.fee_enabled = true;
+        assert_eq!(config.protocol_fee_bps_for(30), 10);
+        // Never more than the fee actually charged
+        assert_eq!(config.protocol_fee_bps_for(5), 5);
+    }
+
+    #[test]
+    fn test_record_swap() {
+        let mut config = Config::default();
+
+        config.record_swap(true, 1_000, 1_900).unwrap();
+        config.record_swap(false, 500, 240).unwrap();
+
+        assert_eq!(config.cumulative_volume_x, 1_240);
+        assert_eq!(config.cumulative_volume_y, 2_400);
+        assert_eq!(config.swap_count, 2);
+
+        config.swap_count = u64::MAX;
+        assert_eq!(config.record_swap(true, 1, 1).unwrap_err(), error!(AmmError::Overflow));
+    }
+
+    #[test]
+    fn test_record_collected_fees() {
+        let mut config = Config {
+            mint_x: Pubkey::new_unique(),
+            mint_y: Pubkey::new_unique(),
+            ..Default::default()
+        };
+
+        config.record_collected_fees(&config.mint_x.clone(), 100).unwrap();
+        config.record_collected_fees(&config.mint_x.clone(), 50).unwrap();
+        config.record_collected_fees(&config.mint_y.clone(), 7).unwrap();
+        assert_eq!((config.collected_fees_x, config.collected_fees_y), (150, 7));
+
+        // Only the pool's own mints are tracked
+        assert_eq!(
+            config.record_collected_fees(&Pubkey::new_unique(), 1).unwrap_err(),
+            error!(AmmError::InvalidToken)
+        );
+    }
+
+    #[test]
+    fn test_migrate_v0_config() {
+        let legacy = ConfigV0 {
+            seed: 7,
+            authority: Some(Pubkey::new_unique()),
+            mint_x: Pubkey::new_unique(),
+            mint_y: Pubkey::new_unique(),
+            fee: 30,
+            locked: false,
+            lp_bump: 254,
+            config_bump: 253,
+            fee_destination: Pubkey::new_unique(),
+            default_transfer_fee_basis_points: 50,
+            default_transfer_fee_max: 1_000,
+            fee_withdraw_authority: Pubkey::new_unique(),
+            default_hook_program: None,
+            supports_transfer_fees: true,
+            supports_transfer_hooks: false,
+            supports_metadata: false,
+            supports_interest_bearing: false,
+            approved_hook_programs: vec![Pubkey::new_unique(); 4],
+        };
+
+        // Account as allocated by the old initialize, zero padded after the hook list
+        let mut data = vec![0u8; 8 + ConfigV0::INIT_SPACE];
+        data[..8].copy_from_slice(Config::DISCRIMINATOR);
+        legacy.serialize(&mut &mut data[8..]).unwrap();
+        assert!(data.len() < 8 + Config::INIT_SPACE);
+
+        let migrated = Config::migrate(&data, 1_000, 2_000, 100).unwrap();
+        assert_eq!(migrated.version, CONFIG_VERSION);
+        assert_eq!(migrated.seed, legacy.seed);
+        assert_eq!(migrated.authority, legacy.authority);
+        assert_eq!(migrated.fee, legacy.fee);
+        assert_eq!(migrated.config_bump, legacy.config_bump);
+        assert_eq!(migrated.approved_hook_programs, legacy.approved_hook_programs);
+        assert_eq!((migrated.reserve_x, migrated.reserve_y), (1_000, 2_000));
+        assert_eq!(migrated.flash_fee_bps, DEFAULT_FLASH_FEE_BPS);
+        assert_eq!(migrated.last_oracle_timestamp, 100);
+        assert!(migrated.direct_fee_collection);
+        migrated.check_version().unwrap();
+
+        // A version 1 account keeps its own values and only gains the newer fields
+        let mut v1 = migrated.clone();
+        v1.version = 1;
+        v1.reserve_x = 5_000;
+        let mut v1_data = Vec::new();
+        v1.try_serialize(&mut v1_data).unwrap();
+        // permissioned, mint_allowlist, min_reserve, direct_fee_collection, collected_fees_x/y,
+        // fee_enabled, cumulative_volume_x/y, swap_count, emergency, min/max_dynamic_fee_bps,
+        // protocol_lp_destination, protocol_lp_share_bps, last_k_sqrt, last_k_lp_supply, pass_output_fee,
+        // hook_unsafe
+        v1_data.truncate(v1_data.len() - 164);
+        let from_v1 = Config::migrate(&v1_data, 1, 1, 200).unwrap();
+        assert_eq!(from_v1.reserve_x, 5_000);
+        assert_eq!(from_v1.last_oracle_timestamp, 100);
+        assert!(!from_v1.permissioned);
+        assert_eq!(from_v1.min_reserve, 0);
+        assert!(from_v1.direct_fee_collection);
+        assert!(from_v1.fee_enabled);
+        assert!(!from_v1.emergency);
+        assert_eq!(from_v1.min_dynamic_fee_bps, legacy.fee);
+        assert_eq!(from_v1.max_dynamic_fee_bps, MAX_FEE_BPS);
+        assert_eq!(from_v1.protocol_lp_share_bps, 0);
+        assert!(!from_v1.pass_output_fee);
+
+        // Written back at the current size, a second migration is refused
+        let mut upgraded = Vec::new();
+        migrated.try_serialize(&mut upgraded).unwrap();
+        upgraded.resize(8 + Config::INIT_SPACE, 0);
+        assert_eq!(
+            Config::migrate(&upgraded, 0, 0, 0).err().unwrap(),
+            error!(AmmError::ConfigAlreadyMigrated)
+        );
+    }
+
+    #[test]
+    fn test_timelocked_change() {
+        let destination = Pubkey::new_unique();
+        let change = ConfigChange::FeeDestination { destination };
+
+        // Without a delay the change applies immediately
+        let mut config = Config::default();
+        assert_eq!(config.queue_change(change.clone(), 100).unwrap(), None);
+        assert_eq!(config.fee_destination, destination);
+
+        let mut config = Config {
+            timelock_delay: 3_600,
+            ..Default::default()
+        };
+        let pending = config.queue_change(change.clone(), 100).unwrap().unwrap();
+        assert_eq!(pending.effective_at, 3_700);
+        assert_eq!(config.fee_destination, Pubkey::default());
+
+        // Too early: nothing changes and the change stays queued
+        assert_eq!(
+            config.apply_pending_change(3_699).unwrap_err(),
+            error!(AmmError::TimelockNotElapsed)
+        );
+        assert!(config.pending_change.is_some());
+
+        assert_eq!(config.apply_pending_change(3_700).unwrap(), change);
+        assert_eq!(config.fee_destination, destination);
+        assert_eq!(
+            config.apply_pending_change(3_700).unwrap_err(),
+            error!(AmmError::NoPendingChange)
+        );
+
+        // Trading fee changes wait out the same delay
+        config.fee = 30;
+        config.max_dynamic_fee_bps = MAX_FEE_BPS;
+        config.queue_change(ConfigChange::TradingFee { fee: 50 }, 4_000).unwrap();
+        assert_eq!(config.fee, 30);
+        config.apply_pending_change(7_600).unwrap();
+        assert_eq!(config.fee, 50);
+    }
+
+    #[test]
+    fn test_trading_fee_checked_against_bounds() {
+        let mut config = Config {
+            fee: 30,
+            protocol_fee_bps: 10,
+            min_dynamic_fee_bps: 30,
+            max_dynamic_fee_bps: 100,
+            timelock_delay: 3_600,
+            ..Default::default()
+        };
+
+        // Outside the dynamic fee bounds or below the protocol share, rejected when queued
+        for fee in [29, 101, 5, MAX_FEE_BPS + 1] {
+            assert_eq!(
+                config.queue_change(ConfigChange::TradingFee { fee }, 0).unwrap_err(),
+                error!(AmmError::InvalidFee)
+            );
+        }
+        assert!(config.pending_change.is_none());
+
+        // Checked again when applied, the bounds moved after it was queued
+        config.queue_change(ConfigChange::TradingFee { fee: 80 }, 0).unwrap();
+        config.max_dynamic_fee_bps = 60;
+        assert_eq!(config.apply_pending_change(3_600).unwrap_err(), error!(AmmError::InvalidFee));
+        assert_eq!(config.fee, 30);
+        assert!(config.pending_change.is_some());
+
+        config.max_dynamic_fee_bps = 100;
+        config.apply_pending_change(3_600).unwrap();
+        assert_eq!(config.fee, 80);
+    }
+
+    #[test]
+    fn test_dynamic_fee_bounds_timelocked() {
+        let mut config = Config {
+            fee: 30,
+            min_dynamic_fee_bps: 30,
+            max_dynamic_fee_bps: 100,
+            timelock_delay: 3_600,
+            ..Default::default()
+        };
+
+        // Bounds that leave out the trading fee or pass the cap are rejected when queued
+        for (min_bps, max_bps) in [(31, 100), (10, 29), (10, MAX_FEE_BPS + 1)] {
+            assert_eq!(
+                config
+                    .queue_change(ConfigChange::DynamicFeeBounds { min_bps, max_bps }, 0)
+                    .unwrap_err(),
+                error!(AmmError::InvalidFee)
+            );
+        }
+
+        config
+            .queue_change(ConfigChange::DynamicFeeBounds { min_bps: 10, max_bps: 50 }, 0)
+            .unwrap();
+        assert_eq!((config.min_dynamic_fee_bps, config.max_dynamic_fee_bps), (30, 100));
+
+        // Checked again when applied, the fee moved after it was queued
+        config.fee = 60;
+        assert_eq!(config.apply_pending_change(3_600).unwrap_err(), error!(AmmError::InvalidFee));
+        config.fee = 30;
+        config.apply_pending_change(3_600).unwrap();
+        assert_eq!((config.min_dynamic_fee_bps, config.max_dynamic_fee_bps), (10, 50));
+
+        // The fee switches wait out the timelock too
+        config.queue_change(ConfigChange::StrictHooks { enabled: true }, 0).unwrap();
+        assert!(!config.strict_hooks);
+        config.apply_pending_change(3_600).unwrap();
+        assert!(config.strict_hooks);
+    }
+
+    #[test]
+    fn test_protocol_fee_timelocked() {
+        let destination = Pubkey::new_unique();
+        let mut config = Config {
+            fee: 30,
+            timelock_delay: 3_600,
+            ..Default::default()
+        };
+
+        // More than the trading fee is rejected up front
+        assert_eq!(
+            config
+                .queue_change(ConfigChange::ProtocolFee { basis_points: 31, destination }, 0)
+                .unwrap_err(),
+            error!(AmmError::InvalidFee)
+        );
+
+        // Neither the share nor where it goes changes before the delay
+        let change = ConfigChange::ProtocolFee { basis_points: 10, destination };
+        config.queue_change(change.clone(), 0).unwrap();
+        assert_eq!((config.protocol_fee_bps, config.protocol_fee_destination), (0, Pubkey::default()));
+        assert_eq!(config.apply_pending_change(3_600).unwrap(), change);
+        assert_eq!((config.protocol_fee_bps, config.protocol_fee_destination), (10, destination));
+
+        // A queued share is checked against the fee it will apply to
+        config.queue_change(ConfigChange::ProtocolFee { basis_points: 30, destination }, 4_000).unwrap();
+        config.fee = 20;
+        assert_eq!(config.apply_pending_change(7_600).unwrap_err(), error!(AmmError::InvalidFee));
+        assert_eq!(config.protocol_fee_bps, 10);
+    }
+
+    #[test]
+    fn test_protocol_lp_timelocked() {
+        let destination = Pubkey::new_unique();
+        let mut config = Config {
+            timelock_delay: 3_600,
+            last_k_sqrt: 1_000,
+            last_k_lp_supply: 500,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            config
+                .queue_change(
+                    ConfigChange::ProtocolLp { destination, share_bps: MAX_PROTOCOL_LP_SHARE_BPS + 1 },
+                    0
+                )
+                .unwrap_err(),
+            error!(AmmError::InvalidFee)
+        );
+
+        // Growth keeps being measured from the last mint until the change applies
+        config.queue_change(ConfigChange::ProtocolLp { destination, share_bps: 1_000 }, 0).unwrap();
+        assert_eq!(config.protocol_lp_share_bps, 0);
+        assert_eq!((config.last_k_sqrt, config.last_k_lp_supply), (1_000, 500));
+
+        config.apply_pending_change(3_600).unwrap();
+        assert_eq!((config.protocol_lp_destination, config.protocol_lp_share_bps), (destination, 1_000));
+        assert_eq!((config.last_k_sqrt, config.last_k_lp_supply), (0, 0));
+    }
+
+    #[test]
+    fn test_update_oracle() {
+        let mut config = Config {
+            reserve_x: 1_000,
+            reserve_y: 2_000,
+            ..Default::default()
+        };
+
+        // First update only records the timestamp
+        config.update_oracle(100);
+        assert_eq!(config.price_x_cumulative, 0);
+        assert_eq!(config.last_oracle_timestamp, 100);
+
+        // Same-second updates add nothing
+        config.update_oracle(100);
+        assert_eq!(config.price_x_cumulative, 0);
+
+        // 10 seconds at 2 Y per X and 0.5 X per Y
+        config.update_oracle(110);
+        assert_eq!(config.price_x_cumulative, (2u128 << 64) * 10);
+        assert_eq!(config.price_y_cumulative, (1u128 << 63) * 10);
+        assert_eq!(config.last_oracle_timestamp, 110);
+
+        // Price change applies only from the next interval
+        config.reserve_y = 1_000;
+        config.update_oracle(115);
+        assert_eq!(config.price_x_cumulative, (2u128 << 64) * 10 + (1u128 << 64) * 5);
+    }
 }
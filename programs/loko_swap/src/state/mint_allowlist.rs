@@ -0,0 +1,94 @@
+use anchor_lang::prelude::*;
+
+use crate::error::AmmError;
+
+/// Mints a permissioned pool may pair, managed by the allowlist's authority
+#[account]
+#[derive(InitSpace)]
+pub struct MintAllowlist {
+    pub authority: Pubkey,
+    pub bump: u8,
+    #[max_len(32)]
+    pub mints: Vec<Pubkey>,
+}
+
+impl MintAllowlist {
+    pub const MAX_MINTS: usize = 32;
+
+    pub fn contains(&self, mint: &Pubkey) -> bool {
+        self.mints.contains(mint)
+    }
+
+    /// Adding a mint that is already listed is a no-op
+    pub fn add(&mut self, mint: Pubkey) -> Result<()> {
+        if self.contains(&mint) {
+            return Ok(());
+        }
+        require!(self.mints.len() < Self::MAX_MINTS, AmmError::AllowlistFull);
+        self.mints.push(mint);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, mint: &Pubkey) -> Result<()> {
+        let index = self
+            .mints
+            .iter()
+            .position(|m| m == mint)
+            .ok_or(AmmError::MintNotAllowed)?;
+        self.mints.swap_remove(index);
+        Ok(())
+    }
+
+    /// Both sides of a permissioned pool must be listed
+    pub fn check_pair(&self, mint_x: &Pubkey, mint_y: &Pubkey) -> Result<()> {
+        require!(
+            self.contains(mint_x) && self.contains(mint_y),
+            AmmError::MintNotAllowed
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_pair() {
+        let (a, b, c) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let mut allowlist = MintAllowlist {
+            authority: Pubkey::new_unique(),
+            bump: 255,
+            mints: vec![],
+        };
+
+        allowlist.add(a).unwrap();
+        allowlist.add(b).unwrap();
+        allowlist.add(b).unwrap();
+        assert_eq!(allowlist.mints.len(), 2);
+
+        assert!(allowlist.check_pair(&a, &b).is_ok());
+        assert_eq!(allowlist.check_pair(&a, &c).unwrap_err(), error!(AmmError::MintNotAllowed));
+
+        allowlist.remove(&b).unwrap();
+        assert_eq!(allowlist.check_pair(&a, &b).unwrap_err(), error!(AmmError::MintNotAllowed));
+        assert!(allowlist.remove(&b).is_err());
+    }
+
+    #[test]
+    fn test_allowlist_full() {
+        let mut allowlist = MintAllowlist {
+            authority: Pubkey::new_unique(),
+            bump: 255,
+            mints: vec![],
+        };
+        for _ in 0..MintAllowlist::MAX_MINTS {
+            allowlist.add(Pubkey::new_unique()).unwrap();
+        }
+
+        assert_eq!(
+            allowlist.add(Pubkey::new_unique()).unwrap_err(),
+            error!(AmmError::AllowlistFull)
+        );
+    }
+}
@@ -1,3 +1,4 @@
 pub mod config;
+pub mod mint_allowlist;
 pub use config::*;
-
+pub use mint_allowlist::*;
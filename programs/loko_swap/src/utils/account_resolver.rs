@@ -0,0 +1,296 @@
+use anchor_lang::{prelude::*, solana_program::instruction::AccountMeta};
+use spl_tlv_account_resolution::{account::ExtraAccountMeta, state::ExtraAccountMetaList};
+use spl_transfer_hook_interface::{
+    get_extra_account_metas_address,
+    instruction::{ExecuteInstruction, TransferHookInstruction},
+};
+use spl_type_length_value::state::TlvStateBorrowed;
+
+use crate::{
+    constants::{MAX_EXTRA_ACCOUNT_METAS, MAX_EXTRA_METAS_ACCOUNT_LEN},
+    error::AmmError,
+};
+
+/// Parse the Execute `ExtraAccountMeta` entries stored in a hook's extra-account-metas account
+///
+/// The account is supplied by whoever builds the transaction, so its size and entry count are
+/// bounded before anything is iterated. Malformed or oversized data fails with `InvalidToken`.
+pub fn parse_extra_account_metas(data: &[u8]) -> Result<Vec<ExtraAccountMeta>> {
+    require!(
+        data.len() <= MAX_EXTRA_METAS_ACCOUNT_LEN as usize,
+        AmmError::InvalidToken
+    );
+
+    let state = TlvStateBorrowed::unpack(data).map_err(|_| AmmError::InvalidToken)?;
+    let extra_meta_list = ExtraAccountMetaList::unpack_with_tlv_state::<ExecuteInstruction>(&state)
+        .map_err(|_| AmmError::InvalidToken)?;
+
+    let extra_metas = extra_meta_list.data();
+    require!(
+        extra_metas.len() <= MAX_EXTRA_ACCOUNT_METAS as usize,
+        AmmError::InvalidToken
+    );
+
+    Ok(extra_metas.to_vec())
+}
+
+/// Check that the provided extra-account-metas account is the hook's PDA for `mint`
+/// A substituted account could otherwise make the hook run with crafted accounts
+pub fn validate_extra_account_metas_address(
+    hook_program_id: &Pubkey,
+    mint: &Pubkey,
+    provided: &Pubkey,
+) -> Result<()> {
+    let extra_metas_address = get_extra_account_metas_address(mint, hook_program_id);
+    require_keys_eq!(*provided, extra_metas_address, AmmError::TransferHookNotFound);
+    Ok(())
+}
+
+/// Check that `remaining_accounts` carries what a hook transfer of `mint` needs:
+/// the hook program, its extra-account-metas PDA and as many accounts as the metas list
+pub fn validate_hook_accounts(
+    hook_program_id: &Pubkey,
+    mint: &Pubkey,
+    remaining_accounts: &[AccountInfo],
+) -> Result<()> {
+    require!(
+        remaining_accounts.iter().any(|account| account.key == hook_program_id),
+        AmmError::TransferHookNotFound
+    );
+
+    let required = required_hook_accounts(hook_program_id, mint, remaining_accounts)?;
+    require!(
+        remaining_accounts.len() >= required,
+        AmmError::HookAccountsMissing
+    );
+
+    Ok(())
+}
+
+/// Number of `remaining_accounts` a hook transfer of `mint` needs:
+/// the extra accounts plus the hook program and the extra-account-metas account
+pub fn required_hook_accounts(
+    hook_program_id: &Pubkey,
+    mint: &Pubkey,
+    remaining_accounts: &[AccountInfo],
+) -> Result<usize> {
+    let extra_metas_address = get_extra_account_metas_address(mint, hook_program_id);
+    let extra_metas_account = remaining_accounts
+        .iter()
+        .find(|account| account.key == &extra_metas_address)
+        .ok_or(AmmError::HookAccountsMissing)?;
+    let extra_metas = parse_extra_account_metas(&extra_metas_account.try_borrow_data()?)?;
+
+    Ok(extra_metas.len() + 2)
+}
+
+/// Materialize `ExtraAccountMeta` entries into concrete `AccountMeta`s
+///
+/// `base_accounts` are the accounts of the Execute instruction the metas index into:
+/// source, mint, destination, authority and the extra-account-metas account.
+/// Each resolved account is appended so later metas can refer to earlier ones.
+/// `account_data` supplies the data for seeds derived from account data.
+pub fn resolve_extra_account_metas(
+    extra_metas: &[ExtraAccountMeta],
+    hook_program_id: &Pubkey,
+    instruction_data: &[u8],
+    base_accounts: &[AccountMeta],
+    account_data: impl Fn(&Pubkey) -> Option<Vec<u8>>,
+) -> Result<Vec<AccountMeta>> {
+    let mut keys: Vec<Pubkey> = base_accounts.iter().map(|meta| meta.pubkey).collect();
+    let mut data: Vec<Option<Vec<u8>>> = keys.iter().map(&account_data).collect();
+    let mut resolved = Vec::with_capacity(extra_metas.len());
+
+    for extra_meta in extra_metas {
+        let meta = extra_meta.resolve(instruction_data, hook_program_id, |index| {
+            keys.get(index)
+                .map(|key| (key, data.get(index).and_then(|d| d.as_deref())))
+        })?;
+
+        data.push(account_data(&meta.pubkey));
+        keys.push(meta.pubkey);
+        resolved.push(meta);
+    }
+
+    Ok(resolved)
+}
+
+/// Resolve the extra accounts a hook's Execute instruction needs for a transfer of `amount`
+/// Returns the matching account infos from `remaining_accounts` in the order the hook expects,
+/// followed by the hook program and the extra-account-metas account
+pub fn resolve_hook_execution_accounts<'info>(
+    hook_program: &AccountInfo<'info>,
+    source: &AccountInfo<'info>,
+    mint: &AccountInfo<'info>,
+    destination: &AccountInfo<'info>,
+    authority: &AccountInfo<'info>,
+    extra_account_metas: &AccountInfo<'info>,
+    amount: u64,
+    remaining_accounts: &[AccountInfo<'info>],
+) -> Result<Vec<AccountInfo<'info>>> {
+    validate_extra_account_metas_address(hook_program.key, mint.key, extra_account_metas.key)?;
+    let extra_metas = parse_extra_account_metas(&extra_account_metas.try_borrow_data()?)?;
+
+    let base_infos = [source, mint, destination, authority, extra_account_metas];
+    let base_accounts: Vec<AccountMeta> = base_infos
+        .iter()
+        .map(|info| AccountMeta::new_readonly(info.key(), false))
+        .collect();
+
+    let find_info = |key: &Pubkey| {
+        base_infos
+            .iter()
+            .copied()
+            .chain(remaining_accounts.iter())
+            .find(|info| info.key == key)
+    };
+
+    let instruction_data = TransferHookInstruction::Execute { amount }.pack();
+    let resolved = resolve_extra_account_metas(
+        &extra_metas,
+        hook_program.key,
+        &instruction_data,
+        &base_accounts,
+        |key| find_info(key).and_then(|info| info.try_borrow_data().ok().map(|d| d.to_vec())),
+    )?;
+
+    let mut account_infos = Vec::with_capacity(resolved.len() + 2);
+    for meta in &resolved {
+        let info = find_info(&meta.pubkey).ok_or(AmmError::TransferHookNotFound)?;
+        account_infos.push(info.clone());
+    }
+    account_infos.push(hook_program.clone());
+    account_infos.push(extra_account_metas.clone());
+
+    Ok(account_infos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spl_tlv_account_resolution::seeds::Seed;
+
+    #[test]
+    fn test_resolve_extra_account_metas_round_trip() {
+        let hook_program_id = Pubkey::new_unique();
+        let other_program_id = Pubkey::new_unique();
+        let fixed_account = Pubkey::new_unique();
+        let base_accounts: Vec<AccountMeta> = (0..5)
+            .map(|_| AccountMeta::new_readonly(Pubkey::new_unique(), false))
+            .collect();
+
+        let extra_metas = vec![
+            ExtraAccountMeta::new_with_pubkey(&fixed_account, false, false).unwrap(),
+            ExtraAccountMeta::new_with_seeds(
+                &[Seed::Literal { bytes: b"fee_stats".to_vec() }],
+                false,
+                true,
+            )
+            .unwrap(),
+            ExtraAccountMeta::new_with_pubkey(&other_program_id, false, false).unwrap(),
+            // PDA of the program resolved at index 7, seeded by the source and mint
+            ExtraAccountMeta::new_external_pda_with_seeds(
+                7,
+                &[Seed::AccountKey { index: 0 }, Seed::AccountKey { index: 1 }],
+                false,
+                true,
+            )
+            .unwrap(),
+        ];
+
+        let mut buffer = vec![0u8; ExtraAccountMetaList::size_of(extra_metas.len()).unwrap()];
+        ExtraAccountMetaList::init::<ExecuteInstruction>(&mut buffer, &extra_metas).unwrap();
+
+        let parsed = parse_extra_account_metas(&buffer).unwrap();
+        assert_eq!(parsed, extra_metas);
+
+        let instruction_data = TransferHookInstruction::Execute { amount: 1_000 }.pack();
+        let resolved = resolve_extra_account_metas(
+            &parsed,
+            &hook_program_id,
+            &instruction_data,
+            &base_accounts,
+            |_| None,
+        )
+        .unwrap();
+
+        let (fee_stats, _) = Pubkey::find_program_address(&[b"fee_stats"], &hook_program_id);
+        let (external, _) = Pubkey::find_program_address(
+            &[base_accounts[0].pubkey.as_ref(), base_accounts[1].pubkey.as_ref()],
+            &other_program_id,
+        );
+
+        assert_eq!(resolved.len(), 4);
+        assert_eq!(resolved[0], AccountMeta::new_readonly(fixed_account, false));
+        assert_eq!(resolved[1], AccountMeta::new(fee_stats, false));
+        assert_eq!(resolved[2].pubkey, other_program_id);
+        assert_eq!(resolved[3], AccountMeta::new(external, false));
+    }
+
+    #[test]
+    fn test_validate_extra_account_metas_address() {
+        let hook_program_id = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let expected = get_extra_account_metas_address(&mint, &hook_program_id);
+
+        assert!(validate_extra_account_metas_address(&hook_program_id, &mint, &expected).is_ok());
+
+        // A metas account for another mint is rejected
+        let other = get_extra_account_metas_address(&Pubkey::new_unique(), &hook_program_id);
+        assert!(validate_extra_account_metas_address(&hook_program_id, &mint, &other).is_err());
+    }
+
+    #[test]
+    fn test_parse_extra_account_metas_rejects_malformed_data() {
+        let extra_metas: Vec<ExtraAccountMeta> = (0..3)
+            .map(|_| ExtraAccountMeta::new_with_pubkey(&Pubkey::new_unique(), false, true).unwrap())
+            .collect();
+        let mut buffer = vec![0u8; ExtraAccountMetaList::size_of(extra_metas.len()).unwrap()];
+        ExtraAccountMetaList::init::<ExecuteInstruction>(&mut buffer, &extra_metas).unwrap();
+
+        // Every truncation fails cleanly instead of panicking or reading past the end
+        for len in 0..buffer.len() {
+            assert_eq!(
+                parse_extra_account_metas(&buffer[..len]).unwrap_err(),
+                error!(AmmError::InvalidToken),
+                "truncated to {len} bytes"
+            );
+        }
+
+        // Trailing zero padding is still a valid account
+        let mut padded = buffer.clone();
+        padded.resize(buffer.len() + 64, 0);
+        assert_eq!(parse_extra_account_metas(&padded).unwrap(), extra_metas);
+
+        // Oversized accounts are rejected before parsing
+        let mut oversized = buffer.clone();
+        oversized.resize(MAX_EXTRA_METAS_ACCOUNT_LEN as usize + 1, 0);
+        assert_eq!(parse_extra_account_metas(&oversized).unwrap_err(), error!(AmmError::InvalidToken));
+
+        // A list longer than the resolver accepts
+        let too_many: Vec<ExtraAccountMeta> = (0..=MAX_EXTRA_ACCOUNT_METAS)
+            .map(|_| ExtraAccountMeta::new_with_pubkey(&Pubkey::new_unique(), false, false).unwrap())
+            .collect();
+        let mut long = vec![0u8; ExtraAccountMetaList::size_of(too_many.len()).unwrap()];
+        ExtraAccountMetaList::init::<ExecuteInstruction>(&mut long, &too_many).unwrap();
+        assert_eq!(parse_extra_account_metas(&long).unwrap_err(), error!(AmmError::InvalidToken));
+
+        // Corrupted lengths and pseudo-random bytes never panic
+        let mut corrupted = buffer.clone();
+        corrupted[8..12].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(parse_extra_account_metas(&corrupted).is_err());
+
+        let mut seed = 0x2545_f491_4f6c_dd1du64;
+        for len in [1, 12, 16, 51, 128, 512] {
+            let garbage: Vec<u8> = (0..len)
+                .map(|_| {
+                    seed ^= seed << 13;
+                    seed ^= seed >> 7;
+                    seed ^= seed << 17;
+                    seed as u8
+                })
+                .collect();
+            let _ = parse_extra_account_metas(&garbage);
+        }
+    }
+}
@@ -0,0 +1,446 @@
+use anchor_lang::prelude::*;
+
+use crate::error::AmmError;
+
+/// Integer square root (floor) using Newton's method
+pub fn integer_sqrt(value: u128) -> u128 {
+    if value < 2 {
+        return value;
+    }
+
+    let mut x = value;
+    let mut y = value / 2 + value % 2;
+    while y < x {
+        x = y;
+        y = (x + value / x) / 2;
+    }
+    x
+}
+
+/// `a * b / denominator` rounded down
+/// Fails on a zero denominator or a result that doesn't fit in a u64
+pub fn mul_div_floor(a: u64, b: u64, denominator: u64) -> Result<u64> {
+    require!(denominator > 0, AmmError::MathOverflow);
+
+    let value = a as u128 * b as u128 / denominator as u128;
+    u64::try_from(value).map_err(|_| AmmError::MathOverflow.into())
+}
+
+/// `a * b / denominator` rounded up
+/// Fails on a zero denominator or a result that doesn't fit in a u64
+pub fn mul_div_ceil(a: u64, b: u64, denominator: u64) -> Result<u64> {
+    require!(denominator > 0, AmmError::MathOverflow);
+
+    let value = (a as u128 * b as u128).div_ceil(denominator as u128);
+    u64::try_from(value).map_err(|_| AmmError::MathOverflow.into())
+}
+
+/// Price of A in units of B as a Q64.64 fixed-point number, 0 when `reserve_a` is empty
+pub fn price_q64(reserve_a: u64, reserve_b: u64) -> u128 {
+    if reserve_a == 0 {
+        return 0;
+    }
+    ((reserve_b as u128) << 64) / reserve_a as u128
+}
+
+/// Price of one whole X token in whole Y tokens as a Q64.64 fixed-point number
+/// Scales the raw reserve ratio by 10^(decimals_x - decimals_y)
+pub fn spot_price_q64(reserve_x: u64, reserve_y: u64, decimals_x: u8, decimals_y: u8) -> Result<u128> {
+    require!(reserve_x > 0 && reserve_y > 0, AmmError::NoLiquidityInPool);
+
+    if decimals_x >= decimals_y {
+        let scale = 10u128
+            .checked_pow((decimals_x - decimals_y) as u32)
+            .ok_or(AmmError::MathOverflow)?;
+        // Scale quotient and remainder separately so the floor is exact
+        let numerator = (reserve_y as u128) << 64;
+        let quotient = numerator / reserve_x as u128;
+        let remainder = numerator % reserve_x as u128;
+        quotient
+            .checked_mul(scale)
+            .and_then(|whole| {
+                let fraction = remainder.checked_mul(scale)? / reserve_x as u128;
+                whole.checked_add(fraction)
+            })
+            .ok_or(AmmError::MathOverflow.into())
+    } else {
+        // Scale the denominator instead so the division happens once
+        let denominator = 10u128
+            .checked_pow((decimals_y - decimals_x) as u32)
+            .and_then(|scale| scale.checked_mul(reserve_x as u128))
+            .ok_or(AmmError::MathOverflow)?;
+        Ok(((reserve_y as u128) << 64) / denominator)
+    }
+}
+
+/// Portion of `amount_in` to swap so the remainder and the swap output match the post-swap pool ratio
+///
+/// Solves g·s² + r (F + g)·s - a·r·F = 0 with F = 10000 and g = F - `fee_bps`, in the
+/// cancellation-free form s = 2·a·F / (sqrt((F + g)² + 4·g·F·a / r) + (F + g)),
+/// with the square root taken in Q32 fixed point
+pub fn optimal_swap_amount(reserve_in: u64, amount_in: u64, fee_bps: u16) -> Result<u64> {
+    require!(fee_bps < 10_000, AmmError::InvalidFee);
+    require!(reserve_in > 0, AmmError::NoLiquidityInPool);
+
+    let r = reserve_in as u128;
+    let a = amount_in as u128;
+    let f_total = 10_000u128;
+    let g = f_total - fee_bps as u128;
+    let f_plus_g = f_total + g;
+
+    // 4·g·F·a / r in Q64, split into quotient and remainder to keep the precision
+    let numerator = 4 * g * f_total * a;
+    let ratio_q64 = (numerator / r)
+        .checked_shl(64)
+        .filter(|v| v >> 64 == numerator / r)
+        .and_then(|v| v.checked_add(((numerator % r) << 64) / r))
+        .ok_or(AmmError::MathOverflow)?;
+
+    let root_q32 = integer_sqrt(
+        ((f_plus_g * f_plus_g) << 64)
+            .checked_add(ratio_q64)
+            .ok_or(AmmError::MathOverflow)?,
+    );
+    let denominator = root_q32
+        .checked_add(f_plus_g << 32)
+        .ok_or(AmmError::MathOverflow)?;
+
+    let swap_amount = ((2 * a * f_total) << 32) / denominator;
+
+    Ok(std::cmp::min(swap_amount, a) as u64)
+}
+
+/// Price impact of a trade in basis points, comparing the execution price `amount_out / amount_in`
+/// against the pre-trade spot price `reserve_out / reserve_in`
+pub fn price_impact_bps(
+    amount_in: u64,
+    amount_out: u64,
+    reserve_in: u64,
+    reserve_out: u64,
+) -> Result<u64> {
+    require!(reserve_in > 0 && reserve_out > 0, AmmError::NoLiquidityInPool);
+
+    // Output the trade would receive at the spot price
+    let spot_out = amount_in as u128 * reserve_out as u128 / reserve_in as u128;
+    if spot_out == 0 || amount_out as u128 >= spot_out {
+        return Ok(0);
+    }
+
+    let impact = (spot_out - amount_out as u128)
+        .checked_mul(10_000)
+        .ok_or(AmmError::MathOverflow)?
+        / spot_out;
+
+    Ok(impact as u64)
+}
+
+/// Tokens of one side required to mint `lp` against `reserve`, rounded up in favour of the pool
+pub fn deposit_amount_for_lp(reserve: u64, supply: u64, lp: u64) -> Result<u64> {
+    require!(supply > 0, AmmError::NoLiquidityInPool);
+
+    mul_div_ceil(reserve, lp, supply)
+}
+
+/// Tokens of one side paid out for burning `lp` against `reserve`, rounded down in favour of the pool
+pub fn withdraw_amount_for_lp(reserve: u64, supply: u64, lp: u64) -> Result<u64> {
+    require!(supply > 0, AmmError::NoLiquidityInPool);
+    require!(lp <= supply, AmmError::InsufficientFunds);
+
+    mul_div_floor(reserve, lp, supply)
+}
+
+/// LP the first deposit of `net_x` and `net_y` is worth, the geometric mean sqrt(x·y)
+/// The product is taken in u128, where it can't overflow, and the root always fits a u64
+pub fn initial_lp_amount(net_x: u64, net_y: u64) -> Result<u64> {
+    let lp = integer_sqrt(net_x as u128 * net_y as u128);
+    u64::try_from(lp).map_err(|_| AmmError::MathOverflow.into())
+}
+
+/// LP minted for up to `net_x`/`net_y` deposited against the reserves, with the amounts it takes
+///
+/// The side giving fewer LP tokens binds and the other side is cut down to the pool ratio,
+/// rounding the amounts up in favour of the pool. An empty pool mints sqrt(x·y).
+pub fn lp_for_exact_amounts(
+    net_x: u64,
+    net_y: u64,
+    reserve_x: u64,
+    reserve_y: u64,
+    supply: u64,
+) -> Result<(u64, u64, u64)> {
+    if supply == 0 {
+        require!(reserve_x == 0 && reserve_y == 0, AmmError::NoLiquidityInPool);
+        return Ok((initial_lp_amount(net_x, net_y)?, net_x, net_y));
+    }
+    require!(reserve_x > 0 && reserve_y > 0, AmmError::NoLiquidityInPool);
+
+    let lp_from_x = mul_div_floor(net_x, supply, reserve_x)?;
+    let lp_from_y = mul_div_floor(net_y, supply, reserve_y)?;
+    let lp = std::cmp::min(lp_from_x, lp_from_y);
+
+    // ceil(lp · reserve / supply) never exceeds the net amount of the binding side
+    let x = mul_div_ceil(lp, reserve_x, supply)?;
+    let y = mul_div_ceil(lp, reserve_y, supply)?;
+
+    Ok((lp, x, y))
+}
+
+/// LP to mint so the protocol owns `share_bps` of the sqrt(k) growth since the last mint
+///
+/// The last root is first scaled to the current supply, so deposits and withdrawals at the
+/// pool ratio don't count as growth. Minting `m` against the new root `r` and scaled last root
+/// `l` gives the protocol `m / (supply + m) = share · (r - l) / r`, which solves to
+/// `supply · (r - l) / ((1/share - 1) · r + l)`. Every rounding goes against the protocol.
+pub fn protocol_lp_for_growth(
+    supply: u64,
+    root_k: u128,
+    last_root_k: u128,
+    last_supply: u64,
+    share_bps: u16,
+) -> Result<u64> {
+    if supply == 0 || last_supply == 0 || last_root_k == 0 || share_bps == 0 {
+        return Ok(0);
+    }
+    require!(share_bps < 10_000, AmmError::InvalidFee);
+
+    let last_root_k = (last_root_k * supply as u128).div_ceil(last_supply as u128);
+    if root_k <= last_root_k {
+        return Ok(0);
+    }
+
+    let share = share_bps as u128;
+    let denominator = ((10_000 - share) * root_k)
+        .div_ceil(share)
+        .checked_add(last_root_k)
+        .ok_or(AmmError::MathOverflow)?;
+    let minted = supply as u128 * (root_k - last_root_k) / denominator;
+
+    u64::try_from(minted).map_err(|_| AmmError::MathOverflow.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_integer_sqrt() {
+        assert_eq!(integer_sqrt(0), 0);
+        assert_eq!(integer_sqrt(1), 1);
+        assert_eq!(integer_sqrt(2), 1);
+        assert_eq!(integer_sqrt(15), 3);
+        assert_eq!(integer_sqrt(16), 4);
+        assert_eq!(integer_sqrt(1_000_000_000_000), 1_000_000);
+        assert_eq!(integer_sqrt(999_999_999_999), 999_999);
+        assert_eq!(integer_sqrt(u64::MAX as u128 * u64::MAX as u128), u64::MAX as u128);
+        assert_eq!(integer_sqrt(u128::MAX), u64::MAX as u128);
+    }
+
+    #[test]
+    fn test_mul_div() {
+        assert_eq!(mul_div_floor(7, 3, 2).unwrap(), 10);
+        assert_eq!(mul_div_ceil(7, 3, 2).unwrap(), 11);
+        assert_eq!(mul_div_ceil(6, 3, 2).unwrap(), 9);
+        assert_eq!(mul_div_floor(1_000_000, 30, 10_000).unwrap(), 3_000);
+
+        // The intermediate product may exceed u64 as long as the result fits
+        assert_eq!(mul_div_floor(u64::MAX, u64::MAX, u64::MAX).unwrap(), u64::MAX);
+        assert_eq!(mul_div_ceil(u64::MAX, 3, 4).unwrap(), (u64::MAX / 4) * 3 + 3);
+
+        assert_eq!(mul_div_floor(u64::MAX, 2, 1).unwrap_err(), error!(AmmError::MathOverflow));
+        assert_eq!(mul_div_ceil(u64::MAX, 2, 1).unwrap_err(), error!(AmmError::MathOverflow));
+        assert_eq!(mul_div_floor(1, 1, 0).unwrap_err(), error!(AmmError::MathOverflow));
+        assert_eq!(mul_div_ceil(1, 1, 0).unwrap_err(), error!(AmmError::MathOverflow));
+    }
+
+    #[test]
+    fn test_price_q64() {
+        assert_eq!(price_q64(1_000, 1_000), 1u128 << 64);
+        assert_eq!(price_q64(1_000, 2_000), 2u128 << 64);
+        assert_eq!(price_q64(2_000, 1_000), 1u128 << 63);
+        assert_eq!(price_q64(3, 1), (1u128 << 64) / 3);
+        assert_eq!(price_q64(1, u64::MAX), (u64::MAX as u128) << 64);
+        assert_eq!(price_q64(0, 1_000), 0);
+    }
+
+    #[test]
+    fn test_spot_price_q64() {
+        // 1 X (6 decimals) against 2 Y (9 decimals)
+        assert_eq!(spot_price_q64(1_000_000, 2_000_000_000, 6, 9).unwrap(), 2u128 << 64);
+        // 1 X (9 decimals) against 2 Y (6 decimals)
+        assert_eq!(spot_price_q64(1_000_000_000, 2_000_000, 9, 6).unwrap(), 2u128 << 64);
+        // Equal decimals reduce to the raw ratio
+        assert_eq!(spot_price_q64(3, 1, 6, 6).unwrap(), price_q64(3, 1));
+
+        assert!(spot_price_q64(0, 1_000, 6, 6).is_err());
+        assert!(spot_price_q64(1_000, 0, 6, 6).is_err());
+        assert!(spot_price_q64(1, u64::MAX, 40, 0).is_err());
+    }
+
+    #[test]
+    fn test_pool_math_across_decimals() {
+        // 1_000 X at 2 Y each, deposit and withdraw 1% of it; raw base units are scale-invariant,
+        // so every decimal pairing prices the same whole-token amounts without normalization
+        for (decimals_x, decimals_y) in [(6, 6), (9, 6), (6, 9), (0, 9), (9, 0), (9, 9)] {
+            let unit_x = 10u64.pow(decimals_x as u32);
+            let unit_y = 10u64.pow(decimals_y as u32);
+            let (reserve_x, reserve_y) = (1_000 * unit_x, 2_000 * unit_y);
+            let (supply, _, _) = lp_for_exact_amounts(reserve_x, reserve_y, 0, 0, 0).unwrap();
+
+            // Amounts are exact up to what one LP unit is worth of each side
+            let close = |amount: u64, expected: u64, reserve: u64| {
+                amount <= expected && expected - amount <= reserve / supply + 1
+            };
+            let (lp, x, y) = lp_for_exact_amounts(10 * unit_x, 20 * unit_y, reserve_x, reserve_y, supply).unwrap();
+            assert_eq!(lp, supply / 100);
+            assert!(close(x, 10 * unit_x, reserve_x) && close(y, 20 * unit_y, reserve_y));
+
+            let out_x = withdraw_amount_for_lp(reserve_x, supply, lp).unwrap();
+            let out_y = withdraw_amount_for_lp(reserve_y, supply, lp).unwrap();
+            assert!(close(out_x, 10 * unit_x, reserve_x) && close(out_y, 20 * unit_y, reserve_y));
+            assert_eq!(
+                spot_price_q64(reserve_x, reserve_y, decimals_x, decimals_y).unwrap(),
+                2u128 << 64
+            );
+        }
+    }
+
+    #[test]
+    fn test_optimal_swap_amount() {
+        // Without a fee: s = r (sqrt(1 + a / r) - 1) = 1_000_000 * (1.1 - 1)
+        let no_fee = optimal_swap_amount(1_000_000, 210_000, 0).unwrap();
+        assert!(no_fee.abs_diff(100_000) <= 1);
+
+        // A fee pushes slightly more of the input through the swap
+        let with_fee = optimal_swap_amount(1_000_000, 210_000, 30).unwrap();
+        assert!(with_fee > 100_000 && with_fee < 100_500);
+
+        // Deep pools approach an even split without overflowing
+        let deep = optimal_swap_amount(u64::MAX / 2, 1_000_000, 30).unwrap();
+        assert!(deep > 499_000 && deep < 501_000);
+    }
+
+    #[test]
+    fn test_lp_for_exact_amounts() {
+        // Pool of 1_000 X / 4_000 Y with 2_000 LP, Y is in excess and gets cut down
+        assert_eq!(
+            lp_for_exact_amounts(100, 1_000, 1_000, 4_000, 2_000).unwrap(),
+            (200, 100, 400)
+        );
+
+        // X binding the other way round
+        assert_eq!(
+            lp_for_exact_amounts(1_000, 40, 1_000, 4_000, 2_000).unwrap(),
+            (20, 10, 40)
+        );
+
+        // Rounding favours the pool
+        assert_eq!(lp_for_exact_amounts(3, 3, 3, 3, 2).unwrap(), (2, 3, 3));
+
+        // Empty pool mints the geometric mean
+        assert_eq!(lp_for_exact_amounts(100, 400, 0, 0, 0).unwrap(), (200, 100, 400));
+    }
+
+    #[test]
+    fn test_lp_math_near_u64_max() {
+        // The first deposit's product overflows u64 but not the u128 it is taken in
+        assert_eq!(initial_lp_amount(u64::MAX, u64::MAX).unwrap(), u64::MAX);
+        assert_eq!(initial_lp_amount(u64::MAX, 1).unwrap(), (1u64 << 32) - 1);
+        assert_eq!(
+            lp_for_exact_amounts(u64::MAX, u64::MAX, 0, 0, 0).unwrap(),
+            (u64::MAX, u64::MAX, u64::MAX)
+        );
+
+        // Full reserves and supply price a deposit without overflowing
+        let half = u64::MAX / 2;
+        assert_eq!(
+            lp_for_exact_amounts(half, half, u64::MAX, u64::MAX, u64::MAX).unwrap(),
+            (half, half, half)
+        );
+        assert_eq!(withdraw_amount_for_lp(u64::MAX, u64::MAX, half).unwrap(), half);
+
+        // Results past u64 fail with MathOverflow instead of wrapping
+        assert_eq!(
+            deposit_amount_for_lp(u64::MAX, 1, 2).unwrap_err(),
+            error!(AmmError::MathOverflow)
+        );
+        assert_eq!(
+            lp_for_exact_amounts(u64::MAX, u64::MAX, 1, 1, u64::MAX).unwrap_err(),
+            error!(AmmError::MathOverflow)
+        );
+        assert_eq!(
+            mul_div_floor(u64::MAX, u64::MAX, 1).unwrap_err(),
+            error!(AmmError::MathOverflow)
+        );
+    }
+
+    #[test]
+    fn test_rounding_favours_pool_on_dust() {
+        // Reserves, supplies and LP amounts chosen so the exact shares are fractional
+        let reserves = [1u64, 3, 7, 999, 1_000_003, u64::MAX / 3];
+        let supplies = [1u64, 2, 3, 1_000, 999_983];
+
+        for &reserve in &reserves {
+            for &supply in &supplies {
+                for lp in 1..=supply.min(7) {
+                    // Value per LP never drops after a deposit: (r + x) / (s + lp) >= r / s
+                    let x = deposit_amount_for_lp(reserve, supply, lp).unwrap();
+                    assert!(
+                        (reserve as u128 + x as u128) * supply as u128
+                            >= reserve as u128 * (supply + lp) as u128
+                    );
+
+                    // Nor after a withdrawal: (r - x) / (s - lp) >= r / s
+                    let x = withdraw_amount_for_lp(reserve, supply, lp).unwrap();
+                    assert!(
+                        (reserve - x) as u128 * supply as u128
+                            >= reserve as u128 * (supply - lp) as u128
+                    );
+                }
+            }
+        }
+
+        // Depositing then withdrawing the same LP never returns more than was paid in
+        let paid = deposit_amount_for_lp(10, 3, 1).unwrap();
+        let back = withdraw_amount_for_lp(10 + paid, 4, 1).unwrap();
+        assert_eq!((paid, back), (4, 3));
+    }
+
+    #[test]
+    fn test_price_impact_bps() {
+        // 1% of the pool in a balanced 1:1 pool without fees: out = 9_900 vs 10_000 at spot
+        assert_eq!(price_impact_bps(10_000, 9_900, 1_000_000, 1_000_000).unwrap(), 100);
+
+        // Spot price of 2 Y per X
+        assert_eq!(price_impact_bps(1_000, 1_500, 1_000_000, 2_000_000).unwrap(), 2_500);
+
+        // Receiving at or above spot is no impact
+        assert_eq!(price_impact_bps(1_000, 1_000, 1_000_000, 1_000_000).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_protocol_lp_for_growth() {
+        // Uniswap's 1/6 of growth: 1_000 LP over a root going from 1_000 to 1_100
+        // mints 1_000 · 100 / (5 · 1_100 + 1_000) = 15
+        let share = 10_000 / 6 + 1;
+        assert_eq!(protocol_lp_for_growth(1_000, 1_100, 1_000, 1_000, share).unwrap(), 15);
+
+        // The protocol ends up with no more than its share of the growth
+        let minted = protocol_lp_for_growth(1_000_000, 1_100_000, 1_000_000, 1_000_000, 2_000).unwrap();
+        let owned = minted as u128 * 1_100_000 / (1_000_000 + minted) as u128;
+        assert!(owned <= 100_000 * 2_000 / 10_000);
+        assert_eq!(minted, 18_518);
+
+        // Doubling the pool by deposit doubles supply and root alike, nothing is minted
+        assert_eq!(protocol_lp_for_growth(2_000, 2_000, 1_000, 1_000, 2_000).unwrap(), 0);
+        // Growth per LP still counts after the supply changed
+        assert!(protocol_lp_for_growth(2_000, 2_200, 1_000, 1_000, 2_000).unwrap() > 0);
+
+        // No baseline, a shrinking root or a zero share mint nothing
+        assert_eq!(protocol_lp_for_growth(1_000, 1_100, 0, 0, 2_000).unwrap(), 0);
+        assert_eq!(protocol_lp_for_growth(1_000, 900, 1_000, 1_000, 2_000).unwrap(), 0);
+        assert_eq!(protocol_lp_for_growth(1_000, 1_100, 1_000, 1_000, 0).unwrap(), 0);
+
+        // Full-range values don't overflow
+        let root = u64::MAX as u128;
+        assert!(protocol_lp_for_growth(u64::MAX, root, root / 2, u64::MAX, 5_000).is_ok());
+    }
+}
@@ -1,2 +1,8 @@
 pub mod token_utils;
-pub use token_utils::*;
\ No newline at end of file
+pub mod math;
+pub mod account_resolver;
+pub mod native_sol;
+pub use token_utils::*;
+pub use math::*;
+pub use account_resolver::*;
+pub use native_sol::*;
@@ -0,0 +1,94 @@
+use anchor_lang::{
+    prelude::*,
+    system_program::{transfer, Transfer},
+};
+use anchor_spl::{
+    token::spl_token,
+    token_interface::{close_account, spl_token_2022, sync_native, CloseAccount, SyncNative, TokenAccount},
+};
+
+/// Check if a mint is wrapped SOL under either token program
+pub fn is_native_mint(mint: &Pubkey) -> bool {
+    *mint == spl_token::native_mint::ID || *mint == spl_token_2022::native_mint::ID
+}
+
+/// Lamports to move into a WSOL account holding `balance` so it can cover `amount`
+pub fn wrap_shortfall(balance: u64, amount: u64) -> u64 {
+    amount.saturating_sub(balance)
+}
+
+/// Top up the user's WSOL account from their lamports so it holds at least `amount`
+/// No-op for accounts of other mints
+pub fn wrap_sol<'info>(
+    system_program: AccountInfo<'info>,
+    token_program: AccountInfo<'info>,
+    user: AccountInfo<'info>,
+    token_account: &InterfaceAccount<'info, TokenAccount>,
+    amount: u64,
+) -> Result<()> {
+    if !is_native_mint(&token_account.mint) {
+        return Ok(());
+    }
+
+    let lamports = wrap_shortfall(token_account.amount, amount);
+    if lamports == 0 {
+        return Ok(());
+    }
+
+    transfer(
+        CpiContext::new(
+            system_program,
+            Transfer {
+                from: user,
+                to: token_account.to_account_info(),
+            },
+        ),
+        lamports,
+    )?;
+
+    // The token balance only picks up the lamports once synced
+    sync_native(CpiContext::new(
+        token_program,
+        SyncNative {
+            account: token_account.to_account_info(),
+        },
+    ))
+}
+
+/// Close the user's WSOL account, returning its whole balance and rent to the user as SOL
+/// No-op for accounts of other mints
+pub fn unwrap_sol<'info>(
+    token_program: AccountInfo<'info>,
+    user: AccountInfo<'info>,
+    token_account: &InterfaceAccount<'info, TokenAccount>,
+) -> Result<()> {
+    if !is_native_mint(&token_account.mint) {
+        return Ok(());
+    }
+
+    close_account(CpiContext::new(
+        token_program,
+        CloseAccount {
+            account: token_account.to_account_info(),
+            destination: user.clone(),
+            authority: user,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_native_mint_and_shortfall() {
+        assert!(is_native_mint(&spl_token::native_mint::ID));
+        assert!(is_native_mint(&spl_token_2022::native_mint::ID));
+        assert!(!is_native_mint(&Pubkey::new_unique()));
+
+        // Only the missing lamports are wrapped
+        assert_eq!(wrap_shortfall(0, 1_000), 1_000);
+        assert_eq!(wrap_shortfall(400, 1_000), 600);
+        assert_eq!(wrap_shortfall(1_500, 1_000), 0);
+    }
+}
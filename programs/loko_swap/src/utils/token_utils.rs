@@ -1,13 +1,25 @@
 use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{
+    transfer_checked, transfer_checked_with_fee, TransferChecked, TransferCheckedWithFee,
+};
 use anchor_spl::token_interface::spl_token_2022::{
+    self,
     extension::{
         BaseStateWithExtensions, StateWithExtensions, 
-        transfer_fee::TransferFeeConfig, transfer_hook::TransferHook
+        cpi_guard::CpiGuard,
+        interest_bearing_mint::InterestBearingConfig,
+        transfer_fee::{TransferFee, TransferFeeConfig}, transfer_hook::TransferHook
     },
-    state::Mint,
+    state::{Account, Mint},
     onchain::invoke_transfer_checked,
 };
-use crate::error::AmmError;
+use spl_tlv_account_resolution::error::AccountResolutionError;
+use spl_transfer_hook_interface::error::TransferHookError;
+use crate::{
+    constants::MAX_HOOK_ACCOUNTS,
+    error::AmmError,
+    utils::account_resolver::{required_hook_accounts, validate_hook_accounts},
+};
 
 /// Check if a mint has the transfer fee extension
 pub fn has_transfer_fee_extension(mint_account: &AccountInfo) -> Result<bool> {
@@ -32,7 +44,10 @@ pub fn has_transfer_hook_extension(mint_account: &AccountInfo) -> Result<bool> {
     }
     
     let mint_state = StateWithExtensions::<Mint>::unpack(&mint_data)?;
-    Ok(mint_state.get_extension::<TransferHook>().is_ok())
+    // Token-2022 skips the hook when its program id is cleared, so treat the mint as hook-less
+    Ok(mint_state
+        .get_extension::<TransferHook>()
+        .is_ok_and(|hook| Option::<Pubkey>::from(hook.program_id).is_some()))
 }
 
 /// Get the transfer fee configuration from a mint
@@ -53,45 +68,96 @@ pub fn get_transfer_hook_program_id(mint_account: &AccountInfo) -> Result<Pubkey
     let transfer_hook = mint_state.get_extension::<TransferHook>()
         .map_err(|_| error!(AmmError::TransferHookNotFound))?;
     
-    Ok(Pubkey::try_from(transfer_hook.program_id.0.as_ref()).unwrap_or_default())
+    Option::<Pubkey>::from(transfer_hook.program_id).ok_or(error!(AmmError::TransferHookNotFound))
 }
 
-/// Calculate the transfer fee for a given amount
-pub fn calculate_transfer_fee(amount: u64, fee_config: &TransferFeeConfig) -> u64 {
-    // Use the newer transfer fee configuration
-    let fee_basis_points = u16::from(fee_config.newer_transfer_fee.transfer_fee_basis_points);
-    let maximum_fee = u64::from(fee_config.newer_transfer_fee.maximum_fee);
+/// Check if a mint has the interest-bearing extension
+pub fn has_interest_bearing_extension(mint_account: &AccountInfo) -> Result<bool> {
+    let mint_data = mint_account.try_borrow_data()?;
     
-    let fee = (amount as u128)
-        .checked_mul(fee_basis_points as u128)
-        .unwrap()
-        .checked_div(10_000)
-        .unwrap() as u64;
+    // Only check Token-2022 mints
+    if mint_account.owner != &anchor_spl::token_interface::spl_token_2022::ID {
+        return Ok(false);
+    }
     
-    std::cmp::min(fee, maximum_fee)
+    let mint_state = StateWithExtensions::<Mint>::unpack(&mint_data)?;
+    Ok(mint_state.get_extension::<InterestBearingConfig>().is_ok())
 }
 
-/// Calculate the gross amount needed to achieve a net amount after fees
-/// Formula: gross = net / (1 - fee_rate)
-pub fn calculate_gross_amount(net_amount: u64, fee_config: &TransferFeeConfig) -> u64 {
-    let fee_rate = u16::from(fee_config.newer_transfer_fee.transfer_fee_basis_points) as u128;
-    
-    if fee_rate == 0 {
-        return net_amount;
+/// Convert a raw amount to its UI amount for quote display, including accrued interest
+/// The curve itself always prices in raw base units
+pub fn amount_to_ui_amount(mint_account: &AccountInfo, amount: u64, unix_timestamp: i64) -> Result<String> {
+    let mint_data = mint_account.try_borrow_data()?;
+
+    if mint_account.owner != &anchor_spl::token_interface::spl_token_2022::ID {
+        let mint_state = StateWithExtensions::<Mint>::unpack(&mint_data)?;
+        return Ok(spl_token_2022::amount_to_ui_amount_string_trimmed(amount, mint_state.base.decimals));
+    }
+
+    let mint_state = StateWithExtensions::<Mint>::unpack(&mint_data)?;
+    let decimals = mint_state.base.decimals;
+
+    match mint_state.get_extension::<InterestBearingConfig>() {
+        Ok(interest_config) => interest_config
+            .amount_to_ui_amount(amount, decimals, unix_timestamp)
+            .ok_or(AmmError::MathOverflow.into()),
+        Err(_) => Ok(spl_token_2022::amount_to_ui_amount_string_trimmed(amount, decimals)),
     }
-    
-    let gross = (net_amount as u128)
-        .checked_mul(10_000)
-        .unwrap()
-        .checked_div(10_000 - fee_rate)
-        .unwrap() as u64;
-    
-    gross
 }
 
-/// Calculate the net amount that will be received after fees are deducted
-pub fn calculate_net_amount(gross_amount: u64, fee_config: &TransferFeeConfig) -> u64 {
-    let fee = calculate_transfer_fee(gross_amount, fee_config);
+/// Calculate the transfer fee Token-2022 charges on `amount` in `epoch`
+pub fn calculate_transfer_fee(amount: u64, fee_config: &TransferFeeConfig, epoch: u64) -> u64 {
+    fee_config.get_epoch_fee(epoch).calculate_fee(amount).unwrap_or(u64::MAX)
+}
+
+/// Token-2022 transfer fee of `fee_basis_points` capped at `maximum_fee`
+fn transfer_fee(fee_basis_points: u16, maximum_fee: u64) -> TransferFee {
+    TransferFee {
+        epoch: 0.into(),
+        maximum_fee: maximum_fee.into(),
+        transfer_fee_basis_points: fee_basis_points.into(),
+    }
+}
+
+/// Gross amount whose transfer delivers `net_amount` after a fee of `fee_basis_points` capped at `maximum_fee`
+/// Formula: gross = ceil(net / (1 - fee_rate)), or net + maximum_fee once the fee on that gross hits the cap.
+/// Token-2022 rounds the fee up, so this is its own inverse; amounts past u64 saturate.
+pub fn gross_for_net(net_amount: u64, fee_basis_points: u16, maximum_fee: u64) -> u64 {
+    transfer_fee(fee_basis_points, maximum_fee)
+        .calculate_pre_fee_amount(net_amount)
+        .unwrap_or(u64::MAX)
+}
+
+/// Reject transfer fee settings that contradict each other
+///
+/// The fee is `min(amount * basis_points / 10_000, maximum_fee)`. A rate of 100% or more leaves
+/// nothing to gross up, a rate with a zero cap never charges, and a cap without a rate is unused.
+/// Zero for both means no transfer fee.
+pub fn validate_transfer_fee_config(fee_basis_points: u16, maximum_fee: u64) -> Result<()> {
+    require!(fee_basis_points < 10_000, AmmError::InvalidFee);
+    require!(
+        (fee_basis_points == 0) == (maximum_fee == 0),
+        AmmError::InvalidFee
+    );
+    Ok(())
+}
+
+/// Rate and cap of the transfer fee Token-2022 charges in `epoch`
+/// A fee change only takes effect from its epoch on, until then the older fee applies
+pub fn epoch_transfer_fee(fee_config: &TransferFeeConfig, epoch: u64) -> (u16, u64) {
+    let fee = fee_config.get_epoch_fee(epoch);
+    (u16::from(fee.transfer_fee_basis_points), u64::from(fee.maximum_fee))
+}
+
+/// Calculate the gross amount needed to achieve a net amount after the fee in effect in `epoch`
+pub fn calculate_gross_amount(net_amount: u64, fee_config: &TransferFeeConfig, epoch: u64) -> u64 {
+    let (fee_basis_points, maximum_fee) = epoch_transfer_fee(fee_config, epoch);
+    gross_for_net(net_amount, fee_basis_points, maximum_fee)
+}
+
+/// Calculate the net amount that will be received after the fee in effect in `epoch`
+pub fn calculate_net_amount(gross_amount: u64, fee_config: &TransferFeeConfig, epoch: u64) -> u64 {
+    let fee = calculate_transfer_fee(gross_amount, fee_config, epoch);
     gross_amount.saturating_sub(fee)
 }
 
@@ -136,12 +202,10 @@ impl TokenExtensions {
         let has_transfer_fee = has_transfer_fee_extension(mint_account)?;
         let has_transfer_hook = has_transfer_hook_extension(mint_account)?;
         
+        // Rates of the current epoch, so fees and gross-ups match what Token-2022 charges
         let (transfer_fee_basis_points, transfer_fee_maximum) = if has_transfer_fee {
             let config = get_transfer_fee_config(mint_account)?;
-            (
-                u16::from(config.newer_transfer_fee.transfer_fee_basis_points),
-                u64::from(config.newer_transfer_fee.maximum_fee)
-            )
+            epoch_transfer_fee(&config, Clock::get()?.epoch)
         } else {
             (0, 0)
         };
@@ -162,14 +226,12 @@ impl TokenExtensions {
     }
     
     /// Calculate fee for this token if it has transfer fee extension
+    /// Rounds up like Token-2022, which rejects a `transfer_checked_with_fee` with any other fee
     pub fn calculate_fee(&self, amount: u64) -> u64 {
         if self.has_transfer_fee {
-            let fee = (amount as u128)
-                .checked_mul(self.transfer_fee_basis_points as u128)
-                .unwrap()
-                .checked_div(10_000)
-                .unwrap() as u64;
-            std::cmp::min(fee, self.transfer_fee_maximum)
+            transfer_fee(self.transfer_fee_basis_points, self.transfer_fee_maximum)
+                .calculate_fee(amount)
+                .unwrap_or(u64::MAX)
         } else {
             0
         }
@@ -177,13 +239,8 @@ impl TokenExtensions {
     
     /// Calculate gross amount needed to get net amount for this token
     pub fn calculate_gross_for_net(&self, net_amount: u64) -> u64 {
-        if self.has_transfer_fee && self.transfer_fee_basis_points > 0 {
-            let fee_rate = self.transfer_fee_basis_points as u128;
-            (net_amount as u128)
-                .checked_mul(10_000)
-                .unwrap()
-                .checked_div(10_000 - fee_rate)
-                .unwrap() as u64
+        if self.has_transfer_fee {
+            gross_for_net(net_amount, self.transfer_fee_basis_points, self.transfer_fee_maximum)
         } else {
             net_amount
         }
@@ -213,16 +270,7 @@ pub fn calculate_fee_direct(mint_account: &AccountInfo, amount: u64) -> Result<u
     }
     
     let config = get_transfer_fee_config(mint_account)?;
-    let fee_basis_points = u16::from(config.newer_transfer_fee.transfer_fee_basis_points);
-    let maximum_fee = u64::from(config.newer_transfer_fee.maximum_fee);
-    
-    let fee = (amount as u128)
-        .checked_mul(fee_basis_points as u128)
-        .unwrap()
-        .checked_div(10_000)
-        .unwrap() as u64;
-    
-    Ok(std::cmp::min(fee, maximum_fee))
+    Ok(calculate_transfer_fee(amount, &config, Clock::get()?.epoch))
 }
 
 /// Direct gross amount calculation without struct allocation - optimized for stack usage
@@ -232,20 +280,86 @@ pub fn calculate_gross_for_net_direct(mint_account: &AccountInfo, net_amount: u6
     }
     
     let config = get_transfer_fee_config(mint_account)?;
-    let fee_basis_points = u16::from(config.newer_transfer_fee.transfer_fee_basis_points);
-    
-    if fee_basis_points == 0 {
-        return Ok(net_amount);
+    Ok(calculate_gross_amount(net_amount, &config, Clock::get()?.epoch))
+}
+
+/// Fail before any transfer if the freeze authority of either mint froze its pool vault
+/// Token-2022 would otherwise reject the transfer mid-CPI with an opaque error
+pub fn check_vaults_not_frozen(vault_x: &Account, vault_y: &Account) -> Result<()> {
+    require!(
+        !vault_x.is_frozen() && !vault_y.is_frozen(),
+        AmmError::VaultFrozen
+    );
+    Ok(())
+}
+
+/// Fail if `source` is a Token-2022 account with CPI Guard on and `authority` is its owner
+/// CPI Guard blocks owner-signed transfers made through another program, so the transfer
+/// would otherwise fail inside Token-2022 with an opaque error. Delegates aren't affected.
+pub fn check_cpi_guard(source: &AccountInfo, authority: &Pubkey) -> Result<()> {
+    if source.owner != &spl_token_2022::ID {
+        return Ok(());
     }
-    
-    let fee_rate = fee_basis_points as u128;
-    let gross = (net_amount as u128)
-        .checked_mul(10_000)
-        .unwrap()
-        .checked_div(10_000 - fee_rate)
-        .unwrap() as u64;
-    
-    Ok(gross)
+
+    let data = source.try_borrow_data()?;
+    let state = StateWithExtensions::<Account>::unpack(&data)?;
+    let locked = state
+        .get_extension::<CpiGuard>()
+        .map(|guard| bool::from(guard.lock_cpi))
+        .unwrap_or(false);
+
+    require!(!(locked && state.base.owner == *authority), AmmError::CpiGuardEnabled);
+    Ok(())
+}
+
+/// Fail unless both vaults are token accounts of the pool mints owned by `config`
+/// Backs up the Anchor ATA constraints where the mints are validated by hand
+pub fn check_pool_vaults(
+    config: &Pubkey,
+    mint_x: &Pubkey,
+    mint_y: &Pubkey,
+    vault_x: &Account,
+    vault_y: &Account,
+) -> Result<()> {
+    require!(
+        vault_x.owner == *config && vault_y.owner == *config,
+        AmmError::InvalidTokenAccount
+    );
+    require!(
+        vault_x.mint == *mint_x && vault_y.mint == *mint_y,
+        AmmError::InvalidToken
+    );
+    Ok(())
+}
+
+/// Fail before any transfer when the hooked legs of an instruction need more than
+/// `MAX_HOOK_ACCOUNTS` remaining accounts in total, or the client passed more than that
+/// `mints` are the mints the instruction transfers; hook-less mints need none
+pub fn check_hook_account_budget(mints: &[AccountInfo], remaining_accounts: &[AccountInfo]) -> Result<()> {
+    let mut required = 0;
+    for mint in mints {
+        let extensions = TokenExtensions::new(mint)?;
+        if !extensions.has_transfer_hook {
+            continue;
+        }
+        let hook_program_id = extensions
+            .transfer_hook_program_id
+            .ok_or(AmmError::TransferHookNotFound)?;
+        required += required_hook_accounts(&hook_program_id, mint.key, remaining_accounts)?;
+    }
+
+    let limit = MAX_HOOK_ACCOUNTS as usize;
+    if required > limit || remaining_accounts.len() > limit {
+        msg!(
+            "Hook transfers need {} remaining accounts, {} passed, at most {} allowed",
+            required,
+            remaining_accounts.len(),
+            limit
+        );
+        return err!(AmmError::TooManyHookAccounts);
+    }
+
+    Ok(())
 }
 
 /// Direct Token-2022 transfer with hook support
@@ -274,10 +388,103 @@ pub fn invoke_transfer_checked_with_hooks<'info>(
         signer_seeds,
     ).map_err(move |e| {
         msg!("Direct Token-2022 transfer with hooks failed: {:?}", e);
-        anchor_lang::error::Error::from(e)
+        hook_transfer_error(&e).into()
     })
 }
 
+/// Pool error for a hooked transfer that failed before or while Token-2022 ran
+///
+/// Resolving the hook's extra accounts fails with `HookAccountsMissing` when one isn't in
+/// the remaining accounts or isn't the expected account, anything else with
+/// `HookExecutionFailed`. The original error is logged by the caller.
+fn hook_transfer_error(error: &ProgramError) -> AmmError {
+    let missing_account_codes = [
+        TransferHookError::IncorrectAccount as u32,
+        AccountResolutionError::IncorrectAccount as u32,
+        AccountResolutionError::NotEnoughAccounts as u32,
+        AccountResolutionError::AccountNotFound as u32,
+        AccountResolutionError::AccountDataNotFound as u32,
+    ];
+
+    match error {
+        ProgramError::NotEnoughAccountKeys => AmmError::HookAccountsMissing,
+        ProgramError::Custom(code) if missing_account_codes.contains(code) => AmmError::HookAccountsMissing,
+        _ => AmmError::HookExecutionFailed,
+    }
+}
+
+/// Transfer tokens using the path required by the mint's Token-2022 extensions
+/// Pass empty `signer_seeds` when the authority signs the outer instruction
+pub fn transfer_with_extensions<'info>(
+    token_program: AccountInfo<'info>,
+    from: AccountInfo<'info>,
+    mint: AccountInfo<'info>,
+    to: AccountInfo<'info>,
+    authority: AccountInfo<'info>,
+    extensions: &TokenExtensions,
+    remaining_accounts: &[AccountInfo<'info>],
+    amount: u64,
+    decimals: u8,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    // Pool PDAs sign their own transfers, only user-signed ones can run into CPI Guard
+    if signer_seeds.is_empty() {
+        check_cpi_guard(&from, authority.key)?;
+    }
+
+    match (extensions.has_transfer_fee, extensions.has_transfer_hook) {
+        // Token with transfer fee only
+        (true, false) => {
+            let cpi_accounts = TransferCheckedWithFee {
+                source: from,
+                destination: to,
+                authority,
+                mint,
+                token_program_id: token_program.clone(),
+            };
+            let ctx = CpiContext::new_with_signer(token_program, cpi_accounts, signer_seeds);
+            let expected_fee = extensions.calculate_fee(amount);
+            transfer_checked_with_fee(ctx, amount, decimals, expected_fee)
+        }
+
+        // Token with transfer hook (with or without fee) - use direct Token-2022 call
+        (_, true) => {
+            // Fail with a clear error before Token-2022 does with an opaque one
+            let hook_program_id = extensions
+                .transfer_hook_program_id
+                .ok_or(AmmError::TransferHookNotFound)?;
+            validate_hook_accounts(&hook_program_id, mint.key, remaining_accounts)?;
+
+            compute_trace!("hook transfer: start");
+            let result = invoke_transfer_checked_with_hooks(
+                &token_program.key(),
+                from,
+                mint,
+                to,
+                authority,
+                remaining_accounts,
+                amount,
+                decimals,
+                signer_seeds,
+            );
+            compute_trace!("hook transfer: end");
+            result
+        }
+
+        // Standard token (no extensions)
+        (false, false) => {
+            let cpi_accounts = TransferChecked {
+                from,
+                to,
+                authority,
+                mint,
+            };
+            let ctx = CpiContext::new_with_signer(token_program, cpi_accounts, signer_seeds);
+            transfer_checked(ctx, amount, decimals)
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -297,12 +504,102 @@ mod tests {
         };
         
         // Test normal case
-        assert_eq!(calculate_transfer_fee(10000, &fee_config), 5); // 0.5% of 10000
+        assert_eq!(calculate_transfer_fee(10000, &fee_config, 0), 50); // 0.5% of 10000
+        assert_eq!(calculate_transfer_fee(10001, &fee_config, 0), 51); // Rounded up like Token-2022
         
         // Test maximum fee cap
-        assert_eq!(calculate_transfer_fee(1000000, &fee_config), 1000); // Capped at max
+        assert_eq!(calculate_transfer_fee(1000000, &fee_config, 0), 1000); // Capped at max
     }
     
+    fn hook_mint_data(program_id: Option<Pubkey>) -> Vec<u8> {
+        use anchor_spl::token_interface::spl_token_2022::extension::{
+            BaseStateWithExtensionsMut, ExtensionType, StateWithExtensionsMut,
+        };
+
+        let len = ExtensionType::try_calculate_account_len::<Mint>(&[ExtensionType::TransferHook]).unwrap();
+        let mut data = vec![0u8; len];
+        let mut state = StateWithExtensionsMut::<Mint>::unpack_uninitialized(&mut data).unwrap();
+        let hook = state.init_extension::<TransferHook>(true).unwrap();
+        hook.program_id = program_id.try_into().unwrap();
+        state.base = Mint {
+            decimals: 6,
+            is_initialized: true,
+            ..Default::default()
+        };
+        state.pack_base();
+        state.init_account_type().unwrap();
+        data
+    }
+
+    fn cpi_guard_account_data(owner: Pubkey, lock_cpi: bool) -> Vec<u8> {
+        use anchor_spl::token_interface::spl_token_2022::{
+            extension::{BaseStateWithExtensionsMut, ExtensionType, StateWithExtensionsMut},
+            state::AccountState,
+        };
+
+        let len = ExtensionType::try_calculate_account_len::<Account>(&[ExtensionType::CpiGuard]).unwrap();
+        let mut data = vec![0u8; len];
+        let mut state = StateWithExtensionsMut::<Account>::unpack_uninitialized(&mut data).unwrap();
+        state.init_extension::<CpiGuard>(true).unwrap().lock_cpi = lock_cpi.into();
+        state.base = Account {
+            mint: Pubkey::new_unique(),
+            owner,
+            amount: 1_000,
+            state: AccountState::Initialized,
+            ..Default::default()
+        };
+        state.pack_base();
+        state.init_account_type().unwrap();
+        data
+    }
+
+    #[test]
+    fn test_check_cpi_guard() {
+        let key = Pubkey::new_unique();
+        let user = Pubkey::new_unique();
+        let token_2022 = spl_token_2022::ID;
+        let mut lamports = 0;
+
+        // The owner signing through the pool is blocked with a clear error
+        let mut data = cpi_guard_account_data(user, true);
+        let account = AccountInfo::new(&key, false, true, &mut lamports, &mut data, &token_2022, false, 0);
+        assert_eq!(check_cpi_guard(&account, &user).unwrap_err(), error!(AmmError::CpiGuardEnabled));
+
+        // A delegate can still move the tokens
+        assert!(check_cpi_guard(&account, &Pubkey::new_unique()).is_ok());
+
+        // Disabled guard, or no Token-2022 account at all
+        let mut data = cpi_guard_account_data(user, false);
+        let account = AccountInfo::new(&key, false, true, &mut lamports, &mut data, &token_2022, false, 0);
+        assert!(check_cpi_guard(&account, &user).is_ok());
+        let legacy = anchor_spl::token::ID;
+        let mut data = vec![0u8; 165];
+        let account = AccountInfo::new(&key, false, true, &mut lamports, &mut data, &legacy, false, 0);
+        assert!(check_cpi_guard(&account, &user).is_ok());
+    }
+
+    #[test]
+    fn test_transfer_hook_with_cleared_program_id() {
+        let key = Pubkey::new_unique();
+        let owner = spl_token_2022::ID;
+        let mut lamports = 0;
+
+        // Cleared program id: no hook runs, so the mint is hook-less
+        let mut data = hook_mint_data(None);
+        let mint = AccountInfo::new(&key, false, false, &mut lamports, &mut data, &owner, false, 0);
+        assert!(!has_transfer_hook_extension(&mint).unwrap());
+        assert!(get_transfer_hook_program_id(&mint).is_err());
+        let extensions = TokenExtensions::new(&mint).unwrap();
+        assert!(!extensions.has_transfer_hook);
+        assert_eq!(extensions.transfer_hook_program_id, None);
+
+        let hook_program = Pubkey::new_unique();
+        let mut data = hook_mint_data(Some(hook_program));
+        let mint = AccountInfo::new(&key, false, false, &mut lamports, &mut data, &owner, false, 0);
+        assert!(has_transfer_hook_extension(&mint).unwrap());
+        assert_eq!(get_transfer_hook_program_id(&mint).unwrap(), hook_program);
+    }
+
     #[test]
     fn test_calculate_gross_amount() {
         let fee_config = TransferFeeConfig {
@@ -318,11 +615,214 @@ mod tests {
         };
         
         // Test: to get 9950 net, need ~10000 gross (with 0.5% fee)
-        let gross = calculate_gross_amount(9950, &fee_config);
-        let fee = calculate_transfer_fee(gross, &fee_config);
+        let gross = calculate_gross_amount(9950, &fee_config, 0);
+        let fee = calculate_transfer_fee(gross, &fee_config, 0);
         let net = gross - fee;
         
         assert!(net >= 9950);
         assert!(net <= 9951); // Allow for rounding
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_calculate_gross_amount_across_fee_change() {
+        use anchor_spl::token_interface::spl_token_2022::extension::transfer_fee::TransferFee;
+
+        // 1% until epoch 10, then 3%
+        let fee_config = TransferFeeConfig {
+            transfer_fee_config_authority: Default::default(),
+            withdraw_withheld_authority: Default::default(),
+            withheld_amount: 0.into(),
+            older_transfer_fee: TransferFee {
+                epoch: 0.into(),
+                transfer_fee_basis_points: 100.into(),
+                maximum_fee: u64::MAX.into(),
+            },
+            newer_transfer_fee: TransferFee {
+                epoch: 10.into(),
+                transfer_fee_basis_points: 300.into(),
+                maximum_fee: u64::MAX.into(),
+            },
+        };
+
+        assert_eq!(epoch_transfer_fee(&fee_config, 9), (100, u64::MAX));
+        assert_eq!(epoch_transfer_fee(&fee_config, 10), (300, u64::MAX));
+        assert_eq!(calculate_transfer_fee(10_000, &fee_config, 9), 100);
+        assert_eq!(calculate_transfer_fee(10_000, &fee_config, 10), 300);
+        assert_eq!(calculate_net_amount(10_000, &fee_config, 9), 9_900);
+
+        for (epoch, expected_gross) in [(9, 9_700), (10, 9_900)] {
+            let gross = calculate_gross_amount(9_603, &fee_config, epoch);
+            assert_eq!(gross, expected_gross);
+
+            // Token-2022 charges the same epoch's fee, so the net still covers the request
+            let fee = fee_config.calculate_epoch_fee(epoch, gross).unwrap();
+            assert_eq!(gross - fee, 9_603);
+        }
+
+        // Grossing up with the older rate at the boundary would come up short
+        let stale = calculate_gross_amount(9_603, &fee_config, 9);
+        assert!(stale - fee_config.calculate_epoch_fee(10, stale).unwrap() < 9_603);
+    }
+
+    #[test]
+    fn test_hook_transfer_error() {
+        // Accounts the hook needs but the client left out or got wrong
+        for error in [
+            ProgramError::NotEnoughAccountKeys,
+            TransferHookError::IncorrectAccount.into(),
+            AccountResolutionError::IncorrectAccount.into(),
+            AccountResolutionError::AccountNotFound.into(),
+        ] {
+            assert!(matches!(hook_transfer_error(&error), AmmError::HookAccountsMissing));
+        }
+
+        // Everything else, including the hook's own errors, is a failed hook execution
+        for error in [
+            ProgramError::Custom(6_000),
+            ProgramError::InvalidAccountData,
+            TransferHookError::ProgramCalledOutsideOfTransfer.into(),
+        ] {
+            assert!(matches!(hook_transfer_error(&error), AmmError::HookExecutionFailed));
+        }
+    }
+
+    #[test]
+    fn test_check_vaults_not_frozen() {
+        use anchor_spl::token_interface::spl_token_2022::state::AccountState;
+
+        let open = Account {
+            state: AccountState::Initialized,
+            ..Default::default()
+        };
+        let frozen = Account {
+            state: AccountState::Frozen,
+            ..Default::default()
+        };
+
+        assert!(check_vaults_not_frozen(&open, &open).is_ok());
+        assert!(check_vaults_not_frozen(&frozen, &open).is_err());
+        assert!(check_vaults_not_frozen(&open, &frozen).is_err());
+    }
+
+    #[test]
+    fn test_check_pool_vaults() {
+        let (config, mint_x, mint_y) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let vault = |owner: Pubkey, mint: Pubkey| Account {
+            owner,
+            mint,
+            ..Default::default()
+        };
+        let vault_x = vault(config, mint_x);
+        let vault_y = vault(config, mint_y);
+
+        assert!(check_pool_vaults(&config, &mint_x, &mint_y, &vault_x, &vault_y).is_ok());
+
+        // Swapped vaults, a foreign owner, or a vault of another mint are all rejected
+        assert!(check_pool_vaults(&config, &mint_x, &mint_y, &vault_y, &vault_x).is_err());
+        let foreign = vault(Pubkey::new_unique(), mint_y);
+        assert!(check_pool_vaults(&config, &mint_x, &mint_y, &vault_x, &foreign).is_err());
+        let other_mint = vault(config, Pubkey::new_unique());
+        assert!(check_pool_vaults(&config, &mint_x, &mint_y, &other_mint, &vault_y).is_err());
+    }
+
+    #[test]
+    fn test_validate_transfer_fee_config() {
+        assert!(validate_transfer_fee_config(0, 0).is_ok());
+        assert!(validate_transfer_fee_config(50, 1_000).is_ok());
+        assert!(validate_transfer_fee_config(9_999, u64::MAX).is_ok());
+
+        // A 100% fee can't be grossed up
+        assert!(validate_transfer_fee_config(10_000, u64::MAX).is_err());
+        // A zero cap zeroes the fee, a cap without a rate does nothing
+        assert!(validate_transfer_fee_config(1_000, 0).is_err());
+        assert!(validate_transfer_fee_config(0, 1_000).is_err());
+    }
+
+    #[test]
+    fn test_calculate_gross_for_net_fee_cap() {
+        // 1% fee capped at 1000, so the cap binds from a gross of 100_000
+        let extensions = TokenExtensions {
+            has_transfer_fee: true,
+            has_transfer_hook: false,
+            transfer_hook_program_id: None,
+            transfer_fee_basis_points: 100,
+            transfer_fee_maximum: 1_000,
+        };
+        // What the vault or user receives once Token-2022 has charged its fee
+        let token_fee = transfer_fee(100, 1_000);
+        let net_after = |gross: u64| token_fee.calculate_post_fee_amount(gross).unwrap();
+
+        // Below the cap the percentage gross-up applies, rounded up like Token-2022's fee
+        assert_eq!(extensions.calculate_gross_for_net(50_000), 50_506);
+        assert_eq!(net_after(50_506), 50_000);
+        assert_eq!(net_after(50_505), 49_999);
+
+        // Exactly at the cap both formulas agree
+        assert_eq!(extensions.calculate_gross_for_net(99_000), 100_000);
+        assert_eq!(net_after(100_000), 99_000);
+
+        // Above the cap only the maximum fee is added, as for a large deposit or swap output
+        assert_eq!(extensions.calculate_gross_for_net(200_000), 201_000);
+        assert_eq!(net_after(201_000), 200_000);
+        assert_eq!(extensions.calculate_gross_for_net(u64::MAX - 1_000), u64::MAX);
+        assert_eq!(net_after(u64::MAX), u64::MAX - 1_000);
+
+        // Without the cap the percentage gross-up would have overcharged
+        assert_eq!(gross_for_net(200_000, 100, u64::MAX), 202_021);
+
+        // A net no gross can deliver saturates instead of wrapping
+        assert_eq!(gross_for_net(u64::MAX, 100, u64::MAX), u64::MAX);
+    }
+
+    #[test]
+    fn test_check_hook_account_budget() {
+        use spl_tlv_account_resolution::{account::ExtraAccountMeta, state::ExtraAccountMetaList};
+        use spl_transfer_hook_interface::{get_extra_account_metas_address, instruction::ExecuteInstruction};
+
+        let owner = spl_token_2022::ID;
+        let hook_program = Pubkey::new_unique();
+        let mint_key = Pubkey::new_unique();
+        let metas_key = get_extra_account_metas_address(&mint_key, &hook_program);
+
+        let metas_data = |count: usize| {
+            let metas: Vec<ExtraAccountMeta> = (0..count)
+                .map(|_| ExtraAccountMeta::new_with_pubkey(&Pubkey::new_unique(), false, false).unwrap())
+                .collect();
+            let mut data = vec![0u8; ExtraAccountMetaList::size_of(count).unwrap()];
+            ExtraAccountMetaList::init::<ExecuteInstruction>(&mut data, &metas).unwrap();
+            data
+        };
+
+        let (mut mint_lamports, mut metas_lamports) = (0, 0);
+        let mut mint_data = hook_mint_data(Some(hook_program));
+        let mint = AccountInfo::new(&mint_key, false, false, &mut mint_lamports, &mut mint_data, &owner, false, 0);
+
+        // A hook with 3 extra accounts needs 5 remaining accounts
+        let mut small = metas_data(3);
+        let metas = AccountInfo::new(&metas_key, false, false, &mut metas_lamports, &mut small, &hook_program, false, 0);
+        let remaining = [metas.clone()];
+        assert!(check_hook_account_budget(std::slice::from_ref(&mint), &remaining).is_ok());
+
+        // Two legs of the same mint add up
+        assert!(check_hook_account_budget(&[mint.clone(), mint.clone()], &remaining).is_ok());
+
+        // Passing more accounts than the bound fails early
+        let padding: Vec<AccountInfo> = (0..=MAX_HOOK_ACCOUNTS).map(|_| metas.clone()).collect();
+        assert_eq!(
+            check_hook_account_budget(std::slice::from_ref(&mint), &padding).unwrap_err(),
+            error!(AmmError::TooManyHookAccounts)
+        );
+
+        // A hook asking for more extra accounts than the bound leaves room for
+        let mut large = metas_data(MAX_HOOK_ACCOUNTS as usize - 1);
+        let mut large_lamports = 0;
+        let metas = AccountInfo::new(&metas_key, false, false, &mut large_lamports, &mut large, &hook_program, false, 0);
+        assert_eq!(
+            check_hook_account_budget(std::slice::from_ref(&mint), &[metas]).unwrap_err(),
+            error!(AmmError::TooManyHookAccounts)
+        );
+
+        // Hook-less mints need nothing
+        assert!(check_hook_account_budget(&[], &[]).is_ok());
+    }
+}
@@ -6,6 +6,7 @@ import {
   Keypair,
   SystemProgram,
   Transaction,
+  TransactionInstruction,
   sendAndConfirmTransaction,
 } from "@solana/web3.js";
 import {
@@ -18,6 +19,12 @@ import {
   createInitializeTransferFeeConfigInstruction,
   createInitializeMintInstruction,
   createAssociatedTokenAccountInstruction,
+  createAssociatedTokenAccountIdempotentInstruction,
+  createFreezeAccountInstruction,
+  createThawAccountInstruction,
+  createReallocateInstruction,
+  createEnableCpiGuardInstruction,
+  createDisableCpiGuardInstruction,
   createMintToInstruction,
   createTransferCheckedWithTransferHookInstruction,
   getAssociatedTokenAddressSync,
@@ -28,9 +35,11 @@ import {
   NATIVE_MINT,
 } from "@solana/spl-token";
 import { BN } from "bn.js";
+import { assert } from "chai";
 
 // Import our dynamic fee hook program types
 import type { DynamicFeeHook } from "../target/types/dynamic_fee_hook";
+import type { FlashCallback } from "../target/types/flash_callback";
 
 /**
  * Complete AMM demonstration with Token-2022 transfer hooks
@@ -48,6 +57,7 @@ describe("AMM with Dynamic Fee Hook Integration", function() {
   anchor.setProvider(provider);
   const lokoSwapProgram = anchor.workspace.LokoSwap as Program<LokoSwap>;
   const dynamicFeeHookProgram = anchor.workspace.DynamicFeeHook as Program<DynamicFeeHook>;
+  const flashCallbackProgram = anchor.workspace.FlashCallback as Program<FlashCallback>;
   const connection = provider.connection;
   const payer = provider.wallet as anchor.Wallet;
   
@@ -57,6 +67,7 @@ describe("AMM with Dynamic Fee Hook Integration", function() {
 
   // Use our own deployed dynamic fee hook program
   const OUR_HOOK_PROGRAM = new PublicKey("69VddXVhzGRGh3oU6eKoWEoNMJC8RJX6by1SgcuQfPR9");
+  const BPF_LOADER_UPGRADEABLE_PROGRAM_ID = new PublicKey("BPFLoaderUpgradeab1e11111111111111111111111");
 
   // User accounts
   let userHookAccount: PublicKey;
@@ -77,6 +88,168 @@ describe("AMM with Dynamic Fee Hook Integration", function() {
   let vaultX: PublicKey;
   let vaultY: PublicKey;
 
+  // Vault and user balances touched by a swap
+  const swapBalances = async () => {
+    const amount = async (account: PublicKey) =>
+      (await getAccount(connection, account, undefined, TOKEN_2022_PROGRAM_ID)).amount;
+    return {
+      vaultX: await amount(vaultX),
+      vaultY: await amount(vaultY),
+      userX: await amount(userHookAccount),
+      userY: await amount(userStandardAccount),
+    };
+  };
+
+  // Token-2022 mint with `extensions`, set up by `initExtensions` before the mint itself
+  // The payer is mint and freeze authority
+  const createMint = async (
+    extensions: ExtensionType[] = [],
+    initExtensions: (mint: PublicKey) => TransactionInstruction[] = () => []
+  ): Promise<PublicKey> => {
+    const mint = Keypair.generate();
+    const mintLen = getMintLen(extensions);
+    await sendAndConfirmTransaction(
+      connection,
+      new Transaction().add(
+        SystemProgram.createAccount({
+          fromPubkey: payer.publicKey,
+          newAccountPubkey: mint.publicKey,
+          space: mintLen,
+          lamports: await connection.getMinimumBalanceForRentExemption(mintLen),
+          programId: TOKEN_2022_PROGRAM_ID,
+        }),
+        ...initExtensions(mint.publicKey),
+        createInitializeMintInstruction(mint.publicKey, 9, payer.publicKey, payer.publicKey, TOKEN_2022_PROGRAM_ID)
+      ),
+      [payer.payer, mint]
+    );
+    return mint.publicKey;
+  };
+
+  // Accounts of a new pool pairing `a` and `b` for the payer, the mints sorted into X and Y
+  const poolAccounts = (a: PublicKey, b: PublicKey) => {
+    const [mintX, mintY] = a.toBuffer().compare(b.toBuffer()) < 0 ? [a, b] : [b, a];
+    const seed = new BN(Date.now()).addn(Math.floor(Math.random() * 1_000_000));
+    const [config] = PublicKey.findProgramAddressSync(
+      [Buffer.from("config"), seed.toBuffer("be", 8)],
+      lokoSwapProgram.programId
+    );
+    const [lpMint] = PublicKey.findProgramAddressSync(
+      [Buffer.from("lp"), config.toBytes()],
+      lokoSwapProgram.programId
+    );
+    const ata = (mint: PublicKey, owner: PublicKey) =>
+      getAssociatedTokenAddressSync(mint, owner, true, TOKEN_2022_PROGRAM_ID);
+    return {
+      seed,
+      config,
+      mintX,
+      mintY,
+      mintLp: lpMint,
+      vaultX: ata(mintX, config),
+      vaultY: ata(mintY, config),
+      userX: ata(mintX, payer.publicKey),
+      userY: ata(mintY, payer.publicKey),
+      userLp: ata(lpMint, payer.publicKey),
+    };
+  };
+  type Pool = ReturnType<typeof poolAccounts>;
+
+  // Initialize `pool` with a 0.3% fee, no hook program and the payer as authority
+  const initializePool = (
+    pool: Pool,
+    options: { mintAllowlist?: PublicKey; passOutputFee?: boolean } = {}
+  ) =>
+    lokoSwapProgram.methods
+      .initialize(
+        pool.seed,
+        30,
+        payer.publicKey,
+        0,
+        new BN(0),
+        null,
+        0,
+        payer.publicKey,
+        null,
+        new BN(0),
+        options.mintAllowlist !== undefined,
+        options.passOutputFee ?? false
+      )
+      .accountsStrict({
+        admin: payer.publicKey,
+        mintX: pool.mintX,
+        mintY: pool.mintY,
+        mintLp: pool.mintLp,
+        vaultX: pool.vaultX,
+        vaultY: pool.vaultY,
+        config: pool.config,
+        mintAllowlist: options.mintAllowlist ?? null,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+        tokenProgramX: TOKEN_2022_PROGRAM_ID,
+        tokenProgramY: TOKEN_2022_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+
+  // Fund the payer's X and Y accounts of `pool` and make its first deposit
+  const seedPool = async (pool: Pool, amount = 2 * 10 ** 6) => {
+    const funding = new Transaction();
+    for (const [mint, account] of [[pool.mintX, pool.userX], [pool.mintY, pool.userY]]) {
+      funding.add(
+        createAssociatedTokenAccountIdempotentInstruction(
+          payer.publicKey,
+          account,
+          payer.publicKey,
+          mint,
+          TOKEN_2022_PROGRAM_ID
+        ),
+        createMintToInstruction(mint, account, payer.publicKey, 10 * 10 ** 9, [], TOKEN_2022_PROGRAM_ID)
+      );
+    }
+    await sendAndConfirmTransaction(connection, funding, [payer.payer]);
+
+    await lokoSwapProgram.methods
+      .deposit(new BN(amount / 2), new BN(amount), new BN(amount), new BN(0), new BN(0), false)
+      .accountsPartial({
+        user: payer.publicKey,
+        mintX: pool.mintX,
+        mintY: pool.mintY,
+        userX: pool.userX,
+        userY: pool.userY,
+        vaultX: pool.vaultX,
+        vaultY: pool.vaultY,
+        config: pool.config,
+        mintLp: pool.mintLp,
+        userLp: pool.userLp,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+  };
+
+  // Swap `amount` of X for Y on `pool`, or Y for X, requiring at least `min` out after fees
+  const swapOn = (pool: Pool, amount: number, isX: boolean, min = 1) =>
+    lokoSwapProgram.methods
+      .swap(new BN(amount), isX, new BN(min), 0, 0, false)
+      .accountsPartial({
+        user: payer.publicKey,
+        mintX: pool.mintX,
+        mintY: pool.mintY,
+        userX: pool.userX,
+        userY: pool.userY,
+        vaultX: pool.vaultX,
+        vaultY: pool.vaultY,
+        config: pool.config,
+        mintLp: pool.mintLp,
+        userLp: pool.userLp,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+
   before(() => {
     console.log("Testing AMM with Dynamic Fee Hook Integration");
     console.log("Dynamic fee hook program:", dynamicFeeHookProgram.programId.toString());
@@ -140,7 +313,10 @@ describe("AMM with Dynamic Fee Hook Integration", function() {
     it("Should create standard token and user accounts", async () => {
       console.log("Creating standard token and user accounts...");
 
-      standardMint = Keypair.generate();
+      // Pools require mint_x < mint_y by key bytes, and the hook mint is X here
+      do {
+        standardMint = Keypair.generate();
+      } while (standardMint.publicKey.toBuffer().compare(hookMint.publicKey.toBuffer()) <= 0);
       const decimals = 9;
       
       const mintLen = getMintLen([]);
@@ -237,11 +413,66 @@ describe("AMM with Dynamic Fee Hook Integration", function() {
       console.log(`   Standard token balance: ${standardBalance.amount.toString()}`);
     });
 
+    it("Should initialize the fee stats account as the upgrade authority only", async () => {
+      const [programData] = PublicKey.findProgramAddressSync(
+        [OUR_HOOK_PROGRAM.toBuffer()],
+        BPF_LOADER_UPGRADEABLE_PROGRAM_ID
+      );
+
+      // Anyone else is rejected, even while no authority is set
+      const intruder = Keypair.generate();
+      await sendAndConfirmTransaction(
+        connection,
+        new Transaction().add(
+          SystemProgram.transfer({
+            fromPubkey: payer.publicKey,
+            toPubkey: intruder.publicKey,
+            lamports: 0.01 * 10 ** 9,
+          })
+        ),
+        [payer.payer]
+      );
+      try {
+        await dynamicFeeHookProgram.methods
+          .initializeFeeStats()
+          .accounts({ authority: intruder.publicKey, programData })
+          .signers([intruder])
+          .rpc();
+        assert.fail("initialized by a key other than the upgrade authority");
+      } catch (err) {
+        assert.include(err.toString(), "InvalidAuthority");
+      }
+
+      // The test validator deploys with the provider wallet as upgrade authority
+      await dynamicFeeHookProgram.methods
+        .initializeFeeStats()
+        .accounts({ authority: payer.publicKey, programData })
+        .rpc();
+
+      const [feeStats] = PublicKey.findProgramAddressSync(
+        [Buffer.from("fee_stats")],
+        OUR_HOOK_PROGRAM
+      );
+      const stats = await dynamicFeeHookProgram.account.dynamicFeeStats.fetch(feeStats);
+      assert.isTrue(stats.authority.equals(payer.publicKey));
+
+      // Set exactly once
+      try {
+        await dynamicFeeHookProgram.methods
+          .initializeFeeStats()
+          .accounts({ authority: payer.publicKey, programData })
+          .rpc();
+        assert.fail("fee stats authority set twice");
+      } catch (err) {
+        assert.include(err.toString(), "AuthorityAlreadySet");
+      }
+    });
+
     it("Should initialize hook validation accounts for our token", async () => {
       console.log("Initializing hook validation accounts...");
 
       // Initialize the extra account metas for our hook token
-      // This now also creates the fee stats account
+      // The fee stats account was created by initializeFeeStats
       const initTx = await dynamicFeeHookProgram.methods
         .initializeExtraAccountMetaList()
         .accounts({
@@ -250,7 +481,6 @@ describe("AMM with Dynamic Fee Hook Integration", function() {
         .rpc();
 
       console.log("Hook validation accounts initialized:", initTx);
-      console.log("Fee stats account created for tracking AMM operations");
     });
 
     it("Should setup all required hook accounts including WSOL", async () => {
@@ -384,10 +614,16 @@ describe("AMM with Dynamic Fee Hook Integration", function() {
         .initialize(
           seed,
           fee,
-          null,
+          payer.publicKey, // authority, funds the hook fee below
+          0,
+          new BN(0),
+          OUR_HOOK_PROGRAM,
           0,
+          payer.publicKey,
+          null,
           new BN(0),
-          OUR_HOOK_PROGRAM
+          false,
+          false
         )
         .accountsStrict({
           admin: payer.publicKey,
@@ -397,6 +633,7 @@ describe("AMM with Dynamic Fee Hook Integration", function() {
           vaultX,
           vaultY,
           config: poolConfig,
+          mintAllowlist: null,
           tokenProgram: TOKEN_2022_PROGRAM_ID,
           tokenProgramX: TOKEN_2022_PROGRAM_ID,
           tokenProgramY: TOKEN_2022_PROGRAM_ID,
@@ -465,8 +702,10 @@ describe("AMM with Dynamic Fee Hook Integration", function() {
         { pubkey: OUR_HOOK_PROGRAM, isSigner: false, isWritable: false },              // Hook program ID
       ];
 
+      const statsBefore = await dynamicFeeHookProgram.account.dynamicFeeStats.fetch(feeStatsPDA);
+
       const depositTx = await lokoSwapProgram.methods
-        .deposit(depositAmount, maxX, maxY)
+        .deposit(depositAmount, maxX, maxY, depositAmount, new BN(0), false)
         .accountsPartial({
           user: payer.publicKey,
           mintX: hookMint.publicKey,
@@ -490,6 +729,13 @@ describe("AMM with Dynamic Fee Hook Integration", function() {
       const lpBalance = await getAccount(connection, userLpAccount, undefined, TOKEN_2022_PROGRAM_ID);
       console.log(`LP tokens received: ${lpBalance.amount.toString()}`);
 
+      // The hook counts every transfer it executes
+      const statsAfter = await dynamicFeeHookProgram.account.dynamicFeeStats.fetch(feeStatsPDA);
+      assert.isTrue(
+        statsAfter.totalTransfers.gt(statsBefore.totalTransfers),
+        "hook executed on deposit"
+      );
+
       console.log("Deposit with transfer hooks completed");
     });
 
@@ -512,8 +758,10 @@ describe("AMM with Dynamic Fee Hook Integration", function() {
         { pubkey: OUR_HOOK_PROGRAM, isSigner: false, isWritable: false },              // Hook program ID
       ];
 
+      const before = await swapBalances();
+
       const swapTx = await lokoSwapProgram.methods
-        .swap(swapAmount, true, minOut)  
+        .swap(swapAmount, true, minOut, 0, 0, false)  
         .accountsPartial({
           user: payer.publicKey,
           mintX: hookMint.publicKey,
@@ -534,9 +782,262 @@ describe("AMM with Dynamic Fee Hook Integration", function() {
 
       console.log("Swap transaction:", swapTx);
 
+      // X -> Y: vault X and user Y gain, vault Y and user X lose
+      const after = await swapBalances();
+      assert.isTrue(after.vaultX > before.vaultX, "vault X credited");
+      assert.isTrue(after.vaultY < before.vaultY, "vault Y debited");
+      assert.isTrue(after.userX < before.userX, "user X debited");
+      assert.isTrue(after.userY > before.userY, "user Y credited");
+
       console.log("Swap with transfer hooks completed");
     });
 
+    it("Should swap standard tokens for hook tokens in AMM", async () => {
+      const swapAmount = new BN(1 * 10 ** 5);
+      const minOut = new BN(1);
+
+      // The hook token now leaves the vault, so the config PDA's WSOL account pays the hook
+      const hookAccounts = [
+        { pubkey: extraAccountMetaListPDA, isSigner: false, isWritable: false },       // Extra metas list
+        { pubkey: NATIVE_MINT, isSigner: false, isWritable: false },                   // index 5 - WSOL mint
+        { pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },              // index 6 - Token program
+        { pubkey: ASSOCIATED_TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },   // index 7 - Associated token program
+        { pubkey: delegatePDA, isSigner: false, isWritable: true },                    // index 8 - Delegate PDA
+        { pubkey: delegateWSolAccount, isSigner: false, isWritable: true },            // index 9 - Delegate WSOL
+        { pubkey: ammPdaWSolAccount, isSigner: false, isWritable: true },              // index 10 - AMM PDA WSOL (vault authority)
+        { pubkey: feeStatsPDA, isSigner: false, isWritable: true },                    // index 11 - Fee stats PDA
+        { pubkey: OUR_HOOK_PROGRAM, isSigner: false, isWritable: false },              // Hook program ID
+      ];
+
+      const before = await swapBalances();
+
+      await lokoSwapProgram.methods
+        .swap(swapAmount, false, minOut, 0, 0, false)
+        .accountsPartial({
+          user: payer.publicKey,
+          mintX: hookMint.publicKey,
+          mintY: standardMint.publicKey,
+          userX: userHookAccount,
+          userY: userStandardAccount,
+          vaultX,
+          vaultY,
+          config: poolConfig,
+          mintLp,
+          userLp: userLpAccount,
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+        })
+        .remainingAccounts(hookAccounts)
+        .rpc();
+
+      // Y -> X: vault Y and user X gain, vault X and user Y lose
+      const after = await swapBalances();
+      assert.isTrue(after.vaultY > before.vaultY, "vault Y credited");
+      assert.isTrue(after.vaultX < before.vaultX, "vault X debited");
+      assert.isTrue(after.userY < before.userY, "user Y debited");
+      assert.isTrue(after.userX > before.userX, "user X credited");
+    });
+
+    it("Should charge the pool's WSOL account the dynamic fee on a hooked payout", async () => {
+      // 1 SOL per whole hook token, the fee is a share of the tokens paid out priced at that rate
+      const lamportsPerToken = new BN(10 ** 9);
+      await dynamicFeeHookProgram.methods
+        .setFeeRate(lamportsPerToken)
+        .accounts({ authority: payer.publicKey })
+        .rpc();
+
+      // The vault payout is sent by the config PDA, whose WSOL account pays the hook
+      await lokoSwapProgram.methods
+        .fundHookFee(new BN(0.01 * 10 ** 9))
+        .accountsPartial({
+          authority: payer.publicKey,
+          config: poolConfig,
+          wsolMint: NATIVE_MINT,
+          configWsol: ammPdaWSolAccount,
+          hookDelegate: delegatePDA,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+      const configWsol = await getAccount(connection, ammPdaWSolAccount);
+      assert.isTrue(configWsol.delegate.equals(delegatePDA), "hook delegate approved");
+      assert.equal(configWsol.delegatedAmount, configWsol.amount);
+
+      const hookAccounts = [
+        { pubkey: extraAccountMetaListPDA, isSigner: false, isWritable: false },
+        { pubkey: NATIVE_MINT, isSigner: false, isWritable: false },
+        { pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
+        { pubkey: ASSOCIATED_TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
+        { pubkey: delegatePDA, isSigner: false, isWritable: true },
+        { pubkey: delegateWSolAccount, isSigner: false, isWritable: true },
+        { pubkey: ammPdaWSolAccount, isSigner: false, isWritable: true },
+        { pubkey: feeStatsPDA, isSigner: false, isWritable: true },
+        { pubkey: OUR_HOOK_PROGRAM, isSigner: false, isWritable: false },
+      ];
+
+      const delegateBefore = (await getAccount(connection, delegateWSolAccount)).amount;
+      const statsBefore = await dynamicFeeHookProgram.account.dynamicFeeStats.fetch(feeStatsPDA);
+      const before = await swapBalances();
+
+      // Y -> X, the only hooked transfer is the payout of X from the vault
+      await lokoSwapProgram.methods
+        .swap(new BN(1 * 10 ** 5), false, new BN(1), 0, 0, false)
+        .accountsPartial({
+          user: payer.publicKey,
+          mintX: hookMint.publicKey,
+          mintY: standardMint.publicKey,
+          userX: userHookAccount,
+          userY: userStandardAccount,
+          vaultX,
+          vaultY,
+          config: poolConfig,
+          mintLp,
+          userLp: userLpAccount,
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+        })
+        .remainingAccounts(hookAccounts)
+        .rpc();
+
+      const after = await swapBalances();
+      const statsAfter = await dynamicFeeHookProgram.account.dynamicFeeStats.fetch(feeStatsPDA);
+      const delegateAfter = (await getAccount(connection, delegateWSolAccount)).amount;
+
+      // Charged at the fee the hook settled on for this transfer
+      const paidOut = new BN((before.vaultX - after.vaultX).toString());
+      const expectedFee = paidOut
+        .mul(new BN(statsAfter.currentFeeBasisPoints))
+        .mul(lamportsPerToken)
+        .div(new BN(10_000).mul(new BN(10 ** 9)));
+      assert.isTrue(expectedFee.gtn(0), "payout large enough to pay a fee");
+      assert.equal((delegateAfter - delegateBefore).toString(), expectedFee.toString());
+      assert.equal(
+        statsAfter.totalFeesCollected.sub(statsBefore.totalFeesCollected).toString(),
+        expectedFee.toString()
+      );
+      const configWsolAfter = await getAccount(connection, ammPdaWSolAccount);
+      assert.equal((configWsol.amount - configWsolAfter.amount).toString(), expectedFee.toString());
+    });
+
+    it("Should let a transfer through without the fee when the sender hasn't approved the delegate", async () => {
+      // A fresh holder with hook tokens and no WSOL account at all
+      const holder = Keypair.generate();
+      const holderAccount = getAssociatedTokenAddressSync(
+        hookMint.publicKey,
+        holder.publicKey,
+        false,
+        TOKEN_2022_PROGRAM_ID
+      );
+      await sendAndConfirmTransaction(
+        connection,
+        new Transaction().add(
+          createAssociatedTokenAccountInstruction(
+            payer.publicKey,
+            holderAccount,
+            holder.publicKey,
+            hookMint.publicKey,
+            TOKEN_2022_PROGRAM_ID
+          ),
+          createMintToInstruction(
+            hookMint.publicKey,
+            holderAccount,
+            payer.publicKey,
+            10 ** 6,
+            [],
+            TOKEN_2022_PROGRAM_ID
+          )
+        ),
+        [payer.payer]
+      );
+
+      const delegateBefore = (await getAccount(connection, delegateWSolAccount)).amount;
+      const statsBefore = await dynamicFeeHookProgram.account.dynamicFeeStats.fetch(feeStatsPDA);
+
+      const transferIx = await createTransferCheckedWithTransferHookInstruction(
+        connection,
+        holderAccount,
+        hookMint.publicKey,
+        userHookAccount,
+        holder.publicKey,
+        BigInt(10 ** 5),
+        9,
+        [],
+        "confirmed",
+        TOKEN_2022_PROGRAM_ID
+      );
+      await sendAndConfirmTransaction(connection, new Transaction().add(transferIx), [payer.payer, holder]);
+
+      // The transfer went through and the hook counted it, but charged nothing
+      const holderBalance = await getAccount(connection, holderAccount, undefined, TOKEN_2022_PROGRAM_ID);
+      assert.equal(holderBalance.amount.toString(), (10 ** 6 - 10 ** 5).toString());
+      const statsAfter = await dynamicFeeHookProgram.account.dynamicFeeStats.fetch(feeStatsPDA);
+      assert.equal(statsAfter.totalTransfers.sub(statsBefore.totalTransfers).toNumber(), 1);
+      assert.equal(statsAfter.totalFeesCollected.toString(), statsBefore.totalFeesCollected.toString());
+      const delegateAfter = (await getAccount(connection, delegateWSolAccount)).amount;
+      assert.equal(delegateAfter.toString(), delegateBefore.toString());
+    });
+
+    it("Should fail a swap that re-enters the pool from a flash loan callback", async () => {
+      // Y -> X swap the callback sends back into the pool while the loan is out
+      const swapIx = await lokoSwapProgram.methods
+        .swap(new BN(1 * 10 ** 5), false, new BN(1), 0, 0, false)
+        .accountsPartial({
+          user: payer.publicKey,
+          mintX: hookMint.publicKey,
+          mintY: standardMint.publicKey,
+          userX: userHookAccount,
+          userY: userStandardAccount,
+          vaultX,
+          vaultY,
+          config: poolConfig,
+          mintLp,
+          userLp: userLpAccount,
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+        })
+        .instruction();
+      const callbackData = flashCallbackProgram.coder.instruction.encode("reenter", {
+        data: swapIx.data,
+      });
+
+      const before = await swapBalances();
+
+      try {
+        await lokoSwapProgram.methods
+          .flashLoan(new BN(1 * 10 ** 5), callbackData)
+          .accountsPartial({
+            borrower: payer.publicKey,
+            config: poolConfig,
+            mint: standardMint.publicKey,
+            vault: vaultY,
+            borrowerTokenAccount: userStandardAccount,
+            callbackProgram: flashCallbackProgram.programId,
+            tokenProgram: TOKEN_2022_PROGRAM_ID,
+          })
+          .remainingAccounts([
+            { pubkey: lokoSwapProgram.programId, isSigner: false, isWritable: false },
+            ...swapIx.keys,
+          ])
+          .rpc();
+        assert.fail("swap re-entered the pool during a flash loan");
+      } catch (err) {
+        // The runtime refuses pool -> callback -> pool before the pool's own guard is reached
+        const logs = (err.logs ?? []).join("\n");
+        assert.match(err.toString() + logs, /reentrancy not allowed/i);
+      }
+
+      // Nothing moved and the pool still accepts calls once the loan failed
+      const after = await swapBalances();
+      assert.deepEqual(after, before);
+      const pool = await lokoSwapProgram.account.config.fetch(poolConfig);
+      assert.isFalse(pool.borrowing);
+      assert.isFalse(pool.lockedForReentrancy);
+    });
+
     it("Should withdraw hook tokens from AMM", async () => {
       console.log("Withdrawing hook tokens from AMM...");
 
@@ -561,7 +1062,7 @@ describe("AMM with Dynamic Fee Hook Integration", function() {
       ];
 
       const withdrawTx = await lokoSwapProgram.methods
-        .withdraw(withdrawAmount, minX, minY)
+        .withdraw(withdrawAmount, minX, minY, false)
         .accountsPartial({
           user: payer.publicKey,
           mintX: hookMint.publicKey,
@@ -595,6 +1096,202 @@ describe("AMM with Dynamic Fee Hook Integration", function() {
     });
   });
 
+  describe("Step 4: Pool guards", () => {
+    it("Should refuse to create a pool for a confidential transfer mint", async () => {
+      // ConfidentialTransferExtension (27) InitializeMint (0): no authority, no auto-approve, no auditor
+      const confidentialMint = await createMint([ExtensionType.ConfidentialTransferMint], (mint) => [
+        new TransactionInstruction({
+          programId: TOKEN_2022_PROGRAM_ID,
+          keys: [{ pubkey: mint, isSigner: false, isWritable: true }],
+          data: Buffer.concat([Buffer.from([27, 0]), Buffer.alloc(32), Buffer.from([0]), Buffer.alloc(32)]),
+        }),
+      ]);
+      const pool = poolAccounts(confidentialMint, await createMint());
+
+      try {
+        await initializePool(pool);
+        assert.fail("pool created for a confidential transfer mint");
+      } catch (err) {
+        assert.include(err.toString(), "UnsupportedExtension");
+      }
+
+      // Nothing was left behind
+      assert.isNull(await connection.getAccountInfo(pool.config));
+      assert.isNull(await connection.getAccountInfo(pool.mintLp));
+    });
+
+    it("Should only create permissioned pools for allowlisted mints", async () => {
+      const [mintAllowlist] = PublicKey.findProgramAddressSync(
+        [Buffer.from("allowlist"), payer.publicKey.toBuffer()],
+        lokoSwapProgram.programId
+      );
+      if ((await connection.getAccountInfo(mintAllowlist)) === null) {
+        await lokoSwapProgram.methods
+          .createAllowlist()
+          .accountsPartial({ authority: payer.publicKey, mintAllowlist })
+          .rpc();
+      }
+
+      const [allowedA, allowedB, unlisted] = [await createMint(), await createMint(), await createMint()];
+      for (const mint of [allowedA, allowedB]) {
+        await lokoSwapProgram.methods
+          .addAllowedMint(mint)
+          .accountsPartial({ authority: payer.publicKey, mintAllowlist })
+          .rpc();
+      }
+
+      // One side off the allowlist is enough to be rejected
+      const rejected = poolAccounts(allowedA, unlisted);
+      try {
+        await initializePool(rejected, { mintAllowlist });
+        assert.fail("permissioned pool created with an unlisted mint");
+      } catch (err) {
+        assert.include(err.toString(), "MintNotAllowed");
+      }
+      assert.isNull(await connection.getAccountInfo(rejected.config));
+
+      const allowed = poolAccounts(allowedA, allowedB);
+      await initializePool(allowed, { mintAllowlist });
+      const pool = await lokoSwapProgram.account.config.fetch(allowed.config);
+      assert.isTrue(pool.permissioned);
+      assert.isTrue(pool.mintAllowlist.equals(mintAllowlist));
+    });
+
+    it("Should fail swaps with VaultFrozen while a vault is frozen", async () => {
+      const pool = poolAccounts(await createMint(), await createMint());
+      await initializePool(pool);
+      await seedPool(pool);
+
+      // The payer is the mints' freeze authority
+      await sendAndConfirmTransaction(
+        connection,
+        new Transaction().add(
+          createFreezeAccountInstruction(pool.vaultY, pool.mintY, payer.publicKey, [], TOKEN_2022_PROGRAM_ID)
+        ),
+        [payer.payer]
+      );
+
+      const vaultXBefore = (await getAccount(connection, pool.vaultX, undefined, TOKEN_2022_PROGRAM_ID)).amount;
+      try {
+        await swapOn(pool, 10 ** 5, true);
+        assert.fail("swap went through with a frozen vault");
+      } catch (err) {
+        assert.include(err.toString(), "VaultFrozen");
+      }
+      const vaultXAfter = (await getAccount(connection, pool.vaultX, undefined, TOKEN_2022_PROGRAM_ID)).amount;
+      assert.equal(vaultXAfter.toString(), vaultXBefore.toString());
+
+      // Thawed, the same swap goes through
+      await sendAndConfirmTransaction(
+        connection,
+        new Transaction().add(
+          createThawAccountInstruction(pool.vaultY, pool.mintY, payer.publicKey, [], TOKEN_2022_PROGRAM_ID)
+        ),
+        [payer.payer]
+      );
+      await swapOn(pool, 10 ** 5, true);
+    });
+
+    it("Should fail swaps from an account with CPI Guard with CpiGuardEnabled", async () => {
+      const pool = poolAccounts(await createMint(), await createMint());
+      await initializePool(pool);
+      await seedPool(pool);
+
+      // The ATA has no room for CPI Guard until it is reallocated
+      await sendAndConfirmTransaction(
+        connection,
+        new Transaction().add(
+          createReallocateInstruction(
+            pool.userX,
+            payer.publicKey,
+            [ExtensionType.CpiGuard],
+            payer.publicKey,
+            [],
+            TOKEN_2022_PROGRAM_ID
+          ),
+          createEnableCpiGuardInstruction(pool.userX, payer.publicKey, [], TOKEN_2022_PROGRAM_ID)
+        ),
+        [payer.payer]
+      );
+
+      // The owner signs the transfer into the vault through the pool, which CPI Guard forbids
+      const userXBefore = (await getAccount(connection, pool.userX, undefined, TOKEN_2022_PROGRAM_ID)).amount;
+      try {
+        await swapOn(pool, 10 ** 5, true);
+        assert.fail("swap went through from an account with CPI Guard");
+      } catch (err) {
+        assert.include(err.toString(), "CpiGuardEnabled");
+      }
+      const userXAfter = (await getAccount(connection, pool.userX, undefined, TOKEN_2022_PROGRAM_ID)).amount;
+      assert.equal(userXAfter.toString(), userXBefore.toString());
+
+      // With the guard off the same swap goes through
+      await sendAndConfirmTransaction(
+        connection,
+        new Transaction().add(
+          createDisableCpiGuardInstruction(pool.userX, payer.publicKey, [], TOKEN_2022_PROGRAM_ID)
+        ),
+        [payer.payer]
+      );
+      await swapOn(pool, 10 ** 5, true);
+    });
+
+    it("Should absorb or pass on the output mint's transfer fee as the pool chose", async () => {
+      const feeBps = 100;
+      const feeMint = await createMint([ExtensionType.TransferFeeConfig], (mint) => [
+        createInitializeTransferFeeConfigInstruction(
+          mint,
+          payer.publicKey,
+          payer.publicKey,
+          feeBps,
+          BigInt(10 ** 9),
+          TOKEN_2022_PROGRAM_ID
+        ),
+      ]);
+      const plainMint = await createMint();
+
+      // Two pools in the same state, one of each mode
+      const absorbing = poolAccounts(feeMint, plainMint);
+      const passing = poolAccounts(feeMint, plainMint);
+      await initializePool(absorbing);
+      await initializePool(passing, { passOutputFee: true });
+      await seedPool(absorbing);
+      await seedPool(passing);
+
+      // Swap the plain mint for the fee mint, reporting what left the vault and what the user got
+      const isX = feeMint.equals(absorbing.mintY);
+      const balance = async (account: PublicKey) =>
+        (await getAccount(connection, account, undefined, TOKEN_2022_PROGRAM_ID)).amount;
+      const swapForFeeMint = async (pool: Pool, min = 1) => {
+        const [vault, user] = isX ? [pool.vaultY, pool.userY] : [pool.vaultX, pool.userX];
+        const [vaultBefore, userBefore] = [await balance(vault), await balance(user)];
+        await swapOn(pool, 10 ** 5, isX, min);
+        return { gross: vaultBefore - (await balance(vault)), net: (await balance(user)) - userBefore };
+      };
+      const feeOn = (amount: bigint) => (amount * BigInt(feeBps) + BigInt(9_999)) / BigInt(10_000);
+
+      // Absorbing, the vault grosses the payout up so the user nets the curve output
+      const absorbed = await swapForFeeMint(absorbing);
+      assert.isTrue(absorbed.gross > absorbed.net, "pool paid the transfer fee");
+      assert.equal((absorbed.gross - absorbed.net).toString(), feeOn(absorbed.gross).toString());
+      const curveOut = absorbed.net;
+
+      // Passing it on, `min` applies to the net, so the curve output itself is out of reach
+      try {
+        await swapForFeeMint(passing, Number(curveOut));
+        assert.fail("min checked against the amount before the transfer fee");
+      } catch (err) {
+        assert.include(err.toString(), "SlippageExceeded");
+      }
+
+      // The vault pays the curve output and the user bears the fee
+      const netOut = curveOut - feeOn(curveOut);
+      const passed = await swapForFeeMint(passing, Number(netOut));
+      assert.equal(passed.gross.toString(), curveOut.toString());
+      assert.equal(passed.net.toString(), netOut.toString());
+    });
+  });
+
   after(() => {
     console.log("AMM with dynamic fee hook integration test completed");
   });